@@ -11,17 +11,38 @@ pub mod shared {
 #[doc(hidden)]
 pub mod prelude {
     pub use crate::macros::*;
-    pub use ngyn_hyper::{HyperApplication, HyperConfig};
+    #[cfg(feature = "tls")]
+    pub use ngyn_hyper::TlsConfig;
+    pub use ngyn_hyper::{HyperApplication, HyperConfig, Listener, Protocol};
+    #[cfg(feature = "jwt")]
+    pub use ngyn_shared::auth::jwt::{Claims, JwtAuth, JwtGate};
     pub use ngyn_shared::{
+        auth::{
+            basic::{BasicAuth, BasicAuthGate, BasicUser},
+            policy::{AuthChallenge, Authorize, Policy, Principal, ResourcePolicy, RoleHierarchy},
+        },
         core::{
+            container::{Inject, Lifetime},
             engine::{NgynEngine, NgynHttpEngine},
+            gate::{AllOf, AnyOf, GateOutcome, Not},
             handler::*,
         },
+        middlewares::{
+            cache::{CacheMiddleware, CachePolicy},
+            compression::CompressionMiddleware,
+            cors::CorsMiddleware,
+            csrf::{CsrfMiddleware, CsrfToken},
+            etag::{ETagMiddleware, Preconditions},
+            rate_limit::{RateLimitKey, RateLimiter},
+            session::Session,
+            static_files::StaticFiles,
+        },
         server::{
-            Body, JsonResponse, JsonResult, NgynContext, NgynRequest, NgynResponse, Param, Query,
-            ToBytes, Transducer,
+            Body, ConnectInfo, Cookie, CookieJar, Extension, JsonResponse, JsonResult, NgynContext,
+            NgynRequest, NgynResponse, Param, Query, SameSite, State, ToBytes, Transducer,
+            TrustedProxies,
         },
-        NgynGate, NgynMiddleware,
+        Middleware, NgynGate, NgynMiddleware,
     };
 }
 
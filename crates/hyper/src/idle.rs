@@ -0,0 +1,167 @@
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Instant;
+
+/// Tracks the activity of a connection, to close it once it has been idle for too long.
+///
+/// A connection is idle when it has no request in flight, and nothing has been read from or written to it.
+#[derive(Clone)]
+pub(crate) struct Idle {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    start: Instant,
+    /// The time of the last activity, in milliseconds since `start`.
+    last_activity: AtomicU64,
+    in_flight: AtomicUsize,
+}
+
+impl Idle {
+    pub(crate) fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                start: Instant::now(),
+                last_activity: AtomicU64::new(0),
+                in_flight: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Records activity on the connection.
+    fn touch(&self) {
+        let elapsed = self.inner.start.elapsed().as_millis() as u64;
+        self.inner.last_activity.store(elapsed, Ordering::Relaxed);
+    }
+
+    /// Marks a request as in flight, until the returned guard is dropped.
+    pub(crate) fn request(&self) -> RequestGuard {
+        self.inner.in_flight.fetch_add(1, Ordering::Relaxed);
+        RequestGuard { idle: self.clone() }
+    }
+
+    /// Completes once the connection has been idle for `timeout`.
+    pub(crate) async fn expired(&self, timeout: Duration) {
+        loop {
+            let last_activity =
+                Duration::from_millis(self.inner.last_activity.load(Ordering::Relaxed));
+            let deadline = self.inner.start + last_activity + timeout;
+
+            if Instant::now() >= deadline && self.inner.in_flight.load(Ordering::Relaxed) == 0 {
+                return;
+            }
+            // requests in flight are bounded by the request timeout, check again later
+            tokio::time::sleep_until(deadline.max(Instant::now() + timeout)).await;
+        }
+    }
+
+    /// Wraps an IO object, to record its reads and writes as activity.
+    pub(crate) fn wrap<I>(&self, io: I) -> IdleIo<I> {
+        IdleIo {
+            io,
+            idle: self.clone(),
+        }
+    }
+}
+
+/// Keeps a request in flight, see [`Idle::request`].
+pub(crate) struct RequestGuard {
+    idle: Idle,
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        self.idle.touch();
+        self.idle.inner.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// An IO object whose reads and writes are recorded as activity of a connection.
+pub(crate) struct IdleIo<I> {
+    io: I,
+    idle: Idle,
+}
+
+impl<I> IdleIo<I> {
+    fn record<T>(&self, poll: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        if poll.is_ready() {
+            self.idle.touch();
+        }
+        poll
+    }
+}
+
+impl<I: AsyncRead + Unpin> AsyncRead for IdleIo<I> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.io).poll_read(cx, buf);
+        this.record(poll)
+    }
+}
+
+impl<I: AsyncWrite + Unpin> AsyncWrite for IdleIo<I> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.io).poll_write(cx, buf);
+        this.record(poll)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.io).poll_write_vectored(cx, bufs);
+        this.record(poll)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expired() {
+        let idle = Idle::new();
+        let timeout = Duration::from_secs(5);
+
+        let guard = idle.request();
+        // a request in flight keeps the connection alive
+        assert!(
+            tokio::time::timeout(Duration::from_secs(12), idle.expired(timeout))
+                .await
+                .is_err()
+        );
+
+        drop(guard);
+        let start = Instant::now();
+        idle.expired(timeout).await;
+        assert!(start.elapsed() >= timeout);
+    }
+}
@@ -1,17 +1,50 @@
-use http_body_util::BodyExt;
-use hyper::body::Incoming;
-use hyper::server::conn::http1;
-use hyper::{service::service_fn, Request};
-use hyper_util::rt::TokioIo;
-use hyper_util::server::graceful::GracefulShutdown;
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{HeaderValue, CONNECTION};
+use hyper::{service::service_fn, Request, Response, StatusCode};
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::server::graceful::{GracefulShutdown, Watcher};
+use idle::Idle;
 use ngyn_shared::core::engine::{NgynHttpPlatform, PlatformData};
-use ngyn_shared::server::NgynResponse;
+use ngyn_shared::core::lifecycle::Hooks;
+use ngyn_shared::server::response::ResponseStream;
+use ngyn_shared::server::{ConnectInfo, NgynResponse};
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::net::TcpListener;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::ToSocketAddrs;
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+
+mod idle;
+mod listener;
+#[cfg(feature = "tls")]
+mod tls;
+
+pub use listener::{ListenAddr, Listener};
+
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+
+/// The HTTP protocol versions served by an [`HyperApplication`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Protocol {
+    /// Serve HTTP/1 only.
+    #[default]
+    Http1,
+    /// Serve HTTP/2 only, clients must connect with prior knowledge (h2c).
+    Http2,
+    /// Serve both HTTP/1 and HTTP/2, the version is detected from the first bytes of each connection.
+    Auto,
+}
 
-#[derive(Default)]
 /// Configure an [`HyperApplication`]
 pub struct HyperConfig {
+    protocol: Protocol,
     h1_half_close: bool,
     h1_keep_alive: bool,
     h1_title_case_headers: bool,
@@ -19,6 +52,262 @@ pub struct HyperConfig {
     h1_max_headers: Option<usize>,
     max_buf_size: Option<usize>,
     pipeline_flush: bool,
+    h2_max_concurrent_streams: Option<u32>,
+    h2_initial_stream_window_size: Option<u32>,
+    h2_initial_connection_window_size: Option<u32>,
+    h2_adaptive_window: bool,
+    h2_max_frame_size: Option<u32>,
+    h2_max_header_list_size: Option<u32>,
+    h2_keep_alive_interval: Option<Duration>,
+    h2_keep_alive_timeout: Option<Duration>,
+    max_connections: Option<usize>,
+    header_read_timeout: Option<Duration>,
+    body_read_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    shutdown_timeout: Duration,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConfig>,
+}
+
+impl Default for HyperConfig {
+    fn default() -> Self {
+        Self {
+            protocol: Protocol::default(),
+            h1_half_close: false,
+            h1_keep_alive: false,
+            h1_title_case_headers: false,
+            h1_preserve_header_case: false,
+            h1_max_headers: None,
+            max_buf_size: None,
+            pipeline_flush: false,
+            h2_max_concurrent_streams: None,
+            h2_initial_stream_window_size: None,
+            h2_initial_connection_window_size: None,
+            h2_adaptive_window: false,
+            h2_max_frame_size: None,
+            h2_max_header_list_size: None,
+            h2_keep_alive_interval: None,
+            h2_keep_alive_timeout: None,
+            max_connections: None,
+            header_read_timeout: Some(Duration::from_secs(30)),
+            body_read_timeout: None,
+            request_timeout: None,
+            idle_timeout: None,
+            shutdown_timeout: Duration::from_secs(10),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+impl HyperConfig {
+    /// Sets the HTTP protocol versions to serve. Defaults to [`Protocol::Http1`].
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Sets whether HTTP/1 connections should support half-closures.
+    pub fn h1_half_close(mut self, enabled: bool) -> Self {
+        self.h1_half_close = enabled;
+        self
+    }
+
+    /// Sets whether HTTP/1 connections should be kept alive between requests.
+    pub fn h1_keep_alive(mut self, enabled: bool) -> Self {
+        self.h1_keep_alive = enabled;
+        self
+    }
+
+    /// Sets whether HTTP/1 response headers should be written in title case.
+    pub fn h1_title_case_headers(mut self, enabled: bool) -> Self {
+        self.h1_title_case_headers = enabled;
+        self
+    }
+
+    /// Sets whether the case of HTTP/1 headers should be preserved.
+    pub fn h1_preserve_header_case(mut self, enabled: bool) -> Self {
+        self.h1_preserve_header_case = enabled;
+        self
+    }
+
+    /// Sets the maximum number of headers of an HTTP/1 request.
+    pub fn h1_max_headers(mut self, max: usize) -> Self {
+        self.h1_max_headers = Some(max);
+        self
+    }
+
+    /// Sets the maximum size of the read buffer of a connection.
+    pub fn max_buf_size(mut self, max: usize) -> Self {
+        self.max_buf_size = Some(max);
+        self
+    }
+
+    /// Sets whether HTTP/1 responses should be aggregated and flushed together when pipelining.
+    pub fn pipeline_flush(mut self, enabled: bool) -> Self {
+        self.pipeline_flush = enabled;
+        self
+    }
+
+    /// Sets the maximum number of concurrent streams of an HTTP/2 connection.
+    pub fn h2_max_concurrent_streams(mut self, max: u32) -> Self {
+        self.h2_max_concurrent_streams = Some(max);
+        self
+    }
+
+    /// Sets the initial flow control window size of HTTP/2 streams, in bytes.
+    pub fn h2_initial_stream_window_size(mut self, size: u32) -> Self {
+        self.h2_initial_stream_window_size = Some(size);
+        self
+    }
+
+    /// Sets the initial flow control window size of HTTP/2 connections, in bytes.
+    pub fn h2_initial_connection_window_size(mut self, size: u32) -> Self {
+        self.h2_initial_connection_window_size = Some(size);
+        self
+    }
+
+    /// Sets whether HTTP/2 flow control windows should adapt to the bandwidth of the connection.
+    ///
+    /// When enabled, the initial window sizes are ignored.
+    pub fn h2_adaptive_window(mut self, enabled: bool) -> Self {
+        self.h2_adaptive_window = enabled;
+        self
+    }
+
+    /// Sets the maximum size of the frames of an HTTP/2 connection, in bytes.
+    pub fn h2_max_frame_size(mut self, size: u32) -> Self {
+        self.h2_max_frame_size = Some(size);
+        self
+    }
+
+    /// Sets the maximum size of the headers of an HTTP/2 request, in bytes.
+    pub fn h2_max_header_list_size(mut self, size: u32) -> Self {
+        self.h2_max_header_list_size = Some(size);
+        self
+    }
+
+    /// Sets the interval at which HTTP/2 keep-alive pings are sent, pings are disabled by default.
+    pub fn h2_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.h2_keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Sets how long to wait for the acknowledgement of an HTTP/2 keep-alive ping before closing the connection.
+    ///
+    /// Only used when [`HyperConfig::h2_keep_alive_interval`] is set.
+    pub fn h2_keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.h2_keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum number of connections served concurrently.
+    ///
+    /// Once the limit is reached, new connections wait in the listen backlog of the OS until a connection closes.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Sets how long clients are given to send the headers of an HTTP/1 request, `None` disables the timeout.
+    ///
+    /// The connection is closed when the timeout elapses, this protects against clients sending headers slowly.
+    /// With TLS, it also bounds the handshake, which must complete before the headers are read.
+    /// Defaults to 30 seconds.
+    pub fn header_read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.header_read_timeout = timeout;
+        self
+    }
+
+    /// Sets how long clients are given to send the body of a request.
+    ///
+    /// Requests whose body isn't received within the timeout are answered with `408 Request Timeout`.
+    pub fn body_read_timeout(mut self, timeout: Duration) -> Self {
+        self.body_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long the application is given to handle a request, once its body has been received.
+    ///
+    /// Requests that aren't handled within the timeout are answered with `503 Service Unavailable`.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long a connection is kept open without requests in flight or any activity.
+    ///
+    /// This bounds how long keep-alive connections are kept open between requests.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long in-flight connections are given to complete once a shutdown has started.
+    ///
+    /// Connections still open when the timeout elapses are dropped. Defaults to 10 seconds.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Serves the application over TLS.
+    ///
+    /// The protocols offered through ALPN follow [`HyperConfig::protocol`].
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Creates a connection builder for the configured protocol.
+    fn builder(&self) -> Builder<TokioExecutor> {
+        let mut builder = Builder::new(TokioExecutor::new());
+        builder = match self.protocol {
+            Protocol::Http1 => builder.http1_only(),
+            Protocol::Http2 => builder.http2_only(),
+            Protocol::Auto => builder,
+        };
+
+        let mut http1 = builder.http1();
+        http1
+            .timer(TokioTimer::new())
+            .header_read_timeout(self.header_read_timeout)
+            .half_close(self.h1_half_close)
+            .keep_alive(self.h1_keep_alive)
+            .title_case_headers(self.h1_title_case_headers)
+            .preserve_header_case(self.h1_preserve_header_case)
+            .pipeline_flush(self.pipeline_flush);
+
+        if let Some(buff_size) = self.max_buf_size {
+            http1.max_buf_size(buff_size);
+        }
+
+        if let Some(max_headers) = self.h1_max_headers {
+            http1.max_headers(max_headers);
+        }
+
+        let mut http2 = builder.http2();
+        http2
+            .timer(TokioTimer::new())
+            .adaptive_window(self.h2_adaptive_window)
+            .max_concurrent_streams(self.h2_max_concurrent_streams)
+            .initial_stream_window_size(self.h2_initial_stream_window_size)
+            .initial_connection_window_size(self.h2_initial_connection_window_size)
+            .max_frame_size(self.h2_max_frame_size)
+            .keep_alive_interval(self.h2_keep_alive_interval);
+
+        if let Some(timeout) = self.h2_keep_alive_timeout {
+            http2.keep_alive_timeout(timeout);
+        }
+
+        if let Some(size) = self.h2_max_header_list_size {
+            http2.max_header_list_size(size);
+        }
+
+        builder
+    }
 }
 
 /// Represents a Hyper-based application.
@@ -41,7 +330,11 @@ impl HyperApplication {
             config,
         }
     }
-    /// Listens for incoming connections and serves the application.
+
+    /// Listens for incoming connections and serves the application, until `Ctrl-C` is pressed.
+    ///
+    /// Startup hooks run before the address is bound, so connections are only accepted once the application is ready.
+    /// Shutdown hooks run once in-flight connections have been closed.
     ///
     /// ### Arguments
     ///
@@ -50,76 +343,404 @@ impl HyperApplication {
     /// ### Returns
     ///
     /// A `Result` indicating success or failure.
-    pub async fn listen<A: tokio::net::ToSocketAddrs>(
+    /// Fails if the registered services can't be constructed, if a startup or shutdown hook fails,
+    /// or if the address can't be bound.
+    pub async fn listen<A: ToSocketAddrs>(self, address: A) -> Result<(), std::io::Error> {
+        self.listen_with_shutdown(address, ctrl_c()).await
+    }
+
+    /// Listens for incoming connections and serves the application, until the `signal` future completes.
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - The address to listen on.
+    /// * `signal` - A future that completes when the application should shut down.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use tokio::signal::unix::{signal, SignalKind};
+    ///
+    /// let mut sigterm = signal(SignalKind::terminate())?;
+    /// app.listen_with_shutdown("0.0.0.0:8080", async move {
+    ///     sigterm.recv().await;
+    /// })
+    /// .await?;
+    /// ```
+    pub async fn listen_with_shutdown<A: ToSocketAddrs>(
         self,
         address: A,
+        signal: impl Future<Output = ()> + Send,
     ) -> Result<(), std::io::Error> {
-        let server = TcpListener::bind(address).await?;
-        let data = Arc::new(self.data);
+        self.bind(async { Ok(vec![Listener::bind(address).await?]) })
+            .await?
+            .run(signal)
+            .await
+    }
 
-        let mut http1 = http1::Builder::new();
+    /// Serves the application on existing listeners, until `Ctrl-C` is pressed.
+    ///
+    /// ### Arguments
+    ///
+    /// * `listeners` - The listeners to accept connections from, connections from all of them are served simultaneously.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn_hyper::Listener;
+    ///
+    /// app.listen_on([
+    ///     Listener::bind("0.0.0.0:8080").await?,
+    ///     Listener::bind_unix("/run/app.sock")?,
+    /// ])
+    /// .await?;
+    /// ```
+    pub async fn listen_on<L>(self, listeners: L) -> Result<(), std::io::Error>
+    where
+        L: IntoIterator<Item = Listener>,
+    {
+        self.listen_on_with_shutdown(listeners, ctrl_c()).await
+    }
 
-        http1
-            .half_close(self.config.h1_half_close)
-            .keep_alive(self.config.h1_keep_alive)
-            .title_case_headers(self.config.h1_title_case_headers)
-            .preserve_header_case(self.config.h1_preserve_header_case)
-            .pipeline_flush(self.config.pipeline_flush);
+    /// Serves the application on existing listeners, until the `signal` future completes.
+    ///
+    /// ### Arguments
+    ///
+    /// * `listeners` - The listeners to accept connections from, connections from all of them are served simultaneously.
+    /// * `signal` - A future that completes when the application should shut down.
+    pub async fn listen_on_with_shutdown<L>(
+        self,
+        listeners: L,
+        signal: impl Future<Output = ()> + Send,
+    ) -> Result<(), std::io::Error>
+    where
+        L: IntoIterator<Item = Listener>,
+    {
+        let listeners = listeners.into_iter().collect();
+        self.bind(async { Ok(listeners) }).await?.run(signal).await
+    }
 
-        if let Some(buff_size) = self.config.max_buf_size {
-            http1.max_buf_size(buff_size);
-        }
+    /// Starts serving the application in the background, and returns a handle to control it.
+    ///
+    /// Unlike [`HyperApplication::listen`], the server doesn't shut down on `Ctrl-C`,
+    /// it runs until [`ServerHandle::shutdown`] is called.
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - The address to listen on, use port `0` to pick any available port.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// let handle = app.serve("127.0.0.1:0").await?;
+    /// let url = format!("http://{}/", handle.local_addr());
+    /// // send requests to the server
+    /// handle.shutdown().await?;
+    /// ```
+    pub async fn serve<A: ToSocketAddrs>(self, address: A) -> Result<ServerHandle, std::io::Error> {
+        let server = self
+            .bind(async { Ok(vec![Listener::bind(address).await?]) })
+            .await?;
+        Self::spawn(server)
+    }
 
-        if let Some(max_headers) = self.config.h1_max_headers {
-            http1.max_headers(max_headers);
+    /// Starts serving the application on existing listeners in the background, and returns a handle to control it.
+    ///
+    /// ### Arguments
+    ///
+    /// * `listeners` - The listeners to accept connections from, connections from all of them are served simultaneously.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn_hyper::Listener;
+    ///
+    /// // bind port 0 beforehand, to read back the chosen port
+    /// let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    /// let port = listener.local_addr()?.port();
+    ///
+    /// let handle = app.serve_on([Listener::try_from(listener)?]).await?;
+    /// ```
+    pub async fn serve_on<L>(self, listeners: L) -> Result<ServerHandle, std::io::Error>
+    where
+        L: IntoIterator<Item = Listener>,
+    {
+        let listeners = listeners.into_iter().collect();
+        let server = self.bind(async { Ok(listeners) }).await?;
+        Self::spawn(server)
+    }
+
+    /// Runs the server in a background task, until the returned handle shuts it down.
+    fn spawn(server: Server) -> Result<ServerHandle, std::io::Error> {
+        let local_addrs = server
+            .listeners
+            .iter()
+            .map(Listener::local_addr)
+            .collect::<Result<_, _>>()?;
+        let (shutdown, signal) = oneshot::channel::<()>();
+
+        let task = tokio::spawn(server.run(async {
+            // a dropped handle also stops the server
+            let _ = signal.await;
+        }));
+
+        Ok(ServerHandle {
+            local_addrs,
+            shutdown,
+            task,
+        })
+    }
+
+    /// Prepares the application and binds its listeners, without accepting connections.
+    ///
+    /// `listeners` is awaited once startup hooks have run.
+    async fn bind<F>(mut self, listeners: F) -> Result<Server, std::io::Error>
+    where
+        F: Future<Output = Result<Vec<Listener>, std::io::Error>>,
+    {
+        self.data
+            .validate()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+        let shutdown_hooks = self.data.take_shutdown_hooks();
+        self.data
+            .take_startup_hooks()
+            .run()
+            .await
+            .map_err(std::io::Error::other)?;
+
+        let listeners = listeners.await?;
+        if listeners.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "no listeners to serve the application on",
+            ));
         }
 
+        #[cfg(feature = "tls")]
+        let tls = self
+            .config
+            .tls
+            .as_ref()
+            .map(|tls| tls.acceptor(self.config.protocol))
+            .transpose()?;
+
+        Ok(Server {
+            listeners,
+            data: Arc::new(self.data),
+            builder: self.config.builder(),
+            #[cfg(feature = "tls")]
+            tls,
+            connection_limit: self
+                .config
+                .max_connections
+                .map(|max| Arc::new(Semaphore::new(max))),
+            timeouts: Timeouts {
+                #[cfg(feature = "tls")]
+                handshake: self.config.header_read_timeout,
+                body_read: self.config.body_read_timeout,
+                request: self.config.request_timeout,
+                idle: self.config.idle_timeout,
+            },
+            shutdown_timeout: self.config.shutdown_timeout,
+            shutdown_hooks,
+        })
+    }
+}
+
+/// A handle to an application served in the background, returned by [`HyperApplication::serve`].
+pub struct ServerHandle {
+    local_addrs: Vec<ListenAddr>,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<Result<(), std::io::Error>>,
+}
+
+impl ServerHandle {
+    /// Returns the address of the first TCP listener the server is listening on.
+    ///
+    /// # Panics
+    /// Panics if the server doesn't listen on TCP, use [`ServerHandle::local_addrs`] instead.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addrs
+            .iter()
+            .find_map(|addr| match addr {
+                ListenAddr::Tcp(addr) => Some(*addr),
+                #[cfg(unix)]
+                ListenAddr::Unix(_) => None,
+            })
+            .expect("the server doesn't listen on TCP")
+    }
+
+    /// Returns the addresses of all the listeners the server is listening on.
+    pub fn local_addrs(&self) -> &[ListenAddr] {
+        &self.local_addrs
+    }
+
+    /// Shuts the server down, and waits for in-flight connections to complete and shutdown hooks to run.
+    pub async fn shutdown(self) -> Result<(), std::io::Error> {
+        let _ = self.shutdown.send(());
+        self.task.await.map_err(std::io::Error::other)?
+    }
+}
+
+struct Server {
+    listeners: Vec<Listener>,
+    data: Arc<PlatformData>,
+    builder: Builder<TokioExecutor>,
+    #[cfg(feature = "tls")]
+    tls: Option<tls::Tls>,
+    connection_limit: Option<Arc<Semaphore>>,
+    timeouts: Timeouts,
+    shutdown_timeout: Duration,
+    shutdown_hooks: Hooks,
+}
+
+/// The timeouts applied to each connection of a server.
+#[derive(Clone, Copy)]
+struct Timeouts {
+    #[cfg(feature = "tls")]
+    handshake: Option<Duration>,
+    body_read: Option<Duration>,
+    request: Option<Duration>,
+    idle: Option<Duration>,
+}
+
+impl Server {
+    /// Waits for a connection slot if the number of connections is limited, then accepts the next connection.
+    async fn accept(
+        &self,
+    ) -> Result<(Option<OwnedSemaphorePermit>, listener::Stream), std::io::Error> {
+        let permit = match &self.connection_limit {
+            Some(limit) => Some(
+                limit
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("connection limit semaphore is never closed"),
+            ),
+            None => None,
+        };
+        let stream = listener::accept(&self.listeners).await?;
+        Ok((permit, stream))
+    }
+
+    /// Accepts connections until `signal` completes, then shuts down gracefully.
+    async fn run(self, signal: impl Future<Output = ()>) -> Result<(), std::io::Error> {
         let graceful = GracefulShutdown::new();
+        let mut connections = JoinSet::new();
         // when this signal completes, start shutdown
-        let mut signal = std::pin::pin!(shutdown_signal());
+        let mut signal = std::pin::pin!(signal);
 
         loop {
-            let data = data.clone();
+            let data = self.data.clone();
             tokio::select! {
-                Ok((stream, _)) = server.accept() => {
-                    let io = TokioIo::new(stream);
-                    let conn = http1.serve_connection(io, service_fn(move |req| hyper_service(data.clone(), req)));
-                    let handle = graceful.watch(conn);
-
-                    tokio::task::spawn(async move {
-                        if let Err(e) = handle.await {
-                            eprintln!("server connection error: {}", e);
+                Ok((permit, stream)) = self.accept() => {
+                    let builder = self.builder.clone();
+                    let watcher = graceful.watcher();
+                    #[cfg(feature = "tls")]
+                    let acceptor = self.tls.as_ref().map(|tls| tls.acceptor.clone());
+
+                    let info = stream.connect_info();
+                    let timeouts = self.timeouts;
+
+                    connections.spawn(async move {
+                        // the connection slot is released once the connection is closed
+                        let _permit = permit;
+                        #[cfg(feature = "tls")]
+                        if let Some(acceptor) = acceptor {
+                            let handshake = acceptor.accept(stream);
+                            let stream = match timeouts.handshake {
+                                Some(timeout) => tokio::time::timeout(timeout, handshake).await.ok(),
+                                None => Some(handshake.await),
+                            };
+                            // failed and stalled handshakes are the client's concern
+                            if let Some(Ok(stream)) = stream {
+                                let mut info = info.with_scheme(hyper::http::uri::Scheme::HTTPS);
+                                if let Some(name) = stream.get_ref().1.server_name() {
+                                    info = info.with_server_name(name);
+                                }
+                                serve_connection(builder, watcher, stream, info, timeouts, data).await;
+                            }
+                            return;
                         }
+                        serve_connection(builder, watcher, stream, info, timeouts, data).await;
                     });
                 }
+                // reap completed connections
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
                 _ = &mut signal => {
-                    eprintln!("graceful shutdown signal received");
                     // stop the accept loop
                     break;
                 }
                 else => continue, // continue waiting for the next signal or connection
             }
         }
+        // stop accepting new connections while in-flight ones are drained
+        drop(self.listeners);
 
-        tokio::select! {
-            _ = graceful.shutdown() => {
-                eprintln!("all connections gracefully closed");
-            },
-            _ = tokio::time::sleep(std::time::Duration::from_secs(10)) => {
-                eprintln!("timed out wait for all connections to close");
+        let _ = tokio::time::timeout(self.shutdown_timeout, graceful.shutdown()).await;
+        // connections still open after the timeout are dropped
+        connections.shutdown().await;
+
+        self.shutdown_hooks
+            .run_all()
+            .await
+            .map_err(std::io::Error::other)
+    }
+}
+
+/// Serves the requests of a connection, until it is closed or the server shuts down.
+async fn serve_connection<I>(
+    builder: Builder<TokioExecutor>,
+    watcher: Watcher,
+    io: I,
+    info: ConnectInfo,
+    timeouts: Timeouts,
+    data: Arc<PlatformData>,
+) where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let idle = Idle::new();
+    let service = {
+        let idle = idle.clone();
+        service_fn(move |mut req: Request<Incoming>| {
+            let info = info.clone().with_version(req.version());
+            req.extensions_mut().insert(info);
+
+            let data = data.clone();
+            let guard = idle.request();
+            async move {
+                let res = hyper_service(data, req, timeouts).await;
+                drop(guard);
+                res
             }
-        }
+        })
+    };
+    let conn = builder.serve_connection(TokioIo::new(idle.wrap(io)), service);
+    let conn = watcher.watch(conn.into_owned());
 
-        Ok(())
+    // connection errors are the client's concern, e.g. a reset connection
+    match timeouts.idle {
+        Some(timeout) => {
+            tokio::select! {
+                _ = conn => {}
+                // idle connections are dropped, there's no request to complete
+                _ = idle.expired(timeout) => {}
+            }
+        }
+        None => {
+            let _ = conn.await;
+        }
     }
 }
 
 async fn hyper_service(
     data: Arc<PlatformData>,
     req: Request<Incoming>,
-) -> Result<NgynResponse, hyper::Error> {
+    timeouts: Timeouts,
+) -> Result<Response<UnsyncBoxBody<Bytes, io::Error>>, hyper::Error> {
     let (parts, mut body) = req.into_parts();
-    let body = {
+    let body = async {
         let mut buf = Vec::new();
         // TODO: change this approach. It's not efficient.
         while let Some(frame) = body.frame().await {
@@ -129,16 +750,360 @@ async fn hyper_service(
                 break;
             }
         }
-        buf
+        Ok::<_, hyper::Error>(buf)
     };
+    let body = match timeouts.body_read {
+        Some(timeout) => match tokio::time::timeout(timeout, body).await {
+            Ok(body) => body?,
+            Err(_) => {
+                let res = timeout_response(StatusCode::REQUEST_TIMEOUT);
+                return Ok(ResponseStream::into_boxed(res));
+            }
+        },
+        None => body.await?,
+    };
+
     let req = Request::from_parts(parts, body);
-    let res = data.respond(req).await;
+    let res = match timeouts.request {
+        Some(timeout) => match tokio::time::timeout(timeout, data.respond(req)).await {
+            Ok(res) => res,
+            Err(_) => timeout_response(StatusCode::SERVICE_UNAVAILABLE),
+        },
+        None => data.respond(req).await,
+    };
 
-    Ok::<_, hyper::Error>(res)
+    Ok::<_, hyper::Error>(ResponseStream::into_boxed(res))
 }
 
-async fn shutdown_signal() {
+/// Creates the response of a request that timed out.
+fn timeout_response(status: StatusCode) -> NgynResponse {
+    let mut res = NgynResponse::default();
+    *res.status_mut() = status;
+    if status == StatusCode::REQUEST_TIMEOUT {
+        // the rest of the body may still be in flight, the connection can't be reused
+        res.headers_mut()
+            .insert(CONNECTION, HeaderValue::from_static("close"));
+    }
+    res
+}
+
+async fn ctrl_c() {
     tokio::signal::ctrl_c()
         .await
         .expect("failed to listen for signal");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ngyn_shared::core::engine::{NgynEngine, NgynHttpEngine};
+    use ngyn_shared::core::handler::{async_handler, handler};
+    use ngyn_shared::server::NgynContext;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    async fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET {} HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n",
+            path
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    async fn get_h2(addr: SocketAddr, path: &str) -> String {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut sender, conn) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await
+                .unwrap();
+        tokio::spawn(conn);
+
+        let request = Request::builder()
+            .uri(format!("http://{}{}", addr, path))
+            .body(http_body_util::Empty::<ngyn_shared::server::Bytes>::new())
+            .unwrap();
+        let response = sender.send_request(request).await.unwrap();
+        assert_eq!(response.version(), hyper::Version::HTTP_2);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    fn hello_app(protocol: Protocol) -> HyperApplication {
+        let mut app = HyperApplication::with_config(HyperConfig::default().protocol(protocol));
+        app.get("/", handler(|_: &mut NgynContext| "Hello"));
+        app
+    }
+
+    #[tokio::test]
+    async fn test_http2() {
+        let handle = hello_app(Protocol::Http2)
+            .serve("127.0.0.1:0")
+            .await
+            .unwrap();
+
+        assert_eq!(get_h2(handle.local_addr(), "/").await, "Hello");
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_auto() {
+        let handle = hello_app(Protocol::Auto)
+            .serve("127.0.0.1:0")
+            .await
+            .unwrap();
+
+        assert_eq!(get_h2(handle.local_addr(), "/").await, "Hello");
+        assert!(get(handle.local_addr(), "/").await.ends_with("Hello"));
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_serve_and_shutdown() {
+        let mut app = HyperApplication::default();
+        app.get("/", handler(|_: &mut NgynContext| "Hello"));
+        let (stopped, mut on_stop) = tokio::sync::mpsc::unbounded_channel();
+        app.on_shutdown(move || async move {
+            stopped.send(()).unwrap();
+            Ok(())
+        });
+
+        let handle = app.serve("127.0.0.1:0").await.unwrap();
+        let addr = handle.local_addr();
+
+        let response = get(addr, "/").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("Hello"));
+
+        handle.shutdown().await.unwrap();
+        assert!(on_stop.try_recv().is_ok());
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_listen_with_shutdown() {
+        let (tx, rx) = oneshot::channel::<()>();
+        let app = HyperApplication::with_config(
+            HyperConfig::default().shutdown_timeout(Duration::from_millis(100)),
+        );
+
+        let server = tokio::spawn(app.listen_with_shutdown("127.0.0.1:0", async {
+            let _ = rx.await;
+        }));
+        tx.send(()).unwrap();
+
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_serve_on_std_listener() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = hello_app(Protocol::Http1)
+            .serve_on([Listener::try_from(listener).unwrap()])
+            .await
+            .unwrap();
+
+        assert_eq!(handle.local_addr(), addr);
+        assert!(get(addr, "/").await.ends_with("Hello"));
+        handle.shutdown().await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket() {
+        let path = std::env::temp_dir().join(format!("ngyn-hyper-{}.sock", std::process::id()));
+        let handle = hello_app(Protocol::Http1)
+            .serve_on([Listener::bind_unix(&path).unwrap()])
+            .await
+            .unwrap();
+        assert!(matches!(handle.local_addrs(), [ListenAddr::Unix(_)]));
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.ends_with("Hello"));
+
+        handle.shutdown().await.unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_multiple_listeners() {
+        let handle = hello_app(Protocol::Http1)
+            .serve_on([
+                Listener::bind("127.0.0.1:0").await.unwrap(),
+                Listener::bind("127.0.0.1:0").await.unwrap(),
+            ])
+            .await
+            .unwrap();
+
+        for addr in handle.local_addrs() {
+            let ListenAddr::Tcp(addr) = addr else {
+                unreachable!()
+            };
+            assert!(get(*addr, "/").await.ends_with("Hello"));
+        }
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_no_listeners() {
+        let error = hello_app(Protocol::Http1).serve_on([]).await.err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn test_connect_info() {
+        use ngyn_shared::server::Transformer;
+
+        let mut app = HyperApplication::default();
+        app.get(
+            "/",
+            handler(|cx: &mut NgynContext| {
+                let info = ConnectInfo::transform(cx);
+                format!(
+                    "{} {:?} {}",
+                    info.client_ip().unwrap(),
+                    info.version(),
+                    info.scheme()
+                )
+            }),
+        );
+
+        let handle = app.serve("127.0.0.1:0").await.unwrap();
+        let response = get(handle.local_addr(), "/").await;
+        assert!(response.ends_with("127.0.0.1 HTTP/1.1 http"));
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_max_connections() {
+        let handle = HyperApplication::with_config(HyperConfig::default().max_connections(1))
+            .serve("127.0.0.1:0")
+            .await
+            .unwrap();
+        let addr = handle.local_addr();
+
+        // an open connection holds the only slot
+        let holder = TcpStream::connect(addr).await.unwrap();
+        let waiting = tokio::spawn(get(addr, "/"));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!waiting.is_finished());
+
+        drop(holder);
+        let response = tokio::time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 404"));
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let mut app = HyperApplication::with_config(
+            HyperConfig::default().request_timeout(Duration::from_millis(50)),
+        );
+        app.get(
+            "/slow",
+            async_handler(|_: &mut NgynContext| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                "Done"
+            }),
+        );
+        let handle = app.serve("127.0.0.1:0").await.unwrap();
+
+        let response = get(handle.local_addr(), "/slow").await;
+        assert!(response.starts_with("HTTP/1.1 503"));
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_body_read_timeout() {
+        let mut app = HyperApplication::with_config(
+            HyperConfig::default().body_read_timeout(Duration::from_millis(50)),
+        );
+        app.post("/", handler(|_: &mut NgynContext| "Hello"));
+        let handle = app.serve("127.0.0.1:0").await.unwrap();
+
+        let mut stream = TcpStream::connect(handle.local_addr()).await.unwrap();
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nhost: localhost\r\ncontent-length: 10\r\n\r\nabc")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 408"));
+        assert!(response.contains("connection: close"));
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_header_read_timeout() {
+        let handle = HyperApplication::with_config(
+            HyperConfig::default().header_read_timeout(Some(Duration::from_millis(50))),
+        )
+        .serve("127.0.0.1:0")
+        .await
+        .unwrap();
+
+        let mut stream = TcpStream::connect(handle.local_addr()).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: loc")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        // the connection is closed, without waiting for the rest of the headers
+        tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response))
+            .await
+            .unwrap()
+            .unwrap();
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        let mut app = HyperApplication::with_config(
+            HyperConfig::default()
+                .h1_keep_alive(true)
+                .idle_timeout(Duration::from_millis(100)),
+        );
+        app.get("/", handler(|_: &mut NgynContext| "Hello"));
+        let handle = app.serve("127.0.0.1:0").await.unwrap();
+
+        let mut stream = TcpStream::connect(handle.local_addr()).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        // the kept-alive connection is closed once idle
+        tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(String::from_utf8(response).unwrap().ends_with("Hello"));
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failing_startup_hook() {
+        let mut app = HyperApplication::default();
+        app.on_startup(|| async { Err("migration failed".into()) });
+
+        let error = app.serve("127.0.0.1:0").await.err().unwrap();
+        assert_eq!(error.to_string(), "migration failed");
+    }
+}
@@ -0,0 +1,272 @@
+use ngyn_shared::server::ConnectInfo;
+use std::io;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+/// A listener an [`HyperApplication`](crate::HyperApplication) accepts connections from.
+///
+/// Listeners are either bound by the application, or created from an existing `std` or `tokio` listener,
+/// e.g. a socket passed by a service manager or a TCP listener bound to port `0`.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn_hyper::Listener;
+///
+/// let tcp = std::net::TcpListener::bind("127.0.0.1:0")?;
+/// let port = tcp.local_addr()?.port();
+///
+/// let handle = app
+///     .serve_on([Listener::try_from(tcp)?, Listener::bind_unix("/tmp/app.sock")?])
+///     .await?;
+/// ```
+#[derive(Debug)]
+pub struct Listener {
+    inner: Inner,
+}
+
+#[derive(Debug)]
+enum Inner {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix {
+        listener: UnixListener,
+        /// The socket file created by [`Listener::bind_unix`], removed when the listener is dropped.
+        path: Option<PathBuf>,
+    },
+}
+
+/// The local address of a [`Listener`].
+#[derive(Clone, Debug)]
+pub enum ListenAddr {
+    /// The address of a TCP listener.
+    Tcp(SocketAddr),
+    /// The address of a Unix domain socket listener.
+    #[cfg(unix)]
+    Unix(tokio::net::unix::SocketAddr),
+}
+
+impl Listener {
+    /// Binds a TCP listener to the given address.
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - The address to listen on, use port `0` to pick any available port.
+    pub async fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        Ok(TcpListener::bind(address).await?.into())
+    }
+
+    /// Binds a Unix domain socket listener to the given path.
+    ///
+    /// The socket file is removed when the listener is dropped, i.e. when the application shuts down.
+    ///
+    /// ### Arguments
+    ///
+    /// * `path` - The path of the socket file, binding fails if the file already exists.
+    ///
+    /// # Panics
+    /// Panics if called outside of a tokio runtime.
+    #[cfg(unix)]
+    pub fn bind_unix<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        Ok(Self {
+            inner: Inner::Unix {
+                listener: UnixListener::bind(path)?,
+                path: Some(path.to_path_buf()),
+            },
+        })
+    }
+
+    /// Returns the local address of the listener.
+    pub fn local_addr(&self) -> io::Result<ListenAddr> {
+        match &self.inner {
+            Inner::Tcp(listener) => listener.local_addr().map(ListenAddr::Tcp),
+            #[cfg(unix)]
+            Inner::Unix { listener, .. } => listener.local_addr().map(ListenAddr::Unix),
+        }
+    }
+
+    /// Polls for the next incoming connection.
+    pub(crate) fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<Stream>> {
+        match &self.inner {
+            Inner::Tcp(listener) => listener
+                .poll_accept(cx)
+                .map_ok(|(stream, _)| Stream::Tcp(stream)),
+            #[cfg(unix)]
+            Inner::Unix { listener, .. } => listener
+                .poll_accept(cx)
+                .map_ok(|(stream, _)| Stream::Unix(stream)),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Inner::Unix {
+            path: Some(path), ..
+        } = &self.inner
+        {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+impl From<TcpListener> for Listener {
+    fn from(listener: TcpListener) -> Self {
+        Self {
+            inner: Inner::Tcp(listener),
+        }
+    }
+}
+
+impl TryFrom<std::net::TcpListener> for Listener {
+    type Error = io::Error;
+
+    /// Converts a `std` TCP listener, the listener is switched to non-blocking mode.
+    ///
+    /// # Panics
+    /// Panics if called outside of a tokio runtime.
+    fn try_from(listener: std::net::TcpListener) -> io::Result<Self> {
+        listener.set_nonblocking(true)?;
+        Ok(TcpListener::from_std(listener)?.into())
+    }
+}
+
+#[cfg(unix)]
+impl From<UnixListener> for Listener {
+    fn from(listener: UnixListener) -> Self {
+        Self {
+            inner: Inner::Unix {
+                listener,
+                path: None,
+            },
+        }
+    }
+}
+
+#[cfg(unix)]
+impl TryFrom<std::os::unix::net::UnixListener> for Listener {
+    type Error = io::Error;
+
+    /// Converts a `std` Unix domain socket listener, the listener is switched to non-blocking mode.
+    ///
+    /// # Panics
+    /// Panics if called outside of a tokio runtime.
+    fn try_from(listener: std::os::unix::net::UnixListener) -> io::Result<Self> {
+        listener.set_nonblocking(true)?;
+        Ok(UnixListener::from_std(listener)?.into())
+    }
+}
+
+/// Accepts the next connection from any of the listeners.
+pub(crate) async fn accept(listeners: &[Listener]) -> io::Result<Stream> {
+    std::future::poll_fn(|cx| {
+        for listener in listeners {
+            if let Poll::Ready(result) = listener.poll_accept(cx) {
+                return Poll::Ready(result);
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// A connection accepted by a [`Listener`].
+pub(crate) enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    /// Returns the info of the connection, Unix domain socket connections have no addresses.
+    pub(crate) fn connect_info(&self) -> ConnectInfo {
+        let info = ConnectInfo::new();
+        match self {
+            Stream::Tcp(stream) => {
+                let info = match stream.peer_addr() {
+                    Ok(addr) => info.with_peer_addr(addr),
+                    Err(_) => info,
+                };
+                match stream.local_addr() {
+                    Ok(addr) => info.with_local_addr(addr),
+                    Err(_) => info,
+                }
+            }
+            #[cfg(unix)]
+            Stream::Unix(_) => info,
+        }
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            Stream::Tcp(stream) => stream.is_write_vectored(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
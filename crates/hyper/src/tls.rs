@@ -0,0 +1,505 @@
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use rustls::sign::CertifiedKey;
+use rustls::{RootCertStore, ServerConfig};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+
+use crate::Protocol;
+
+/// Configure TLS for an [`HyperApplication`](crate::HyperApplication).
+///
+/// Certificates and keys are loaded from PEM files.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+/// use ngyn_hyper::TlsConfig;
+///
+/// let tls = TlsConfig::new("certs/server.pem", "certs/server.key")
+///     .client_auth("certs/clients-ca.pem")
+///     .reload_interval(Duration::from_secs(60));
+///
+/// let app = HyperApplication::with_config(HyperConfig::default().tls(tls));
+/// app.listen("0.0.0.0:443").await?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    client_ca_path: Option<PathBuf>,
+    client_auth_optional: bool,
+    reload_interval: Option<Duration>,
+}
+
+impl TlsConfig {
+    /// Creates a new `TlsConfig`.
+    ///
+    /// ### Arguments
+    ///
+    /// * `cert_path` - The path of the certificate chain, the server certificate comes first.
+    /// * `key_path` - The path of the private key of the server certificate.
+    pub fn new(cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Self {
+        Self {
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+            client_ca_path: None,
+            client_auth_optional: false,
+            reload_interval: None,
+        }
+    }
+
+    /// Requires clients to present a certificate signed by one of the certificate authorities in `ca_path` (mTLS).
+    pub fn client_auth(mut self, ca_path: impl Into<PathBuf>) -> Self {
+        self.client_ca_path = Some(ca_path.into());
+        self.client_auth_optional = false;
+        self
+    }
+
+    /// Verifies client certificates against the certificate authorities in `ca_path`,
+    /// but also accepts clients that don't present a certificate.
+    pub fn optional_client_auth(mut self, ca_path: impl Into<PathBuf>) -> Self {
+        self.client_ca_path = Some(ca_path.into());
+        self.client_auth_optional = true;
+        self
+    }
+
+    /// Checks the certificate and key files for changes at the given interval,
+    /// and uses the new certificate for new connections without restarting the application.
+    ///
+    /// If the files can't be loaded (e.g. while they are being written), the current certificate is kept.
+    pub fn reload_interval(mut self, interval: Duration) -> Self {
+        self.reload_interval = Some(interval);
+        self
+    }
+
+    /// Builds the acceptor of TLS connections, and starts watching the certificate if needed.
+    pub(crate) fn acceptor(&self, protocol: Protocol) -> io::Result<Tls> {
+        let provider = Arc::new(ring::default_provider());
+        let (cert, key) = (
+            std::fs::read(&self.cert_path)?,
+            std::fs::read(&self.key_path)?,
+        );
+        let resolver = Arc::new(CertResolver {
+            key: RwLock::new(load_certified_key(&provider, &self.cert_path, &cert, &key)?),
+        });
+
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?;
+
+        let builder = match &self.client_ca_path {
+            Some(ca_path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in CertificateDer::pem_file_iter(ca_path).map_err(pem_error)? {
+                    roots
+                        .add(cert.map_err(pem_error)?)
+                        .map_err(io::Error::other)?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone());
+                let verifier = if self.client_auth_optional {
+                    verifier.allow_unauthenticated()
+                } else {
+                    verifier
+                };
+                builder.with_client_cert_verifier(verifier.build().map_err(io::Error::other)?)
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let mut config = builder.with_cert_resolver(resolver.clone());
+        config.alpn_protocols = match protocol {
+            Protocol::Http1 => vec![b"http/1.1".to_vec()],
+            Protocol::Http2 => vec![b"h2".to_vec()],
+            Protocol::Auto => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        };
+
+        let reload = self.reload_interval.map(|interval| {
+            let (cert_path, key_path) = (self.cert_path.clone(), self.key_path.clone());
+            // compared with the files that were loaded, which may change before the task runs
+            let mut current = Some((cert, key));
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    let files = read_files(&cert_path, &key_path).await;
+                    let Some((cert, key)) = files.as_ref().filter(|_| files != current) else {
+                        continue;
+                    };
+                    if let Ok(certified) = load_certified_key(&provider, &cert_path, cert, key) {
+                        *resolver.key.write().unwrap_or_else(|e| e.into_inner()) = certified;
+                        current = files;
+                    }
+                }
+            })
+        });
+
+        Ok(Tls {
+            acceptor: TlsAcceptor::from(Arc::new(config)),
+            reload,
+        })
+    }
+}
+
+/// The TLS state of a running server.
+pub(crate) struct Tls {
+    pub(crate) acceptor: TlsAcceptor,
+    reload: Option<JoinHandle<()>>,
+}
+
+impl Drop for Tls {
+    fn drop(&mut self) {
+        if let Some(reload) = &self.reload {
+            reload.abort();
+        }
+    }
+}
+
+/// Provides the current certificate, which can be replaced while the server is running.
+struct CertResolver {
+    key: RwLock<Arc<CertifiedKey>>,
+}
+
+impl fmt::Debug for CertResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertResolver").finish_non_exhaustive()
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.key.read().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+}
+
+/// Parses the PEM encoded certificate chain and private key read from `cert_path` and its key file.
+fn load_certified_key(
+    provider: &CryptoProvider,
+    cert_path: &Path,
+    cert: &[u8],
+    key: &[u8],
+) -> io::Result<Arc<CertifiedKey>> {
+    let certs = CertificateDer::pem_slice_iter(cert)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(pem_error)?;
+    if certs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no certificate found in {}", cert_path.display()),
+        ));
+    }
+    let key = PrivateKeyDer::from_pem_slice(key).map_err(pem_error)?;
+    let key = provider
+        .key_provider
+        .load_private_key(key)
+        .map_err(io::Error::other)?;
+
+    let certified = CertifiedKey::new(certs, key);
+    // a certificate and key being replaced can be read half way through, so they must match
+    if let Err(rustls::Error::InconsistentKeys(rustls::InconsistentKeys::KeyMismatch)) =
+        certified.keys_match()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the private key doesn't match the certificate",
+        ));
+    }
+
+    Ok(Arc::new(certified))
+}
+
+async fn read_files(cert_path: &Path, key_path: &Path) -> Option<(Vec<u8>, Vec<u8>)> {
+    Some((
+        tokio::fs::read(cert_path).await.ok()?,
+        tokio::fs::read(key_path).await.ok()?,
+    ))
+}
+
+fn pem_error(e: rustls::pki_types::pem::Error) -> io::Error {
+    match e {
+        rustls::pki_types::pem::Error::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HyperApplication, HyperConfig};
+    use ngyn_shared::core::engine::NgynHttpEngine;
+    use ngyn_shared::core::handler::handler;
+    use ngyn_shared::server::NgynContext;
+    use rcgen::{
+        BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+    };
+    use rustls::pki_types::ServerName;
+    use rustls::ClientConfig;
+    use std::net::SocketAddr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio_rustls::TlsConnector;
+
+    struct Ca {
+        cert: rcgen::Certificate,
+        key: KeyPair,
+    }
+
+    impl Ca {
+        fn new() -> Self {
+            let mut params = CertificateParams::default();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "ngyn test ca");
+            let key = KeyPair::generate().unwrap();
+            let cert = params.self_signed(&key).unwrap();
+            Self { cert, key }
+        }
+
+        fn issue(&self, name: &str, usage: ExtendedKeyUsagePurpose) -> (String, String) {
+            let mut params = CertificateParams::new(vec![name.to_string()]).unwrap();
+            params.distinguished_name.push(DnType::CommonName, name);
+            params.extended_key_usages = vec![usage];
+            let key = KeyPair::generate().unwrap();
+            let cert = params.signed_by(&key, &self.cert, &self.key).unwrap();
+            (cert.pem(), key.serialize_pem())
+        }
+    }
+
+    struct Files {
+        dir: PathBuf,
+    }
+
+    impl Files {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("ngyn-tls-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self { dir }
+        }
+
+        fn write(&self, name: &str, content: &str) -> PathBuf {
+            let path = self.dir.join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        }
+    }
+
+    impl Drop for Files {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn client_config(ca: &Ca, client: Option<(String, String)>) -> ClientConfig {
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.cert.der().clone()).unwrap();
+        let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+
+        let mut config = match client {
+            Some((cert, key)) => builder
+                .with_client_auth_cert(
+                    vec![CertificateDer::from_pem_slice(cert.as_bytes()).unwrap()],
+                    PrivateKeyDer::from_pem_slice(key.as_bytes()).unwrap(),
+                )
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        config
+    }
+
+    async fn get(
+        addr: SocketAddr,
+        config: ClientConfig,
+    ) -> io::Result<(String, Option<Vec<u8>>, CertificateDer<'static>)> {
+        let connector = TlsConnector::from(Arc::new(config));
+        let stream = TcpStream::connect(addr).await?;
+        let mut stream = connector
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await?;
+
+        let alpn = stream.get_ref().1.alpn_protocol().map(|p| p.to_vec());
+        let cert = stream.get_ref().1.peer_certificates().unwrap()[0].clone();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+
+        Ok((response, alpn, cert))
+    }
+
+    fn hello_app(config: HyperConfig) -> HyperApplication {
+        let mut app = HyperApplication::with_config(config);
+        app.get("/", handler(|_: &mut NgynContext| "Hello"));
+        app
+    }
+
+    #[tokio::test]
+    async fn test_tls() {
+        let ca = Ca::new();
+        let files = Files::new("serve");
+        let (cert, key) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let tls = TlsConfig::new(files.write("cert.pem", &cert), files.write("key.pem", &key));
+
+        let handle = hello_app(HyperConfig::default().tls(tls))
+            .serve("127.0.0.1:0")
+            .await
+            .unwrap();
+
+        let (response, alpn, _) = get(handle.local_addr(), client_config(&ca, None))
+            .await
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("Hello"));
+        assert_eq!(alpn.as_deref(), Some(&b"http/1.1"[..]));
+
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handshake_timeout() {
+        let ca = Ca::new();
+        let files = Files::new("handshake");
+        let (cert, key) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let tls = TlsConfig::new(files.write("cert.pem", &cert), files.write("key.pem", &key));
+
+        let config = HyperConfig::default()
+            .tls(tls)
+            .max_connections(1)
+            .header_read_timeout(Some(Duration::from_millis(50)));
+        let handle = hello_app(config).serve("127.0.0.1:0").await.unwrap();
+
+        // a client that never starts the handshake is disconnected, releasing its connection slot
+        let mut stalled = TcpStream::connect(handle.local_addr()).await.unwrap();
+        let mut response = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), stalled.read_to_end(&mut response))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let (response, ..) = get(handle.local_addr(), client_config(&ca, None))
+            .await
+            .unwrap();
+        assert!(response.ends_with("Hello"));
+
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_alpn_h2() {
+        let ca = Ca::new();
+        let files = Files::new("alpn");
+        let (cert, key) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let tls = TlsConfig::new(files.write("cert.pem", &cert), files.write("key.pem", &key));
+        let config = HyperConfig::default().protocol(Protocol::Auto).tls(tls);
+        let handle = hello_app(config).serve("127.0.0.1:0").await.unwrap();
+
+        let connector = TlsConnector::from(Arc::new(client_config(&ca, None)));
+        let stream = TcpStream::connect(handle.local_addr()).await.unwrap();
+        let stream = connector
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap();
+        assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_auth() {
+        let ca = Ca::new();
+        let files = Files::new("mtls");
+        let (cert, key) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let ca_path = files.write("ca.pem", &ca.cert.pem());
+        let tls = TlsConfig::new(files.write("cert.pem", &cert), files.write("key.pem", &key))
+            .client_auth(ca_path);
+
+        let handle = hello_app(HyperConfig::default().tls(tls))
+            .serve("127.0.0.1:0")
+            .await
+            .unwrap();
+
+        let client = ca.issue("client", ExtendedKeyUsagePurpose::ClientAuth);
+        let (response, ..) = get(handle.local_addr(), client_config(&ca, Some(client)))
+            .await
+            .unwrap();
+        assert!(response.ends_with("Hello"));
+
+        // without a client certificate, the server rejects the connection
+        assert!(get(handle.local_addr(), client_config(&ca, None))
+            .await
+            .is_err());
+
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let ca = Ca::new();
+        let files = Files::new("reload");
+        let (cert, key) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let tls = TlsConfig::new(files.write("cert.pem", &cert), files.write("key.pem", &key))
+            .reload_interval(Duration::from_millis(20));
+
+        let handle = hello_app(HyperConfig::default().tls(tls))
+            .serve("127.0.0.1:0")
+            .await
+            .unwrap();
+        let (_, _, first) = get(handle.local_addr(), client_config(&ca, None))
+            .await
+            .unwrap();
+
+        let (cert, key) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        files.write("key.pem", &key);
+        files.write("cert.pem", &cert);
+
+        let mut reloaded = false;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let (_, _, current) = get(handle.local_addr(), client_config(&ca, None))
+                .await
+                .unwrap();
+            if current != first {
+                reloaded = true;
+                break;
+            }
+        }
+        assert!(reloaded);
+
+        handle.shutdown().await.unwrap();
+    }
+
+    #[test]
+    fn test_missing_files() {
+        let tls = TlsConfig::new("/nonexistent/cert.pem", "/nonexistent/key.pem");
+        let error = tls.acceptor(Protocol::Http1).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_mismatched_key() {
+        let ca = Ca::new();
+        let files = Files::new("mismatch");
+        let (cert, _) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let (_, key) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let tls = TlsConfig::new(files.write("cert.pem", &cert), files.write("key.pem", &key));
+
+        let error = tls.acceptor(Protocol::Http1).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,137 @@
+use ngyn_shared::middlewares::{
+    compression::{is_compressible, Encoding},
+    etag::hash,
+    static_files::content_type,
+};
+use proc_macro::TokenStream;
+use quote::quote;
+use std::path::{Path, PathBuf};
+use syn::{
+    parse::{Parse, ParseStream},
+    Ident, LitByteStr, LitStr, Token,
+};
+
+struct EmbedArgs {
+    path: LitStr,
+    precompress: bool,
+}
+
+impl Parse for EmbedArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse()?;
+        let mut precompress = false;
+
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let option: Ident = input.parse()?;
+            if option != "precompress" {
+                return Err(syn::Error::new(option.span(), "expected `precompress`"));
+            }
+            precompress = true;
+            input.parse::<Option<Token![,]>>()?;
+        }
+
+        Ok(EmbedArgs { path, precompress })
+    }
+}
+
+/// Collects the files of a directory recursively, skipping hidden files.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map_or(true, |name| name.starts_with('.'))
+        {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn embed_static_macro(input: TokenStream) -> TokenStream {
+    let EmbedArgs { path, precompress } = syn::parse_macro_input!(input as EmbedArgs);
+    let error = |message: String| {
+        syn::Error::new(path.span(), message)
+            .to_compile_error()
+            .into()
+    };
+
+    // the path is relative to the manifest of the crate using the macro
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let root = Path::new(&manifest_dir).join(path.value());
+
+    let mut files = Vec::new();
+    if let Err(e) = collect_files(&root, &mut files) {
+        return error(format!("failed to embed `{}`: {}", root.display(), e));
+    }
+    files.sort();
+
+    let encodings = ["br", "gzip"].map(|name| name.parse::<Encoding>().ok());
+    if precompress && encodings.iter().all(Option::is_none) {
+        return error(
+            "precompression requires the `brotli` or `gzip` feature of ngyn to be enabled"
+                .to_string(),
+        );
+    }
+
+    let mut embedded = Vec::new();
+    for file in files {
+        let relative = file
+            .strip_prefix(&root)
+            .ok()
+            .and_then(|relative| relative.to_str())
+            .map(|relative| relative.replace('\\', "/"));
+        let (Some(relative), Some(absolute)) = (relative, file.to_str()) else {
+            return error(format!("`{}` isn't a valid UTF-8 path", file.display()));
+        };
+        let content = match std::fs::read(&file) {
+            Ok(content) => content,
+            Err(e) => return error(format!("failed to embed `{}`: {}", file.display(), e)),
+        };
+
+        let content_type = content_type(&file);
+        let content_type = content_type.to_str().unwrap_or("application/octet-stream");
+        let hash = hash(&content);
+
+        // variants are only kept when they are smaller than the content
+        let [brotli, gzip] = encodings.map(|encoding| {
+            let compressed = encoding
+                .filter(|_| precompress && is_compressible(content_type))
+                .and_then(|encoding| encoding.compress(&content).ok())
+                .filter(|compressed| compressed.len() < content.len());
+            match compressed {
+                Some(compressed) => {
+                    let compressed = LitByteStr::new(&compressed, path.span());
+                    quote! { Some(#compressed) }
+                }
+                None => quote! { None },
+            }
+        });
+
+        embedded.push(quote! {
+            ngyn::shared::middlewares::static_files::EmbeddedFile {
+                path: #relative,
+                content: include_bytes!(#absolute),
+                content_type: #content_type,
+                hash: #hash,
+                brotli: #brotli,
+                gzip: #gzip,
+            }
+        });
+    }
+
+    let expanded = quote! {
+        {
+            static FILES: &[ngyn::shared::middlewares::static_files::EmbeddedFile] = &[#(#embedded),*];
+            ngyn::shared::middlewares::static_files::StaticFiles::embedded(FILES)
+        }
+    };
+    expanded.into()
+}
@@ -2,20 +2,45 @@ use proc_macro::TokenStream;
 use quote::{quote, ToTokens};
 use syn::{token::RArrow, ItemFn, Signature};
 
+/// What a route requires to be authorized, a role or a policy type.
+enum Authorize {
+    Role(syn::LitStr),
+    Policy(syn::Path),
+}
+
 pub(super) struct HandlerArgs {
     gates: Vec<syn::Path>,
     middlewares: Vec<syn::Path>,
+    cache: Option<syn::LitInt>,
+    authorize: Option<Authorize>,
 }
 
 impl syn::parse::Parse for HandlerArgs {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         let mut gates = Vec::new();
         let mut middlewares = Vec::new();
+        let mut cache = None;
+        let mut authorize = None;
 
         while !input.is_empty() {
             let ident: syn::Ident = input.parse()?;
             input.parse::<syn::Token![=]>()?;
 
+            // parse the ttl of cached responses, in seconds, or the role or policy authorizing the route
+            if ident == "cache" || ident == "authorize" {
+                if ident == "cache" {
+                    cache = Some(input.parse()?);
+                } else if input.peek(syn::LitStr) {
+                    authorize = Some(Authorize::Role(input.parse()?));
+                } else {
+                    authorize = Some(Authorize::Policy(input.parse()?));
+                }
+                if !input.is_empty() {
+                    input.parse::<syn::Token![,]>()?;
+                }
+                continue;
+            }
+
             let content;
             // parse a list of middleware paths, [middleware1, middleware2]
             syn::bracketed!(content in input);
@@ -44,13 +69,21 @@ impl syn::parse::Parse for HandlerArgs {
                 input.parse::<syn::Token![,]>()?;
             }
         }
-        Ok(HandlerArgs { gates, middlewares })
+        Ok(HandlerArgs {
+            gates,
+            middlewares,
+            cache,
+            authorize,
+        })
     }
 }
 
 pub fn handler_macro(args: TokenStream, raw_input: TokenStream) -> TokenStream {
     let HandlerArgs {
-        gates, middlewares, ..
+        gates,
+        middlewares,
+        cache,
+        authorize,
     } = syn::parse::<HandlerArgs>(args).unwrap();
     let ItemFn {
         sig, block, vis, ..
@@ -67,8 +100,9 @@ pub fn handler_macro(args: TokenStream, raw_input: TokenStream) -> TokenStream {
         ..
     } = sig.clone();
 
-    if asyncness.is_none() && (!gates.is_empty() || !middlewares.is_empty()) {
-        panic!("Gates and middlewares are only supported with async handlers");
+    if asyncness.is_none() && (!gates.is_empty() || !middlewares.is_empty() || authorize.is_some())
+    {
+        panic!("Gates, middlewares and authorization are only supported with async handlers");
     }
 
     let mut generics_stream = generics.to_token_stream();
@@ -85,17 +119,58 @@ pub fn handler_macro(args: TokenStream, raw_input: TokenStream) -> TokenStream {
         })
         .reduce(|args, arg| quote! { #args, #arg });
 
+    let handler_body = quote! { ngyn::shared::core::handler::HandlerBody };
+
+    let arg_types = inputs
+        .iter()
+        .filter_map(|input| match input {
+            syn::FnArg::Typed(pat) => Some(&pat.ty),
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect::<Vec<_>>();
+    // the states required by the arguments are listed when the route is registered, and checked on startup
+    let probe = quote! {
+        ngyn::shared::core::handler::probe(
+            cx,
+            &[#(<#arg_types as ngyn::shared::server::Transformer>::required_state()),*],
+        )
+    };
+    // a missing state is a server error, rather than a panic while transforming the arguments
+    let availability = quote! {
+        if !(true #(&& <#arg_types as ngyn::shared::server::Transformer>::is_available(cx))*) {
+            *cx.response_mut().status_mut() = ngyn::http::StatusCode::INTERNAL_SERVER_ERROR;
+            return #handler_body(Box::new(()));
+        }
+    };
+
     let gate_handlers = gates.iter().map(|path| {
         quote! {
-            if !#path::can_activate(cx).await {
-                return Box::new(()) as Box<dyn ngyn::prelude::ToBytes>;
+            if let Some(body) = <#path>::check(cx).await.apply(cx).await {
+                return #handler_body(Box::new(body));
             }
         }
     });
 
     let middlewares_stream = middlewares.iter().map(|path| {
         quote! {
-            #path::handle(cx).await;
+            <#path>::handle(cx).await;
+        }
+    });
+
+    // authorization is checked once gates authenticated the request
+    let authorization = authorize.map(|authorize| {
+        let outcome = match authorize {
+            Authorize::Role(role) => quote! {
+                ngyn::shared::auth::policy::require_role(cx, #role)
+            },
+            Authorize::Policy(path) => quote! {
+                <ngyn::shared::auth::policy::Authorize<#path>>::check(cx).await
+            },
+        };
+        quote! {
+            if let Some(body) = #outcome.apply(cx).await {
+                return #handler_body(Box::new(body));
+            }
         }
     });
 
@@ -103,27 +178,48 @@ pub fn handler_macro(args: TokenStream, raw_input: TokenStream) -> TokenStream {
         use ngyn::prelude::{NgynMiddleware, NgynGate, ToBytes};
         #(#middlewares_stream)*
         #(#gate_handlers)*
+        #authorization
     };
 
+    // routes opt into caching with a policy, read by the cache middleware
+    let cache_policy = cache.map(|ttl| {
+        quote! {
+            cx.extensions_mut().insert(ngyn::shared::middlewares::cache::CachePolicy::new(
+                std::time::Duration::from_secs(#ttl),
+            ));
+        }
+    });
+
     let body = match asyncness.is_some() {
         true => quote! {
             async fn handle(#inputs) #output #block
-            let body = handle(#args);
+            if #probe {
+                return Box::pin(async { #handler_body(Box::new(())) });
+            }
+            #cache_policy
             Box::pin(#asyncness move {
                 #exe_block;
-                Box::new(body.await) as Box<dyn ngyn::prelude::ToBytes>
+                #availability
+                // arguments are extracted once middlewares and gates ran, so they can read what those inserted
+                let body = handle(#args);
+                #handler_body(Box::new(body.await))
             })
         },
         false => quote! {
+            if #probe {
+                return #handler_body(Box::new(()));
+            }
+            #cache_policy
+            #availability
             let output = (|#inputs| #block)(#args);
-            Box::new(output) as Box<dyn ngyn::prelude::ToBytes>
+            #handler_body(Box::new(output))
         },
     };
 
     let output = asyncness.map(|_| {
         let r_arrow = RArrow::default();
-        quote! { #r_arrow std::pin::Pin<Box<dyn std::future::Future<Output = Box<dyn ngyn::prelude::ToBytes>> + Send + '_cx_lifetime>> }
-    }).unwrap_or_else(|| quote! { -> Box<dyn ngyn::prelude::ToBytes> });
+        quote! { #r_arrow std::pin::Pin<Box<dyn std::future::Future<Output = #handler_body> + Send + '_cx_lifetime>> }
+    }).unwrap_or_else(|| quote! { -> #handler_body });
 
     quote! {
         #vis #constness #unsafety #fn_token #ident <#generics_stream>(cx: &'_cx_lifetime mut ngyn::prelude::NgynContext) #output {
@@ -10,25 +10,48 @@ pub(crate) fn service_macro(input: TokenStream) -> TokenStream {
     } = syn::parse_macro_input!(input as syn::ItemStruct);
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let fields: Vec<_> = fields
+    let mut dependencies = Vec::new();
+    let values: Vec<_> = fields
         .iter()
-        .map(
-            |syn::Field {
-                 ident, colon_token, ..
-             }| {
+        .map(|syn::Field { attrs, ty, .. }| {
+            let inject = attrs.iter().any(|attr| attr.path().is_ident("inject"));
+            if inject {
+                dependencies.push(quote! {
+                    <#ty as ngyn::shared::core::container::Resolve>::dependency()
+                });
                 quote! {
-                    #ident #colon_token Default::default()
+                    <#ty as ngyn::shared::core::container::Resolve>::resolve(resolver)
                 }
-            },
-        )
+            } else {
+                quote! { Default::default() }
+            }
+        })
         .collect();
 
+    let construct = match &fields {
+        syn::Fields::Named(_) => {
+            let names = fields.iter().map(|field| &field.ident);
+            quote! { Self { #(#names: #values),* } }
+        }
+        syn::Fields::Unnamed(_) => quote! { Self(#(#values),*) },
+        syn::Fields::Unit => quote! { Self },
+    };
+
     let expanded = quote! {
+        impl #impl_generics ngyn::shared::core::container::Injectable for #ident #ty_generics #where_clause {
+            fn dependencies() -> Vec<ngyn::shared::core::container::Dependency> {
+                vec![#(#dependencies),*]
+            }
+
+            fn construct(resolver: &mut ngyn::shared::core::container::Resolver<'_>) -> Self {
+                #construct
+            }
+        }
+
         impl #impl_generics ngyn::shared::server::Transformer<'_> for #ident #ty_generics #where_clause {
             fn transform(cx: &mut ngyn::prelude::NgynContext<'_>) -> Self {
-                Self {
-                    #(#fields)*
-                }
+                let mut resolver = ngyn::shared::core::container::Resolver::new(cx);
+                <Self as ngyn::shared::core::container::Injectable>::construct(&mut resolver)
             }
         }
     };
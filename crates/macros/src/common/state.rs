@@ -2,28 +2,58 @@ use proc_macro::TokenStream;
 
 pub(crate) fn derive_app_state_macro(input: TokenStream) -> TokenStream {
     let syn::ItemStruct {
-        ident, generics, ..
+        ident,
+        generics,
+        fields,
+        ..
     } = syn::parse_macro_input!(input as syn::ItemStruct);
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
+    let cookie_key = fields
+        .iter()
+        .enumerate()
+        .find(|(_, field)| {
+            field
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("cookie_key"))
+        })
+        .map(|(index, field)| {
+            let member = match &field.ident {
+                Some(ident) => quote::quote! { #ident },
+                None => {
+                    let index = syn::Index::from(index);
+                    quote::quote! { #index }
+                }
+            };
+            quote::quote! {
+                fn cookie_key(&self) -> Option<&ngyn::shared::server::cookies::Key> {
+                    Some(&self.#member)
+                }
+            }
+        });
+
+    let missing = format!(
+        "State `{}` has not been added, add it with `app.add_state`",
+        ident
+    );
+
     let expanded = quote::quote! {
         impl #impl_generics ngyn::shared::server::context::AppState for #ident #ty_generics #where_clause {
-            fn as_any(&self) -> &dyn std::any::Any {
-                self
-            }
-            fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
-                self
-            }
+            #cookie_key
         }
 
         impl<'a> #impl_generics ngyn::shared::server::Transformer<'a> for &'a #ident #ty_generics #where_clause {
             fn transform(cx: &'a mut ngyn::prelude::NgynContext<'_>) -> Self {
-                cx.state::<#ident>().unwrap()
+                cx.state::<#ident>().expect(#missing)
             }
-        }
-        impl<'a> #impl_generics ngyn::shared::server::Transformer<'a> for &'a mut #ident #ty_generics #where_clause {
-            fn transform(cx: &'a mut ngyn::prelude::NgynContext<'_>) -> Self {
-                cx.state_mut::<#ident>().unwrap()
+
+            fn is_available(cx: &ngyn::prelude::NgynContext<'_>) -> bool {
+                cx.state::<#ident>().is_some()
+            }
+
+            fn required_state() -> Option<ngyn::shared::core::container::Dependency> {
+                Some(ngyn::shared::core::container::Dependency::state::<#ident>())
             }
         }
     };
@@ -1,6 +1,7 @@
 extern crate proc_macro;
 
 mod common {
+    pub mod embed;
     pub mod handler;
     pub mod service;
     pub mod state;
@@ -14,12 +15,30 @@ mod core {
 use crate::core::dto::dto_macro;
 use crate::core::param::param_macro;
 use crate::core::query::query_macro;
+use common::embed::embed_static_macro;
 use common::handler::handler_macro;
 use common::service::service_macro;
 use proc_macro::TokenStream;
 
 /// Attribute macro to define a route handler function with optional gates and middlewares
 /// options in async functions.
+///
+/// Responses of the handler are cached by the `CacheMiddleware` with `cache = <seconds>`.
+/// Async handlers can require a role or a policy with `authorize = "role"` or `authorize = Policy`,
+/// checked after the gates authenticated the request.
+///
+/// ### Example
+/// ```rust ignore
+/// #[handler(cache = 60)]
+/// async fn repos() -> String {
+///     fetch_repos().await
+/// }
+///
+/// #[handler(gates = [JwtGate], authorize = "admin")]
+/// async fn delete_repo(id: Param) -> String {
+///     ...
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn handler(args: TokenStream, input: TokenStream) -> TokenStream {
     handler_macro(args, input)
@@ -39,13 +58,21 @@ pub fn dto_derive_dto(input: TokenStream) -> TokenStream {
     dto_macro(input)
 }
 
-#[proc_macro_derive(Service)]
+#[proc_macro_derive(Service, attributes(inject))]
 /// The `Service` derive macro is used to generate a Service.
 ///
+/// Fields marked with `#[inject]` are resolved from the dependency injection container,
+/// they can be an `Inject<T>` of a registered service, or a `State<T>` of an application state.
+/// Other fields are created with `Default::default()`.
+///
 /// ### Example
 /// ```rust ignore
 /// #[derive(Service)]
 /// struct MyService {
+///     #[inject]
+///     repository: Inject<MyRepository>,
+///     #[inject]
+///     config: State<MyConfig>,
 ///     // fields
 /// }
 /// ```
@@ -82,16 +109,39 @@ pub fn param_derive_macro(input: TokenStream) -> TokenStream {
     param_macro(input)
 }
 
-#[proc_macro_derive(AppState)]
+#[proc_macro_derive(AppState, attributes(cookie_key))]
 /// The `AppState` derive macro is used to derive a struct that can be used as a state in a server.
 ///
+/// A field of type `Key` can be marked with `#[cookie_key]` to sign and encrypt cookies.
+///
 /// ### Example
 /// ```rust ignore
 /// #[derive(AppState)]
 /// struct MyState {
+///    #[cookie_key]
+///    key: Key,
 ///    // fields
 /// }
 /// ```
 pub fn app_state_derive_macro(input: TokenStream) -> TokenStream {
     common::state::derive_app_state_macro(input)
 }
+
+#[proc_macro]
+/// The `embed_static!` macro embeds the files of a directory into the binary, and serves them with a `StaticFiles` middleware.
+///
+/// The path is relative to the `Cargo.toml` of the crate, hidden files aren't embedded.
+/// Content types and the hashes used in `ETag`s are computed at build time.
+/// With the `precompress` option, `br` and `gzip` variants of compressible files are also built,
+/// following the `brotli` and `gzip` features of ngyn.
+///
+/// Changes to embedded files are picked up by cargo, but adding or removing a file requires a rebuild.
+///
+/// ### Example
+/// ```rust ignore
+/// app.use_middleware(embed_static!("public"));
+/// app.use_middleware(embed_static!("assets", precompress).mount("/assets"));
+/// ```
+pub fn embed_static(input: TokenStream) -> TokenStream {
+    embed_static_macro(input)
+}
@@ -4,21 +4,16 @@ use ngyn_macros::AppState;
 struct TestState {
     name: String,
 }
-impl ngyn::shared::server::context::AppState for TestState {
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
-        self
-    }
-}
+impl ngyn::shared::server::context::AppState for TestState {}
 impl<'a> ngyn::shared::server::Transformer<'a> for &'a TestState {
     fn transform(cx: &'a mut ngyn::prelude::NgynContext<'_>) -> Self {
-        cx.state::<TestState>().unwrap()
+        cx.state::<TestState>()
+            .expect("State `TestState` has not been added, add it with `app.add_state`")
     }
-}
-impl<'a> ngyn::shared::server::Transformer<'a> for &'a mut TestState {
-    fn transform(cx: &'a mut ngyn::prelude::NgynContext<'_>) -> Self {
-        cx.state_mut::<TestState>().unwrap()
+    fn is_available(cx: &ngyn::prelude::NgynContext<'_>) -> bool {
+        cx.state::<TestState>().is_some()
+    }
+    fn required_state() -> Option<ngyn::shared::core::container::Dependency> {
+        Some(ngyn::shared::core::container::Dependency::state::<TestState>())
     }
 }
@@ -0,0 +1,200 @@
+use std::{any::type_name, collections::HashMap, sync::Arc};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{
+    auth::{constant_time_eq, credentials, policy::Principal, quote, unauthorized},
+    server::{context::AppState, NgynContext, Transformer},
+    NgynGate,
+};
+
+/// Verifies the username and password of a request.
+type Verify = dyn Fn(&str, &str) -> bool + Send + Sync;
+
+/// Configures HTTP Basic authentication, see [`BasicAuthGate`].
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+/// use ngyn::shared::auth::basic::BasicAuth;
+///
+/// app.set_state(BasicAuth::users([("admin", "secret")]).realm("admin"));
+/// ```
+#[derive(Clone)]
+pub struct BasicAuth {
+    verify: Arc<Verify>,
+    realm: String,
+}
+
+impl BasicAuth {
+    /// Authenticates requests with a function verifying their username and password.
+    pub fn new<F: Fn(&str, &str) -> bool + Send + Sync + 'static>(verify: F) -> Self {
+        Self {
+            verify: Arc::new(verify),
+            realm: "ngyn".to_string(),
+        }
+    }
+
+    /// Authenticates requests against a fixed set of usernames and passwords.
+    pub fn users<I, U, P>(users: I) -> Self
+    where
+        I: IntoIterator<Item = (U, P)>,
+        U: Into<String>,
+        P: Into<String>,
+    {
+        let users = users
+            .into_iter()
+            .map(|(username, password)| (username.into(), password.into()))
+            .collect::<HashMap<String, String>>();
+        Self::new(move |username, password| {
+            users
+                .get(username)
+                .is_some_and(|expected| constant_time_eq(expected.as_bytes(), password.as_bytes()))
+        })
+    }
+
+    /// Sets the realm of the `WWW-Authenticate` challenge. Defaults to `ngyn`.
+    pub fn realm(mut self, realm: &str) -> Self {
+        self.realm = realm.to_string();
+        self
+    }
+}
+
+impl AppState for BasicAuth {}
+
+/// The user authenticated by the [`BasicAuthGate`].
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+/// use ngyn::shared::auth::basic::{BasicAuthGate, BasicUser};
+///
+/// #[handler(gates = [BasicAuthGate])]
+/// async fn dashboard(user: BasicUser) -> String {
+///     format!("Hello {}", user.username)
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct BasicUser {
+    pub username: String,
+}
+
+impl Transformer<'_> for BasicUser {
+    /// Transforms the given `NgynContext` into the authenticated `BasicUser`.
+    ///
+    /// # Panics
+    /// Panics if the request wasn't authenticated by the [`BasicAuthGate`].
+    fn transform(cx: &mut NgynContext) -> Self {
+        match cx.extensions().get::<BasicUser>() {
+            Some(user) => user.clone(),
+            None => panic!("`{}` requires the `BasicAuthGate`", type_name::<Self>()),
+        }
+    }
+}
+
+/// A gate authenticating requests with HTTP Basic authentication.
+///
+/// The credentials are checked against the [`BasicAuth`] state of the application,
+/// the authenticated user is then available to handlers as a [`BasicUser`], and as a [`Principal`] without roles.
+/// Requests without valid credentials get a `401 Unauthorized` response with a `WWW-Authenticate` challenge.
+///
+/// # Panics
+/// Panics if the application has no [`BasicAuth`] state.
+pub struct BasicAuthGate;
+
+impl NgynGate for BasicAuthGate {
+    async fn can_activate(cx: &mut NgynContext<'_>) -> bool {
+        let config = cx
+            .state::<BasicAuth>()
+            .cloned()
+            .expect("`BasicAuthGate` requires a `BasicAuth` state");
+
+        let user = credentials(cx.request().headers(), "Basic")
+            .and_then(|credentials| STANDARD.decode(credentials).ok())
+            .and_then(|credentials| String::from_utf8(credentials).ok())
+            .and_then(|credentials| {
+                let (username, password) = credentials.split_once(':')?;
+                (config.verify)(username, password).then(|| BasicUser {
+                    username: username.to_string(),
+                })
+            });
+
+        match user {
+            Some(user) => {
+                cx.extensions_mut()
+                    .insert(Principal::new(user.username.as_str()));
+                cx.extensions_mut().insert(user);
+                true
+            }
+            None => {
+                let challenge = format!("Basic realm={}, charset=\"UTF-8\"", quote(&config.realm));
+                unauthorized(cx, challenge);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use http::{
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+        Request, StatusCode,
+    };
+
+    use super::*;
+
+    async fn authenticate(config: &BasicAuth, authorization: Option<&str>) -> (bool, String) {
+        let mut req = Request::builder().uri("/");
+        if let Some(authorization) = authorization {
+            req = req.header(AUTHORIZATION, authorization);
+        }
+        let req = req.body(Vec::new()).unwrap();
+
+        let mut cx = NgynContext::from_request(req);
+        Arc::make_mut(&mut cx.states).insert(TypeId::of::<BasicAuth>(), Arc::new(config.clone()));
+        let allowed = BasicAuthGate::can_activate(&mut cx).await;
+        match allowed {
+            true => (true, BasicUser::transform(&mut cx).username),
+            false => {
+                assert_eq!(cx.response_mut().status(), StatusCode::UNAUTHORIZED);
+                let challenge = cx.response_mut().headers()[WWW_AUTHENTICATE]
+                    .to_str()
+                    .unwrap();
+                (false, challenge.to_string())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_basic_auth_gate() {
+        let config = BasicAuth::users([("admin", "s3cr:t")]).realm("admin area");
+
+        let credentials = STANDARD.encode("admin:s3cr:t");
+        let authorization = format!("Basic {}", credentials);
+        assert_eq!(
+            authenticate(&config, Some(&authorization)).await,
+            (true, "admin".to_string())
+        );
+        let authorization = format!("basic  {}", credentials);
+        assert!(authenticate(&config, Some(&authorization)).await.0);
+
+        let challenge = "Basic realm=\"admin area\", charset=\"UTF-8\"".to_string();
+        for authorization in [
+            None,
+            Some(format!("Basic {}", STANDARD.encode("admin:wrong"))),
+            Some(format!("Basic {}", STANDARD.encode("root:s3cr:t"))),
+            Some(format!("Bearer {}", credentials)),
+            Some("Basic !!!".to_string()),
+        ] {
+            assert_eq!(
+                authenticate(&config, authorization.as_deref()).await,
+                (false, challenge.clone())
+            );
+        }
+    }
+}
@@ -0,0 +1,770 @@
+use std::{
+    any::type_name,
+    future::Future,
+    io,
+    ops::{Deref, DerefMut},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use jsonwebtoken::{decode, decode_header, errors::ErrorKind, Validation};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+pub use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey};
+
+use crate::{
+    auth::{credentials, policy::Principal, quote, unauthorized},
+    server::{context::AppState, NgynContext, Transformer},
+    NgynGate,
+};
+
+/// A key verifying the signature of tokens.
+struct Key {
+    /// The `kid` of the key, matched against the header of tokens.
+    id: Option<String>,
+    /// The algorithm the key is restricted to.
+    algorithm: Option<Algorithm>,
+    key: DecodingKey,
+}
+
+/// Configures JWT Bearer authentication, see [`JwtGate`].
+///
+/// Tokens are verified with a single key, or with the keys of a JWKS selected by the `kid` of their header.
+/// Their `exp` claim is required, `nbf`, `iss` and `aud` are checked when present, and required once configured.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+/// use ngyn::shared::auth::jwt::{Algorithm, DecodingKey, JwtAuth};
+///
+/// app.set_state(
+///     JwtAuth::new(DecodingKey::from_secret(b"secret"), Algorithm::HS256)
+///         .issuer(["https://auth.example.com"])
+///         .audience(["api"]),
+/// );
+///
+/// // or with the keys of an identity provider, fetched with your HTTP client
+/// app.set_state(JwtAuth::jwks_from(|| async { fetch_jwks("https://auth.example.com/jwks.json").await }).await?);
+/// ```
+#[derive(Clone)]
+pub struct JwtAuth {
+    keys: Arc<Vec<Key>>,
+    algorithms: Vec<Algorithm>,
+    issuer: Option<Vec<String>>,
+    audience: Option<Vec<String>>,
+    leeway: Duration,
+    realm: String,
+    roles_claim: String,
+}
+
+impl JwtAuth {
+    /// Verifies tokens signed with the given key and algorithm.
+    pub fn new(key: DecodingKey, algorithm: Algorithm) -> Self {
+        Self::with_keys(
+            vec![Key {
+                id: None,
+                algorithm: Some(algorithm),
+                key,
+            }],
+            vec![algorithm],
+        )
+    }
+
+    /// Verifies tokens signed with the keys of a JWKS.
+    ///
+    /// Keys with an unsupported type are skipped. Tokens signed with any asymmetric algorithm are accepted,
+    /// unless the key restricts its algorithm or [`JwtAuth::algorithms`] is set.
+    ///
+    /// ### Returns
+    ///
+    /// An `InvalidData` error if no key of the set is supported.
+    pub fn jwks(set: &JwkSet) -> io::Result<Self> {
+        let keys = set
+            .keys
+            .iter()
+            .filter_map(|jwk| {
+                Some(Key {
+                    id: jwk.common.key_id.clone(),
+                    algorithm: jwk
+                        .common
+                        .key_algorithm
+                        .and_then(|algorithm| algorithm.to_string().parse().ok()),
+                    key: DecodingKey::from_jwk(jwk).ok()?,
+                })
+            })
+            .collect::<Vec<_>>();
+        if keys.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the JWKS has no supported key",
+            ));
+        }
+
+        let algorithms = vec![
+            Algorithm::RS256,
+            Algorithm::RS384,
+            Algorithm::RS512,
+            Algorithm::PS256,
+            Algorithm::PS384,
+            Algorithm::PS512,
+            Algorithm::ES256,
+            Algorithm::ES384,
+            Algorithm::EdDSA,
+        ];
+        Ok(Self::with_keys(keys, algorithms))
+    }
+
+    /// Verifies tokens signed with the keys of a JWKS file.
+    pub fn jwks_from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::jwks(&parse_jwks(&std::fs::read(path)?)?)
+    }
+
+    /// Verifies tokens signed with the keys of a JWKS loaded from a source, e.g. fetched from an identity provider.
+    ///
+    /// The keys are loaded once.
+    ///
+    /// ### Arguments
+    ///
+    /// * `source` - A [`JwksSource`], like an async function returning the JWKS,
+    ///   or a [`JwksUrl`](url::JwksUrl) with the `jwks-url` feature.
+    pub async fn jwks_from<S: JwksSource>(source: S) -> io::Result<Self> {
+        Self::jwks(&source.load().await?)
+    }
+
+    fn with_keys(keys: Vec<Key>, algorithms: Vec<Algorithm>) -> Self {
+        Self {
+            keys: Arc::new(keys),
+            algorithms,
+            issuer: None,
+            audience: None,
+            leeway: Duration::from_secs(60),
+            realm: "ngyn".to_string(),
+            roles_claim: "roles".to_string(),
+        }
+    }
+
+    /// Sets the algorithms tokens can be signed with.
+    pub fn algorithms<I: IntoIterator<Item = Algorithm>>(mut self, algorithms: I) -> Self {
+        self.algorithms = algorithms.into_iter().collect();
+        self
+    }
+
+    /// Requires tokens to be issued by one of the given issuers.
+    pub fn issuer<I: IntoIterator<Item = S>, S: Into<String>>(mut self, issuers: I) -> Self {
+        self.issuer = Some(issuers.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Requires tokens to be intended for one of the given audiences.
+    pub fn audience<I: IntoIterator<Item = S>, S: Into<String>>(mut self, audiences: I) -> Self {
+        self.audience = Some(audiences.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets the clock skew tolerated when checking `exp` and `nbf`. Defaults to 60 seconds.
+    pub fn leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Sets the realm of the `WWW-Authenticate` challenge. Defaults to `ngyn`.
+    pub fn realm(mut self, realm: &str) -> Self {
+        self.realm = realm.to_string();
+        self
+    }
+
+    /// Sets the claim holding the roles of the [`Principal`] of a token. Defaults to `roles`.
+    pub fn roles_claim(mut self, claim: &str) -> Self {
+        self.roles_claim = claim.to_string();
+        self
+    }
+
+    /// Creates the principal of the claims of a token.
+    ///
+    /// Its id is the `sub` claim, its roles are read from the roles claim,
+    /// and its permissions from the `scope` or `permissions` claims.
+    fn principal(&self, claims: &Value) -> Principal {
+        // claims can hold a list, or a single value separated by spaces like `scope`
+        let values = |claim: &str| -> Vec<String> {
+            match claims.get(claim) {
+                Some(Value::String(values)) => {
+                    values.split_whitespace().map(String::from).collect()
+                }
+                Some(Value::Array(values)) => values
+                    .iter()
+                    .filter_map(|value| value.as_str().map(String::from))
+                    .collect(),
+                _ => Vec::new(),
+            }
+        };
+
+        let id = claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        Principal::new(id)
+            .roles(values(&self.roles_claim))
+            .permissions(values("scope"))
+            .permissions(values("permissions"))
+    }
+
+    /// Verifies a token and decodes its claims.
+    ///
+    /// ### Arguments
+    ///
+    /// * `token` - The encoded token.
+    ///
+    /// ### Returns
+    ///
+    /// The claims of the token, or the reason it was rejected.
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> jsonwebtoken::errors::Result<T> {
+        let header = decode_header(token)?;
+        if !self.algorithms.contains(&header.alg) {
+            return Err(ErrorKind::InvalidAlgorithm.into());
+        }
+
+        let key = match &header.kid {
+            Some(kid) => self
+                .keys
+                .iter()
+                .find(|key| key.id.as_deref() == Some(kid.as_str())),
+            // tokens without key id are only accepted when there is a single key
+            None if self.keys.len() == 1 => self.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| jsonwebtoken::errors::Error::from(ErrorKind::InvalidToken))?;
+        if key
+            .algorithm
+            .is_some_and(|algorithm| algorithm != header.alg)
+        {
+            return Err(ErrorKind::InvalidAlgorithm.into());
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = self.leeway.as_secs();
+        validation.validate_nbf = true;
+        validation.validate_aud = self.audience.is_some();
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(issuer);
+            validation.required_spec_claims.insert("iss".to_string());
+        }
+        if let Some(audience) = &self.audience {
+            validation.set_audience(audience);
+            validation.required_spec_claims.insert("aud".to_string());
+        }
+        Ok(decode(token, &key.key, &validation)?.claims)
+    }
+}
+
+impl AppState for JwtAuth {}
+
+fn parse_jwks(bytes: &[u8]) -> io::Result<JwkSet> {
+    serde_json::from_slice(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// A source of the JWKS verifying tokens, see [`JwtAuth::jwks_from`].
+///
+/// It's implemented by [`JwkSet`], and by async functions returning an `io::Result<JwkSet>`,
+/// so keys can be fetched with the HTTP client of the application.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::shared::auth::jwt::{JwkSet, JwtAuth};
+///
+/// let auth = JwtAuth::jwks_from(|| async {
+///     let res = reqwest::get("https://auth.example.com/.well-known/jwks.json").await.map_err(io::Error::other)?;
+///     res.json::<JwkSet>().await.map_err(io::Error::other)
+/// })
+/// .await?;
+/// ```
+pub trait JwksSource {
+    /// Loads the JWKS.
+    #[allow(async_fn_in_trait)]
+    async fn load(&self) -> io::Result<JwkSet>;
+}
+
+impl JwksSource for JwkSet {
+    async fn load(&self) -> io::Result<JwkSet> {
+        Ok(self.clone())
+    }
+}
+
+impl<F, Fut> JwksSource for F
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = io::Result<JwkSet>>,
+{
+    async fn load(&self) -> io::Result<JwkSet> {
+        self().await
+    }
+}
+
+#[cfg(feature = "jwks-url")]
+pub mod url {
+    use std::{io, time::Duration};
+
+    use bytes::Bytes;
+    use http::{header::HOST, uri::Scheme, Request, StatusCode, Uri};
+    use http_body_util::{BodyExt, Empty};
+    use hyper_util::rt::TokioIo;
+    use tokio::net::TcpStream;
+
+    use super::{parse_jwks, JwkSet, JwksSource};
+
+    /// A JWKS served over plain `http`, e.g. by an identity provider running alongside the application.
+    ///
+    /// `https` isn't supported, fetch those keys with the HTTP client of the application through a [`JwksSource`].
+    /// As keys fetched over `http` can be tampered with, the URL must be allowed with [`JwksUrl::allow_http`].
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn::shared::auth::jwt::{url::JwksUrl, JwtAuth};
+    ///
+    /// let auth = JwtAuth::jwks_from(
+    ///     JwksUrl::new("http://localhost:8080/.well-known/jwks.json").allow_http(true),
+    /// )
+    /// .await?;
+    /// ```
+    pub struct JwksUrl {
+        url: String,
+        allow_http: bool,
+        timeout: Duration,
+    }
+
+    impl JwksUrl {
+        /// Fetches the JWKS served at a URL, within 10 seconds.
+        pub fn new(url: &str) -> Self {
+            Self {
+                url: url.to_string(),
+                allow_http: false,
+                timeout: Duration::from_secs(10),
+            }
+        }
+
+        /// Sets whether the JWKS can be fetched over plain `http`, which is refused by default.
+        pub fn allow_http(mut self, allow: bool) -> Self {
+            self.allow_http = allow;
+            self
+        }
+
+        /// Sets how long fetching the JWKS may take.
+        pub fn timeout(mut self, timeout: Duration) -> Self {
+            self.timeout = timeout;
+            self
+        }
+    }
+
+    impl JwksSource for JwksUrl {
+        async fn load(&self) -> io::Result<JwkSet> {
+            let invalid =
+                |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+            let uri = self
+                .url
+                .parse::<Uri>()
+                .map_err(|_| invalid("invalid JWKS url"))?;
+            if uri.scheme() != Some(&Scheme::HTTP) {
+                return Err(invalid("only http JWKS urls are supported"));
+            }
+            if !self.allow_http {
+                return Err(invalid(
+                    "http JWKS urls must be allowed with `JwksUrl::allow_http`",
+                ));
+            }
+
+            let body = tokio::time::timeout(self.timeout, fetch(uri))
+                .await
+                .map_err(|_| {
+                    io::Error::new(io::ErrorKind::TimedOut, "fetching the JWKS timed out")
+                })??;
+            parse_jwks(&body)
+        }
+    }
+
+    /// Fetches the body of an `http` URL.
+    async fn fetch(uri: Uri) -> io::Result<Bytes> {
+        let authority = uri
+            .authority()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid JWKS url"))?
+            .clone();
+
+        let stream =
+            TcpStream::connect((authority.host(), authority.port_u16().unwrap_or(80))).await?;
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(io::Error::other)?;
+        let req = Request::get(uri.path_and_query().map_or("/", |path| path.as_str()))
+            .header(HOST, authority.as_str())
+            .body(Empty::<Bytes>::new())
+            .map_err(io::Error::other)?;
+
+        let fetch = async {
+            let res = sender.send_request(req).await.map_err(io::Error::other)?;
+            if res.status() != StatusCode::OK {
+                return Err(io::Error::other(format!(
+                    "fetching the JWKS failed with {}",
+                    res.status()
+                )));
+            }
+            let body = res.into_body().collect().await.map_err(io::Error::other)?;
+            Ok(body.to_bytes())
+        };
+        // the connection is driven until the response is read
+        futures_util::pin_mut!(fetch, connection);
+        match futures_util::future::select(fetch, connection).await {
+            futures_util::future::Either::Left((body, _)) => body,
+            futures_util::future::Either::Right((result, fetch)) => {
+                result.map_err(io::Error::other)?;
+                fetch.await
+            }
+        }
+    }
+}
+
+/// The claims of the token authenticated by the [`JwtGate`], as JSON.
+#[derive(Clone)]
+struct RawClaims(Value);
+
+/// The claims of the token authenticated by the [`JwtGate`].
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+/// use ngyn::shared::auth::jwt::{Claims, JwtGate};
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     sub: String,
+///     scope: String,
+/// }
+///
+/// #[handler(gates = [JwtGate])]
+/// async fn me(claims: Claims<User>) -> String {
+///     format!("Hello {}", claims.sub)
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Claims<T>(pub T);
+
+impl<T> Deref for Claims<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Claims<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T: DeserializeOwned> Transformer<'_> for Claims<T> {
+    /// Transforms the given `NgynContext` into the `Claims` of the authenticated token.
+    ///
+    /// # Panics
+    /// Panics if the request wasn't authenticated by the [`JwtGate`], or if its claims can't be deserialized into `T`.
+    fn transform(cx: &mut NgynContext) -> Self {
+        let Some(RawClaims(claims)) = cx.extensions().get::<RawClaims>() else {
+            panic!("`{}` requires the `JwtGate`", type_name::<Self>());
+        };
+        match T::deserialize(claims) {
+            Ok(claims) => Claims(claims),
+            Err(e) => panic!("invalid claims for `{}`: {}", type_name::<T>(), e),
+        }
+    }
+}
+
+/// A gate authenticating requests with a JWT Bearer token.
+///
+/// Tokens are verified with the [`JwtAuth`] state of the application,
+/// their claims are then available to handlers with the [`Claims`] extractor, and as a [`Principal`].
+/// Requests without a valid token get a `401 Unauthorized` response with a `WWW-Authenticate` challenge.
+///
+/// # Panics
+/// Panics if the application has no [`JwtAuth`] state.
+pub struct JwtGate;
+
+impl NgynGate for JwtGate {
+    async fn can_activate(cx: &mut NgynContext<'_>) -> bool {
+        let config = cx
+            .state::<JwtAuth>()
+            .cloned()
+            .expect("`JwtGate` requires a `JwtAuth` state");
+        let mut challenge = format!("Bearer realm={}", quote(&config.realm));
+
+        match credentials(cx.request().headers(), "Bearer").map(|token| config.decode(token)) {
+            Some(Ok(claims)) => {
+                cx.extensions_mut().insert(config.principal(&claims));
+                cx.extensions_mut().insert(RawClaims(claims));
+                return true;
+            }
+            Some(Err(e)) => {
+                let description = match e.kind() {
+                    ErrorKind::ExpiredSignature => "the token expired",
+                    ErrorKind::ImmatureSignature => "the token isn't valid yet",
+                    ErrorKind::InvalidIssuer => "the token has an invalid issuer",
+                    ErrorKind::InvalidAudience => "the token has an invalid audience",
+                    ErrorKind::MissingRequiredClaim(_) => "the token misses a required claim",
+                    _ => "the token is invalid",
+                };
+                challenge.push_str(", error=\"invalid_token\", error_description=");
+                challenge.push_str(&quote(description));
+            }
+            None => {}
+        }
+        unauthorized(cx, challenge);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        any::TypeId,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use http::{
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+        Request, StatusCode,
+    };
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct User {
+        sub: String,
+    }
+
+    fn token(claims: Value, kid: Option<&str>, secret: &[u8]) -> String {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = kid.map(|kid| kid.to_string());
+        encode(&header, &claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    fn exp(offset: i64) -> i64 {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        now.as_secs() as i64 + offset
+    }
+
+    async fn authenticate(config: &JwtAuth, token: Option<&str>) -> Result<String, String> {
+        let mut req = Request::builder().uri("/");
+        if let Some(token) = token {
+            req = req.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let mut cx = NgynContext::from_request(req.body(Vec::new()).unwrap());
+        Arc::make_mut(&mut cx.states).insert(TypeId::of::<JwtAuth>(), Arc::new(config.clone()));
+
+        if JwtGate::can_activate(&mut cx).await {
+            return Ok(Claims::<User>::transform(&mut cx).0.sub);
+        }
+        assert_eq!(cx.response_mut().status(), StatusCode::UNAUTHORIZED);
+        let challenge = cx.response_mut().headers()[WWW_AUTHENTICATE]
+            .to_str()
+            .unwrap();
+        Err(challenge.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_jwt_gate() {
+        let config = JwtAuth::new(DecodingKey::from_secret(b"secret"), Algorithm::HS256)
+            .issuer(["ngyn"])
+            .realm("api");
+
+        let valid = token(
+            json!({ "sub": "ada", "iss": "ngyn", "exp": exp(60) }),
+            None,
+            b"secret",
+        );
+        assert_eq!(
+            authenticate(&config, Some(&valid)).await,
+            Ok("ada".to_string())
+        );
+
+        assert_eq!(
+            authenticate(&config, None).await,
+            Err("Bearer realm=\"api\"".to_string())
+        );
+        let expired = token(
+            json!({ "sub": "ada", "iss": "ngyn", "exp": exp(-120) }),
+            None,
+            b"secret",
+        );
+        assert_eq!(
+            authenticate(&config, Some(&expired)).await,
+            Err("Bearer realm=\"api\", error=\"invalid_token\", error_description=\"the token expired\"".to_string())
+        );
+
+        for token in [
+            token(
+                json!({ "sub": "ada", "iss": "ngyn", "exp": exp(60) }),
+                None,
+                b"wrong",
+            ),
+            token(
+                json!({ "sub": "ada", "iss": "other", "exp": exp(60) }),
+                None,
+                b"secret",
+            ),
+            token(json!({ "sub": "ada", "exp": exp(60) }), None, b"secret"),
+            token(json!({ "sub": "ada", "iss": "ngyn" }), None, b"secret"),
+            "not.a.token".to_string(),
+        ] {
+            let challenge = authenticate(&config, Some(&token)).await.unwrap_err();
+            assert!(
+                challenge.contains("error=\"invalid_token\""),
+                "{}",
+                challenge
+            );
+        }
+    }
+
+    #[test]
+    fn test_principal() {
+        let config = JwtAuth::new(DecodingKey::from_secret(b"secret"), Algorithm::HS256)
+            .roles_claim("groups");
+        let principal = config.principal(&json!({
+            "sub": "ada",
+            "groups": ["admin", "staff"],
+            "scope": "posts:read posts:write",
+            "permissions": ["users:read"],
+        }));
+        assert_eq!(
+            principal,
+            Principal::new("ada")
+                .roles(["admin", "staff"])
+                .permissions(["posts:read", "posts:write", "users:read"])
+        );
+        assert_eq!(config.principal(&json!({})), Principal::new(""));
+    }
+
+    fn jwks() -> Value {
+        // JWKS of HMAC keys, encoded in base64url
+        json!({
+            "keys": [
+                { "kty": "oct", "kid": "one", "alg": "HS256", "k": "c2VjcmV0LW9uZQ" },
+                { "kty": "oct", "kid": "two", "alg": "HS256", "k": "c2VjcmV0LXR3bw" },
+            ]
+        })
+    }
+
+    #[tokio::test]
+    async fn test_jwks() {
+        let set = serde_json::from_value::<JwkSet>(jwks()).unwrap();
+        let config = JwtAuth::jwks(&set).unwrap().algorithms([Algorithm::HS256]);
+
+        let claims = json!({ "sub": "ada", "exp": exp(60) });
+        let one = token(claims.clone(), Some("one"), b"secret-one");
+        assert_eq!(
+            authenticate(&config, Some(&one)).await,
+            Ok("ada".to_string())
+        );
+        let two = token(claims.clone(), Some("two"), b"secret-two");
+        assert_eq!(
+            authenticate(&config, Some(&two)).await,
+            Ok("ada".to_string())
+        );
+
+        // the key is selected by the `kid` of the token
+        let mismatch = token(claims.clone(), Some("two"), b"secret-one");
+        assert!(authenticate(&config, Some(&mismatch)).await.is_err());
+        let missing = token(claims.clone(), None, b"secret-one");
+        assert!(authenticate(&config, Some(&missing)).await.is_err());
+
+        // only asymmetric algorithms are accepted by default
+        let config = JwtAuth::jwks(&set).unwrap();
+        assert!(authenticate(&config, Some(&one)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_jwks_from() {
+        let set = parse_jwks(jwks().to_string().as_bytes()).unwrap();
+        let token = token(
+            json!({ "sub": "ada", "exp": exp(60) }),
+            Some("one"),
+            b"secret-one",
+        );
+
+        let config = JwtAuth::jwks_from(set.clone()).await.unwrap();
+        let config = config.algorithms([Algorithm::HS256]);
+        assert_eq!(
+            authenticate(&config, Some(&token)).await,
+            Ok("ada".to_string())
+        );
+
+        let config = JwtAuth::jwks_from(|| async { Ok(set.clone()) })
+            .await
+            .unwrap()
+            .algorithms([Algorithm::HS256]);
+        assert_eq!(
+            authenticate(&config, Some(&token)).await,
+            Ok("ada".to_string())
+        );
+
+        let error = JwtAuth::jwks_from(|| async { Err(io::Error::other("unreachable")) }).await;
+        assert_eq!(error.err().unwrap().kind(), io::ErrorKind::Other);
+    }
+
+    #[cfg(feature = "jwks-url")]
+    #[tokio::test]
+    async fn test_jwks_url() {
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        use super::url::JwksUrl;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = async {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut req = [0; 1024];
+            let _ = stream.read(&mut req).await.unwrap();
+            let body = jwks().to_string();
+            let res = format!(
+                "HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(res.as_bytes()).await.unwrap();
+        };
+
+        let url = format!("http://{}/.well-known/jwks.json", addr);
+        let source = JwksUrl::new(&url).allow_http(true);
+        let (config, _) = tokio::join!(JwtAuth::jwks_from(source), server);
+        let config = config.unwrap().algorithms([Algorithm::HS256]);
+        let token = token(
+            json!({ "sub": "ada", "exp": exp(60) }),
+            Some("one"),
+            b"secret-one",
+        );
+        assert_eq!(
+            authenticate(&config, Some(&token)).await,
+            Ok("ada".to_string())
+        );
+
+        // `http` must be allowed, and `https` isn't supported
+        let error = JwtAuth::jwks_from(JwksUrl::new(&url)).await;
+        assert_eq!(error.err().unwrap().kind(), io::ErrorKind::InvalidInput);
+        let error = JwtAuth::jwks_from(JwksUrl::new("https://localhost/jwks.json")).await;
+        assert_eq!(error.err().unwrap().kind(), io::ErrorKind::InvalidInput);
+
+        // a server that never responds
+        let source = JwksUrl::new(&url)
+            .allow_http(true)
+            .timeout(Duration::from_millis(50));
+        let error = JwtAuth::jwks_from(source).await;
+        assert_eq!(error.err().unwrap().kind(), io::ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,42 @@
+pub mod basic;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+pub mod policy;
+
+use http::{
+    header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, WWW_AUTHENTICATE},
+    HeaderMap, HeaderValue, StatusCode,
+};
+
+use crate::server::NgynContext;
+
+/// Returns the credentials of the `Authorization` header of a request, if it uses the given scheme.
+pub(crate) fn credentials<'a>(headers: &'a HeaderMap, scheme: &str) -> Option<&'a str> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (name, credentials) = value.trim().split_once(' ')?;
+    name.eq_ignore_ascii_case(scheme)
+        .then(|| credentials.trim())
+        .filter(|credentials| !credentials.is_empty())
+}
+
+/// Sets a `401 Unauthorized` response with the given challenge.
+pub(crate) fn unauthorized(cx: &mut NgynContext<'_>, challenge: String) {
+    let res = cx.response_mut();
+    *res.status_mut() = StatusCode::UNAUTHORIZED;
+    *res.body_mut() = Default::default();
+    res.headers_mut().remove(CONTENT_LENGTH);
+    res.headers_mut().remove(CONTENT_TYPE);
+    if let Ok(challenge) = HeaderValue::from_str(&challenge) {
+        res.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+    }
+}
+
+/// Quotes a parameter of a challenge.
+pub(crate) fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Compares two byte strings in constant time, to not leak secrets through timing.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
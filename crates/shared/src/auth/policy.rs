@@ -0,0 +1,443 @@
+use std::{
+    any::type_name,
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    sync::Arc,
+};
+
+use http::{header::WWW_AUTHENTICATE, HeaderValue, StatusCode};
+
+use crate::{
+    core::gate::GateOutcome,
+    server::{context::AppState, NgynContext, NgynResponse, Transformer},
+    NgynGate,
+};
+
+/// The authenticated user or service of a request.
+///
+/// Authentication gates insert it in the extensions of the context, e.g. the `JwtGate` from the claims of the token. Custom gates can insert their own with `cx.extensions_mut().insert(principal)`.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// #[handler(gates = [JwtGate])]
+/// async fn me(principal: Principal) -> String {
+///     format!("Hello {}, admin: {}", principal.id(), principal.has_role("admin"))
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Principal {
+    id: String,
+    roles: HashSet<String>,
+    permissions: HashSet<String>,
+    hierarchy: Option<RoleHierarchy>,
+}
+
+impl PartialEq for Principal {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.roles == other.roles && self.permissions == other.permissions
+    }
+}
+
+impl Principal {
+    /// Creates a principal without roles or permissions.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds roles to the principal.
+    pub fn roles<I: IntoIterator<Item = S>, S: Into<String>>(mut self, roles: I) -> Self {
+        self.roles.extend(roles.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds permissions to the principal.
+    pub fn permissions<I: IntoIterator<Item = S>, S: Into<String>>(
+        mut self,
+        permissions: I,
+    ) -> Self {
+        self.permissions
+            .extend(permissions.into_iter().map(Into::into));
+        self
+    }
+
+    /// The id of the principal, e.g. the `sub` claim of a token.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The roles assigned to the principal, without the roles they include.
+    pub fn assigned_roles(&self) -> impl Iterator<Item = &str> {
+        self.roles.iter().map(String::as_str)
+    }
+
+    /// Checks if the principal has a role, directly or through the [`RoleHierarchy`] of the application.
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.contains(role)
+            || self.hierarchy.as_ref().is_some_and(|hierarchy| {
+                self.roles
+                    .iter()
+                    .any(|assigned| hierarchy.includes(assigned, role))
+            })
+    }
+
+    /// Checks if the principal has a permission.
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.contains(permission)
+    }
+
+    /// Checks a resource-based policy against a resource.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// if !principal.can::<DocumentOwner, _>(&document) {
+    ///     return Err(StatusCode::FORBIDDEN);
+    /// }
+    /// ```
+    pub fn can<P: ResourcePolicy<R>, R: ?Sized>(&self, resource: &R) -> bool {
+        P::authorize(self, resource)
+    }
+
+    /// Returns the principal of a request, with the [`RoleHierarchy`] of the application.
+    pub fn from_context(cx: &NgynContext<'_>) -> Option<Self> {
+        let mut principal = cx.extensions().get::<Principal>()?.clone();
+        principal.hierarchy = cx.state::<RoleHierarchy>().cloned();
+        Some(principal)
+    }
+}
+
+impl Transformer<'_> for Principal {
+    /// Transforms the given `NgynContext` into the authenticated `Principal`.
+    ///
+    /// # Panics
+    /// Panics if the request wasn't authenticated, extract an `Option<Principal>` for routes where authentication is optional.
+    fn transform(cx: &mut NgynContext) -> Self {
+        match Principal::from_context(cx) {
+            Some(principal) => principal,
+            None => panic!("`{}` requires an authentication gate", type_name::<Self>()),
+        }
+    }
+}
+
+impl Transformer<'_> for Option<Principal> {
+    fn transform(cx: &mut NgynContext) -> Self {
+        Principal::from_context(cx)
+    }
+}
+
+/// Roles including other roles, e.g. an `admin` is also an `editor`.
+///
+/// Add it to the state of the application to apply it to every [`Principal`].
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// app.set_state(
+///     RoleHierarchy::new()
+///         .include("admin", ["editor"])
+///         .include("editor", ["viewer"]),
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RoleHierarchy {
+    roles: Arc<HashMap<String, Vec<String>>>,
+}
+
+impl RoleHierarchy {
+    /// Creates an empty hierarchy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes a role include other roles, and the roles those include.
+    pub fn include<I: IntoIterator<Item = S>, S: Into<String>>(
+        mut self,
+        role: &str,
+        included: I,
+    ) -> Self {
+        Arc::make_mut(&mut self.roles)
+            .entry(role.to_string())
+            .or_default()
+            .extend(included.into_iter().map(Into::into));
+        self
+    }
+
+    /// Checks if a role includes another one, directly or transitively.
+    pub fn includes(&self, role: &str, included: &str) -> bool {
+        let mut visited = HashSet::new();
+        let mut pending = vec![role];
+        while let Some(role) = pending.pop() {
+            if role == included {
+                return true;
+            }
+            if visited.insert(role) {
+                if let Some(roles) = self.roles.get(role) {
+                    pending.extend(roles.iter().map(String::as_str));
+                }
+            }
+        }
+        false
+    }
+}
+
+impl AppState for RoleHierarchy {}
+
+/// The challenge of requests reaching a policy without a principal, e.g. routes missing an authentication gate.
+///
+/// Add it to the state of the application for those requests to get a `401 Unauthorized` response
+/// with this `WWW-Authenticate` challenge. Without it, authentication is expected to be handled elsewhere,
+/// and those requests are `403 Forbidden`.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// app.set_state(AuthChallenge::new("Bearer realm=\"api\""));
+/// ```
+#[derive(Clone, Debug)]
+pub struct AuthChallenge(HeaderValue);
+
+impl AuthChallenge {
+    /// Creates the challenge of the `WWW-Authenticate` header.
+    ///
+    /// # Panics
+    /// Panics if the challenge isn't a valid header value.
+    pub fn new(challenge: &str) -> Self {
+        match HeaderValue::from_str(challenge) {
+            Ok(challenge) => Self(challenge),
+            Err(_) => panic!("AuthChallenge: `{}` isn't a valid header value", challenge),
+        }
+    }
+}
+
+impl AppState for AuthChallenge {}
+
+/// The outcome of a request without a principal, see [`AuthChallenge`].
+fn unauthenticated(cx: &NgynContext<'_>) -> GateOutcome {
+    match cx.state::<AuthChallenge>() {
+        Some(AuthChallenge(challenge)) => {
+            let mut res = NgynResponse::default();
+            *res.status_mut() = StatusCode::UNAUTHORIZED;
+            res.headers_mut()
+                .insert(WWW_AUTHENTICATE, challenge.clone());
+            GateOutcome::Respond(res)
+        }
+        None => GateOutcome::Deny(StatusCode::FORBIDDEN),
+    }
+}
+
+/// A policy authorizing access to a route.
+///
+/// Policies are checked with the [`Authorize`] gate, or with `#[handler(authorize = Policy)]`,
+/// after the request was authenticated.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// struct CanPublish;
+///
+/// impl Policy for CanPublish {
+///     async fn authorize(principal: &Principal, _cx: &mut NgynContext<'_>) -> bool {
+///         principal.has_role("editor") || principal.has_permission("posts:publish")
+///     }
+/// }
+///
+/// #[handler(gates = [JwtGate], authorize = CanPublish)]
+/// async fn publish(id: Param) -> String { ... }
+/// ```
+pub trait Policy {
+    /// Checks if the principal can access the route.
+    ///
+    /// ### Arguments
+    ///
+    /// * `principal` - The authenticated principal of the request.
+    /// * `cx` - The request context.
+    ///
+    /// ### Returns
+    ///
+    /// Returns `true` if the principal can access the route, `false` otherwise.
+    #[allow(async_fn_in_trait)]
+    async fn authorize(principal: &Principal, cx: &mut NgynContext<'_>) -> bool;
+}
+
+/// A policy authorizing access to a resource, e.g. only the owner of a document can edit it.
+///
+/// Handlers check it once they loaded the resource, with [`Principal::can`].
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// struct DocumentOwner;
+///
+/// impl ResourcePolicy<Document> for DocumentOwner {
+///     fn authorize(principal: &Principal, document: &Document) -> bool {
+///         document.owner_id == principal.id() || principal.has_role("admin")
+///     }
+/// }
+/// ```
+pub trait ResourcePolicy<R: ?Sized> {
+    /// Checks if the principal can access the resource.
+    fn authorize(principal: &Principal, resource: &R) -> bool;
+}
+
+/// A gate checking a [`Policy`].
+///
+/// Requests the policy denies are `403 Forbidden`, see [`AuthChallenge`] for requests without a principal.
+pub struct Authorize<P>(PhantomData<P>);
+
+impl<P: Policy> NgynGate for Authorize<P> {
+    async fn check(cx: &mut NgynContext<'_>) -> GateOutcome {
+        let Some(principal) = Principal::from_context(cx) else {
+            return unauthenticated(cx);
+        };
+        P::authorize(&principal, cx).await.into()
+    }
+}
+
+/// Checks that the principal of a request has a role, this is what `#[handler(authorize = "role")]` calls.
+///
+/// Requests without the role are `403 Forbidden`, see [`AuthChallenge`] for requests without a principal.
+pub fn require_role(cx: &NgynContext<'_>, role: &str) -> GateOutcome {
+    match Principal::from_context(cx) {
+        Some(principal) => principal.has_role(role).into(),
+        None => unauthenticated(cx),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use http::Request;
+
+    use super::*;
+
+    struct Document {
+        owner: String,
+    }
+
+    struct DocumentOwner;
+
+    impl ResourcePolicy<Document> for DocumentOwner {
+        fn authorize(principal: &Principal, document: &Document) -> bool {
+            document.owner == principal.id() || principal.has_role("admin")
+        }
+    }
+
+    struct CanPublish;
+
+    impl Policy for CanPublish {
+        async fn authorize(principal: &Principal, _cx: &mut NgynContext<'_>) -> bool {
+            principal.has_role("editor") || principal.has_permission("posts:publish")
+        }
+    }
+
+    fn context(principal: Option<Principal>) -> NgynContext<'static> {
+        let req = Request::builder().uri("/").body(Vec::new()).unwrap();
+        let mut cx = NgynContext::from_request(req);
+        let hierarchy = RoleHierarchy::new()
+            .include("admin", ["editor"])
+            .include("editor", ["viewer"])
+            // cycles are fine
+            .include("viewer", ["editor"]);
+        Arc::make_mut(&mut cx.states).insert(TypeId::of::<RoleHierarchy>(), Arc::new(hierarchy));
+        if let Some(principal) = principal {
+            cx.extensions_mut().insert(principal);
+        }
+        cx
+    }
+
+    fn status(outcome: GateOutcome) -> StatusCode {
+        match outcome {
+            GateOutcome::Allow => StatusCode::OK,
+            GateOutcome::Deny(status) => status,
+            GateOutcome::Respond(res) => res.status(),
+        }
+    }
+
+    #[test]
+    fn test_role_hierarchy() {
+        let cx = context(Some(Principal::new("ada").roles(["admin"])));
+        let principal = Principal::from_context(&cx).unwrap();
+        assert!(principal.has_role("admin"));
+        assert!(principal.has_role("viewer"));
+        assert!(!principal.has_role("owner"));
+
+        let cx = context(Some(Principal::new("bob").roles(["viewer"])));
+        let principal = Principal::from_context(&cx).unwrap();
+        assert!(principal.has_role("editor"));
+        assert!(!principal.has_role("admin"));
+
+        assert_eq!(status(require_role(&cx, "editor")), StatusCode::OK);
+        assert_eq!(status(require_role(&cx, "admin")), StatusCode::FORBIDDEN);
+        assert_eq!(
+            status(require_role(&context(None), "admin")),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn test_resource_policy() {
+        let document = Document {
+            owner: "ada".to_string(),
+        };
+        assert!(Principal::new("ada").can::<DocumentOwner, _>(&document));
+        assert!(!Principal::new("bob").can::<DocumentOwner, _>(&document));
+
+        let cx = context(Some(Principal::new("bob").roles(["admin"])));
+        let principal = Principal::from_context(&cx).unwrap();
+        assert!(principal.can::<DocumentOwner, _>(&document));
+    }
+
+    #[tokio::test]
+    async fn test_authorize() {
+        let mut cx = context(Some(Principal::new("ada").roles(["admin"])));
+        assert_eq!(
+            status(Authorize::<CanPublish>::check(&mut cx).await),
+            StatusCode::OK
+        );
+
+        let mut cx = context(Some(Principal::new("bob").permissions(["posts:publish"])));
+        assert_eq!(
+            status(Authorize::<CanPublish>::check(&mut cx).await),
+            StatusCode::OK
+        );
+
+        let mut cx = context(Some(Principal::new("eve")));
+        assert_eq!(
+            status(Authorize::<CanPublish>::check(&mut cx).await),
+            StatusCode::FORBIDDEN
+        );
+
+        let mut cx = context(None);
+        assert_eq!(
+            status(Authorize::<CanPublish>::check(&mut cx).await),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auth_challenge() {
+        let mut cx = context(None);
+        let challenge = AuthChallenge::new("Bearer realm=\"api\"");
+        Arc::make_mut(&mut cx.states).insert(TypeId::of::<AuthChallenge>(), Arc::new(challenge));
+
+        for outcome in [
+            Authorize::<CanPublish>::check(&mut cx).await,
+            require_role(&cx, "admin"),
+        ] {
+            let GateOutcome::Respond(res) = outcome else {
+                panic!("expected a challenge");
+            };
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(res.headers()[WWW_AUTHENTICATE], "Bearer realm=\"api\"");
+        }
+    }
+}
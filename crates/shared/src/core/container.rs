@@ -0,0 +1,636 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::Deref,
+    sync::{Arc, OnceLock},
+};
+
+use crate::server::{
+    context::{AppState, State},
+    NgynContext, Transformer,
+};
+
+type Instance = Arc<dyn Any + Send + Sync>;
+
+/// Determines how long an instance of a service lives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lifetime {
+    /// A single instance is shared by the whole application.
+    Singleton,
+    /// A new instance is created for each request, and shared within the request.
+    Scoped,
+    /// A new instance is created each time the service is resolved.
+    Transient,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DependencyKind {
+    Service,
+    State,
+}
+
+/// Describes a dependency of a service, used to validate the dependency graph.
+#[derive(Clone, Copy, Debug)]
+pub struct Dependency {
+    kind: DependencyKind,
+    pub(crate) type_id: TypeId,
+    pub(crate) type_name: &'static str,
+}
+
+impl Dependency {
+    /// A dependency on a service registered in the container.
+    pub fn service<T: 'static>() -> Self {
+        Self {
+            kind: DependencyKind::Service,
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+        }
+    }
+
+    /// A dependency on an application state.
+    pub fn state<T: AppState>() -> Self {
+        Self {
+            kind: DependencyKind::State,
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+        }
+    }
+}
+
+/// Trait for types that can be constructed by the [`Container`].
+///
+/// This is implemented by the `Service` derive macro, fields marked with `#[inject]` are resolved from the container,
+/// other fields are created with `Default::default()`.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// #[derive(Service)]
+/// struct UserRepository {
+///     #[inject]
+///     pool: State<DbPool>,
+/// }
+///
+/// #[derive(Service)]
+/// struct UserService {
+///     #[inject]
+///     repository: Inject<UserRepository>,
+///     cache: HashMap<u32, String>,
+/// }
+///
+/// app.add_state(DbPool::connect());
+/// app.add_service::<UserRepository>(Lifetime::Singleton);
+/// app.add_service::<UserService>(Lifetime::Scoped);
+/// ```
+pub trait Injectable: Sized {
+    /// Returns the dependencies of the service.
+    fn dependencies() -> Vec<Dependency> {
+        Vec::new()
+    }
+
+    /// Constructs the service, resolving its dependencies with the given resolver.
+    fn construct(resolver: &mut Resolver<'_>) -> Self;
+}
+
+/// Trait for values that can be injected into a service.
+pub trait Resolve: Sized {
+    /// Returns the dependency required to resolve the value.
+    fn dependency() -> Dependency;
+
+    /// Resolves the value with the given resolver.
+    fn resolve(resolver: &mut Resolver<'_>) -> Self;
+}
+
+/// A service resolved from the [`Container`], according to its [`Lifetime`].
+///
+/// `Inject` can be used as a field of a service marked with `#[inject]`, or as a handler argument.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// #[handler]
+/// async fn list_users(users: Inject<UserService>) -> String {
+///     users.list().await
+/// }
+/// ```
+pub struct Inject<T>(pub Arc<T>);
+
+impl<T> Clone for Inject<T> {
+    fn clone(&self) -> Self {
+        Inject(self.0.clone())
+    }
+}
+
+impl<T> Deref for Inject<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: Injectable + Send + Sync + 'static> Resolve for Inject<T> {
+    fn dependency() -> Dependency {
+        Dependency::service::<T>()
+    }
+
+    fn resolve(resolver: &mut Resolver<'_>) -> Self {
+        Inject(resolver.resolve::<T>())
+    }
+}
+
+impl<T: AppState> Resolve for State<T> {
+    fn dependency() -> Dependency {
+        Dependency::state::<T>()
+    }
+
+    fn resolve(resolver: &mut Resolver<'_>) -> Self {
+        resolver.state::<T>()
+    }
+}
+
+impl<T: Injectable + Send + Sync + 'static> Transformer<'_> for Inject<T> {
+    /// Transforms the given `NgynContext` into an `Inject` instance.
+    ///
+    /// # Panics
+    /// Panics if `T` hasn't been registered with `app.add_service`.
+    fn transform(cx: &mut NgynContext) -> Self {
+        Inject::resolve(&mut Resolver::new(cx))
+    }
+}
+
+struct Registration {
+    lifetime: Lifetime,
+    type_name: &'static str,
+    dependencies: fn() -> Vec<Dependency>,
+    construct: fn(&mut Resolver<'_>) -> Instance,
+    instance: OnceLock<Instance>,
+}
+
+/// A dependency injection container.
+///
+/// Services are registered with a [`Lifetime`] and resolved on demand.
+#[derive(Default)]
+pub struct Container {
+    services: HashMap<TypeId, Registration>,
+}
+
+impl Container {
+    /// Registers a service, replacing any previous registration of the same service.
+    ///
+    /// ### Arguments
+    ///
+    /// * `lifetime` - The lifetime of the instances of the service.
+    pub fn register<T: Injectable + Send + Sync + 'static>(&mut self, lifetime: Lifetime) {
+        self.services.insert(
+            TypeId::of::<T>(),
+            Registration {
+                lifetime,
+                type_name: type_name::<T>(),
+                dependencies: T::dependencies,
+                construct: |resolver| Arc::new(T::construct(resolver)),
+                instance: OnceLock::new(),
+            },
+        );
+    }
+
+    /// Checks that a service is registered.
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.services.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Validates the dependency graph of the registered services.
+    ///
+    /// ### Arguments
+    ///
+    /// * `states` - The application states available to the services.
+    pub(crate) fn validate(
+        &self,
+        states: &HashMap<TypeId, Instance>,
+    ) -> Result<(), ContainerError> {
+        for registration in self.services.values() {
+            for dependency in (registration.dependencies)() {
+                let found = match dependency.kind {
+                    DependencyKind::Service => self.services.contains_key(&dependency.type_id),
+                    DependencyKind::State => states.contains_key(&dependency.type_id),
+                };
+                if !found {
+                    return Err(match dependency.kind {
+                        DependencyKind::Service => ContainerError::MissingService {
+                            service: registration.type_name,
+                            dependency: dependency.type_name,
+                        },
+                        DependencyKind::State => ContainerError::MissingState {
+                            service: registration.type_name,
+                            state: dependency.type_name,
+                        },
+                    });
+                }
+            }
+        }
+
+        let mut visited = HashSet::new();
+        for type_id in self.services.keys() {
+            self.check_cycle(*type_id, &mut Vec::new(), &mut visited)?;
+        }
+
+        for registration in self.services.values() {
+            if registration.lifetime == Lifetime::Singleton {
+                if let Some(scoped) = self.find_scoped(registration) {
+                    return Err(ContainerError::ScopedInSingleton {
+                        singleton: registration.type_name,
+                        scoped,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn service_dependencies(&self, registration: &Registration) -> Vec<TypeId> {
+        (registration.dependencies)()
+            .into_iter()
+            .filter(|dependency| dependency.kind == DependencyKind::Service)
+            .map(|dependency| dependency.type_id)
+            .collect()
+    }
+
+    fn check_cycle(
+        &self,
+        type_id: TypeId,
+        path: &mut Vec<TypeId>,
+        visited: &mut HashSet<TypeId>,
+    ) -> Result<(), ContainerError> {
+        if let Some(start) = path.iter().position(|id| *id == type_id) {
+            let mut cycle: Vec<_> = path[start..]
+                .iter()
+                .map(|id| self.services[id].type_name)
+                .collect();
+            cycle.push(self.services[&type_id].type_name);
+            return Err(ContainerError::Cycle(cycle));
+        }
+        if !visited.insert(type_id) {
+            return Ok(());
+        }
+
+        path.push(type_id);
+        for dependency in self.service_dependencies(&self.services[&type_id]) {
+            self.check_cycle(dependency, path, visited)?;
+        }
+        path.pop();
+
+        Ok(())
+    }
+
+    /// Finds a scoped service a singleton would capture, either directly or through transient services.
+    fn find_scoped(&self, registration: &Registration) -> Option<&'static str> {
+        for dependency in self.service_dependencies(registration) {
+            let dependency = &self.services[&dependency];
+            match dependency.lifetime {
+                Lifetime::Scoped => return Some(dependency.type_name),
+                Lifetime::Transient => {
+                    if let Some(scoped) = self.find_scoped(dependency) {
+                        return Some(scoped);
+                    }
+                }
+                Lifetime::Singleton => {}
+            }
+        }
+        None
+    }
+}
+
+/// Resolves services and states for a service being constructed.
+pub struct Resolver<'a> {
+    container: &'a Container,
+    states: &'a HashMap<TypeId, Instance>,
+    scoped: Option<&'a mut HashMap<TypeId, Instance>>,
+}
+
+impl<'a> Resolver<'a> {
+    /// Creates a resolver for the request of the given context.
+    pub fn new(cx: &'a mut NgynContext) -> Self {
+        Self {
+            container: &cx.container,
+            states: &cx.states,
+            scoped: Some(&mut cx.scoped),
+        }
+    }
+
+    /// Resolves a service registered in the container.
+    ///
+    /// # Panics
+    /// Panics if the service hasn't been registered,
+    /// or if a scoped service is resolved while constructing a singleton.
+    pub fn resolve<T: Injectable + Send + Sync + 'static>(&mut self) -> Arc<T> {
+        let type_id = TypeId::of::<T>();
+        let container = self.container;
+        let Some(registration) = container.services.get(&type_id) else {
+            panic!(
+                "Service `{}` has not been registered, register it with `app.add_service`",
+                type_name::<T>()
+            );
+        };
+
+        let instance = match registration.lifetime {
+            Lifetime::Singleton => registration
+                .instance
+                .get_or_init(|| {
+                    // singletons outlive requests, so they can't see scoped services
+                    let mut resolver = Resolver {
+                        container,
+                        states: self.states,
+                        scoped: None,
+                    };
+                    (registration.construct)(&mut resolver)
+                })
+                .clone(),
+            Lifetime::Scoped => {
+                let cached = match &self.scoped {
+                    Some(scoped) => scoped.get(&type_id).cloned(),
+                    None => panic!(
+                        "Scoped service `{}` can't be resolved outside of a request",
+                        registration.type_name
+                    ),
+                };
+                match cached {
+                    Some(instance) => instance,
+                    None => {
+                        let instance = (registration.construct)(self);
+                        if let Some(scoped) = &mut self.scoped {
+                            scoped.insert(type_id, instance.clone());
+                        }
+                        instance
+                    }
+                }
+            }
+            Lifetime::Transient => (registration.construct)(self),
+        };
+
+        instance
+            .downcast()
+            .expect("Service is always stored under its own type")
+    }
+
+    /// Resolves an application state.
+    ///
+    /// # Panics
+    /// Panics if the state hasn't been added to the application.
+    pub fn state<T: AppState>(&self) -> State<T> {
+        match self.states.get(&TypeId::of::<T>()).cloned() {
+            Some(state) => State(
+                state
+                    .downcast()
+                    .expect("State is always stored under its own type"),
+            ),
+            None => panic!(
+                "State `{}` has not been added, add it with `app.add_state`",
+                type_name::<T>()
+            ),
+        }
+    }
+}
+
+/// An error found while validating the dependency graph of a [`Container`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum ContainerError {
+    /// A service depends on a service that hasn't been registered.
+    MissingService {
+        service: &'static str,
+        dependency: &'static str,
+    },
+    /// A service depends on a state that hasn't been added.
+    MissingState {
+        service: &'static str,
+        state: &'static str,
+    },
+    /// A route handler extracts a state that hasn't been added.
+    MissingRouteState { route: String, state: &'static str },
+    /// Services depend on each other, the first and last services of the cycle are the same.
+    Cycle(Vec<&'static str>),
+    /// A singleton depends on a scoped service, which would outlive its request.
+    ScopedInSingleton {
+        singleton: &'static str,
+        scoped: &'static str,
+    },
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerError::MissingService {
+                service,
+                dependency,
+            } => write!(
+                f,
+                "service `{}` depends on `{}`, which has not been registered",
+                service, dependency
+            ),
+            ContainerError::MissingState { service, state } => write!(
+                f,
+                "service `{}` depends on state `{}`, which has not been added",
+                service, state
+            ),
+            ContainerError::MissingRouteState { route, state } => write!(
+                f,
+                "route `{}` requires state `{}`, which has not been added",
+                route, state
+            ),
+            ContainerError::Cycle(cycle) => {
+                write!(f, "circular dependency: {}", cycle.join(" -> "))
+            }
+            ContainerError::ScopedInSingleton { singleton, scoped } => write!(
+                f,
+                "singleton `{}` depends on scoped service `{}`",
+                singleton, scoped
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ContainerError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CONSTRUCTED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Config {
+        name: &'static str,
+    }
+    impl AppState for Config {}
+
+    struct Repository {
+        config: State<Config>,
+    }
+    impl Injectable for Repository {
+        fn dependencies() -> Vec<Dependency> {
+            vec![State::<Config>::dependency()]
+        }
+
+        fn construct(resolver: &mut Resolver<'_>) -> Self {
+            CONSTRUCTED.fetch_add(1, Ordering::SeqCst);
+            Self {
+                config: State::resolve(resolver),
+            }
+        }
+    }
+
+    struct Service {
+        repository: Inject<Repository>,
+    }
+    impl Injectable for Service {
+        fn dependencies() -> Vec<Dependency> {
+            vec![Inject::<Repository>::dependency()]
+        }
+
+        fn construct(resolver: &mut Resolver<'_>) -> Self {
+            Self {
+                repository: Inject::resolve(resolver),
+            }
+        }
+    }
+
+    struct Cyclic;
+    impl Injectable for Cyclic {
+        fn dependencies() -> Vec<Dependency> {
+            vec![Inject::<Cyclic>::dependency()]
+        }
+
+        fn construct(resolver: &mut Resolver<'_>) -> Self {
+            let _ = Inject::<Cyclic>::resolve(resolver);
+            Cyclic
+        }
+    }
+
+    fn states() -> HashMap<TypeId, Instance> {
+        let mut states: HashMap<TypeId, Instance> = HashMap::new();
+        states.insert(TypeId::of::<Config>(), Arc::new(Config { name: "ngyn" }));
+        states
+    }
+
+    fn context(container: Container) -> NgynContext<'static> {
+        let mut cx = NgynContext::from_request(http::Request::new(Vec::new()));
+        cx.container = Arc::new(container);
+        cx.states = Arc::new(states());
+        cx
+    }
+
+    #[test]
+    fn test_resolve_lifetimes() {
+        let mut container = Container::default();
+        container.register::<Repository>(Lifetime::Singleton);
+        container.register::<Service>(Lifetime::Scoped);
+        let container = Arc::new(container);
+
+        let mut cx = context(Container::default());
+        cx.container = container.clone();
+        let first = Inject::<Service>::transform(&mut cx);
+        let second = Inject::<Service>::transform(&mut cx);
+        assert!(Arc::ptr_eq(&first.0, &second.0));
+        assert_eq!(first.repository.config.name, "ngyn");
+
+        // a new request gets a new scoped service, but the same singleton
+        let mut cx = context(Container::default());
+        cx.container = container;
+        let third = Inject::<Service>::transform(&mut cx);
+        assert!(!Arc::ptr_eq(&first.0, &third.0));
+        assert!(Arc::ptr_eq(&first.repository.0, &third.repository.0));
+    }
+
+    #[test]
+    fn test_resolve_transient() {
+        let mut container = Container::default();
+        container.register::<Repository>(Lifetime::Transient);
+        let mut cx = context(container);
+
+        let before = CONSTRUCTED.load(Ordering::SeqCst);
+        let first = Inject::<Repository>::transform(&mut cx);
+        let second = Inject::<Repository>::transform(&mut cx);
+        assert!(!Arc::ptr_eq(&first.0, &second.0));
+        assert!(CONSTRUCTED.load(Ordering::SeqCst) >= before + 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "has not been registered")]
+    fn test_resolve_unregistered() {
+        let mut cx = context(Container::default());
+        let _ = Inject::<Service>::transform(&mut cx);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut container = Container::default();
+        container.register::<Repository>(Lifetime::Singleton);
+        container.register::<Service>(Lifetime::Scoped);
+
+        assert_eq!(container.validate(&states()), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_missing_service() {
+        let mut container = Container::default();
+        container.register::<Service>(Lifetime::Scoped);
+
+        assert_eq!(
+            container.validate(&states()),
+            Err(ContainerError::MissingService {
+                service: type_name::<Service>(),
+                dependency: type_name::<Repository>(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_missing_state() {
+        let mut container = Container::default();
+        container.register::<Repository>(Lifetime::Singleton);
+
+        assert_eq!(
+            container.validate(&HashMap::new()),
+            Err(ContainerError::MissingState {
+                service: type_name::<Repository>(),
+                state: type_name::<Config>(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_cycle() {
+        let mut container = Container::default();
+        container.register::<Cyclic>(Lifetime::Transient);
+
+        assert_eq!(
+            container.validate(&states()),
+            Err(ContainerError::Cycle(vec![
+                type_name::<Cyclic>(),
+                type_name::<Cyclic>()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_validate_scoped_in_singleton() {
+        let mut container = Container::default();
+        container.register::<Repository>(Lifetime::Scoped);
+        container.register::<Service>(Lifetime::Singleton);
+
+        let error = container.validate(&states()).unwrap_err();
+        assert_eq!(
+            error,
+            ContainerError::ScopedInSingleton {
+                singleton: type_name::<Service>(),
+                scoped: type_name::<Repository>(),
+            }
+        );
+        assert!(error.to_string().contains("depends on scoped service"));
+    }
+}
@@ -1,17 +1,33 @@
 use bytes::Bytes;
 use http::Request;
 use matchit::{Match, Router};
-use std::{mem::ManuallyDrop, sync::Arc};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    future::Future,
+    sync::Arc,
+};
 
-use super::handler::{handler, RouteHandler};
+use super::{
+    container::{Container, ContainerError, Dependency, Injectable, Lifetime},
+    handler::{HandlerKind, RouteHandler},
+    lifecycle::{HookResult, Hooks},
+};
 use crate::{
-    server::{context::AppState, Method, NgynContext, NgynResponse, ToBytes},
-    Middleware, NgynMiddleware,
+    middlewares::{compression::RequestDecompression, static_files::StaticFiles},
+    server::{
+        connect_info::{ConnectInfo, TrustedProxies},
+        context::AppState,
+        cookies::Key,
+        Method, NgynContext, NgynResponse, ToBytes,
+    },
+    Middleware,
 };
 
 pub struct GroupRouter<'b> {
     base_path: &'b str,
     router: Router<RouteHandler>,
+    route_states: Vec<(String, Dependency)>,
 }
 
 impl RouteInstance for GroupRouter<'_> {
@@ -19,6 +35,10 @@ impl RouteInstance for GroupRouter<'_> {
         &mut self.router
     }
 
+    fn route_states_mut(&mut self) -> &mut Vec<(String, Dependency)> {
+        &mut self.route_states
+    }
+
     fn mount(&self) -> &str {
         self.base_path
     }
@@ -27,8 +47,15 @@ impl RouteInstance for GroupRouter<'_> {
 #[derive(Default)]
 pub struct PlatformData {
     router: Router<RouteHandler>,
+    route_states: Vec<(String, Dependency)>,
     middlewares: Vec<Box<dyn crate::Middleware>>,
-    state: Option<Arc<Box<dyn AppState>>>,
+    states: Arc<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+    cookie_key: Option<Key>,
+    container: Arc<Container>,
+    startup_hooks: Hooks,
+    shutdown_hooks: Hooks,
+    trusted_proxies: Option<TrustedProxies>,
+    decompression: Option<RequestDecompression>,
 }
 
 /// Represents platform data.
@@ -42,13 +69,27 @@ impl PlatformData {
     /// ### Returns
     ///
     /// The response to the request.
-    pub async fn respond(&self, req: Request<Vec<u8>>) -> NgynResponse {
+    pub async fn respond(&self, mut req: Request<Vec<u8>>) -> NgynResponse {
+        if let Some(proxies) = &self.trusted_proxies {
+            let (mut parts, body) = req.into_parts();
+            if let Some(info) = parts.extensions.get_mut::<ConnectInfo>() {
+                info.resolve_forwarded(&parts.headers, proxies);
+            }
+            req = Request::from_parts(parts, body);
+        }
+
+        if let Some(decompression) = &self.decompression {
+            if let Some(res) = decompression.decompress(&mut req).await {
+                return res;
+            }
+        }
+
         let path = req.method().to_string() + req.uri().path();
         let mut cx = NgynContext::from_request(req);
 
-        if let Some(state) = &self.state {
-            cx.state = Some(ManuallyDrop::new(state.into()));
-        }
+        cx.cookies.set_key(self.cookie_key.clone());
+        cx.states = self.states.clone();
+        cx.container = self.container.clone();
 
         let mut route_handler = None;
         let route_info = self.router.at(&path);
@@ -61,16 +102,21 @@ impl PlatformData {
             *cx.response_mut().status_mut() = http::StatusCode::NOT_FOUND;
         }
 
-        // trigger global middlewares
+        // trigger global middlewares, until one of them halts the request
+        let mut ran = 0;
         for middleware in &self.middlewares {
             middleware.run(&mut cx).await;
+            ran += 1;
+            if cx.is_halted() {
+                break;
+            }
         }
 
         // run the route handler
-        if let Some(route_handler) = route_handler {
-            *cx.response_mut().body_mut() = match route_handler {
-                RouteHandler::Sync(handler) => handler(&mut cx),
-                RouteHandler::Async(async_handler) => async_handler(&mut cx).await,
+        if let Some(route_handler) = route_handler.filter(|_| !cx.is_halted()) {
+            *cx.response_mut().body_mut() = match &route_handler.kind {
+                HandlerKind::Sync(handler) => handler(&mut cx),
+                HandlerKind::Async(async_handler) => async_handler(&mut cx).await,
             }
             .to_bytes()
             .into();
@@ -81,6 +127,12 @@ impl PlatformData {
             }
         }
 
+        for middleware in self.middlewares[..ran].iter().rev() {
+            middleware.after(&mut cx).await;
+        }
+
+        cx.cookies.write_to(cx.response.headers_mut());
+
         cx.response
     }
 
@@ -92,6 +144,115 @@ impl PlatformData {
     pub(self) fn add_middleware(&mut self, middleware: Box<dyn Middleware>) {
         self.middlewares.push(middleware);
     }
+
+    /// Adds a state to the platform data, replacing any state of the same type.
+    ///
+    /// The cookie key is taken from the first state that provides one.
+    ///
+    /// ### Arguments
+    ///
+    /// * `state` - The state to add.
+    pub(self) fn add_state<S: AppState>(&mut self, state: S) {
+        if self.cookie_key.is_none() {
+            self.cookie_key = state.cookie_key().cloned();
+        }
+        Arc::make_mut(&mut self.states).insert(TypeId::of::<S>(), Arc::new(state));
+    }
+
+    /// Registers a service in the dependency injection container of the platform data.
+    ///
+    /// # Panics
+    /// Panics if the application is already serving requests.
+    pub(self) fn add_service<T: Injectable + Send + Sync + 'static>(&mut self, lifetime: Lifetime) {
+        Arc::get_mut(&mut self.container)
+            .expect("Services must be registered before the application starts")
+            .register::<T>(lifetime);
+    }
+
+    /// Validates the platform data before it starts serving requests.
+    ///
+    /// This checks that the states extracted by route handlers have been added,
+    /// and that every service registered in the container can be constructed:
+    /// its dependencies are registered, don't depend on each other in a cycle,
+    /// and singletons don't depend on scoped services.
+    pub fn validate(&self) -> Result<(), ContainerError> {
+        for (route, state) in &self.route_states {
+            if !self.states.contains_key(&state.type_id) {
+                return Err(ContainerError::MissingRouteState {
+                    route: route.clone(),
+                    state: state.type_name,
+                });
+            }
+        }
+        self.container.validate(&self.states)
+    }
+
+    /// Takes the startup hooks of the platform data, platforms should run them before serving requests.
+    pub fn take_startup_hooks(&mut self) -> Hooks {
+        std::mem::take(&mut self.startup_hooks)
+    }
+
+    /// Takes the shutdown hooks of the platform data,
+    /// platforms should run them once in-flight requests have completed.
+    pub fn take_shutdown_hooks(&mut self) -> Hooks {
+        std::mem::take(&mut self.shutdown_hooks)
+    }
+}
+
+/// A platform for testing routes and middlewares without a server.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MockEngine {
+    pub(crate) data: PlatformData,
+}
+
+#[cfg(test)]
+impl NgynHttpPlatform for MockEngine {
+    fn data_mut(&mut self) -> &mut PlatformData {
+        &mut self.data
+    }
+}
+
+#[cfg(test)]
+impl MockEngine {
+    /// Sends a request without a body to the engine.
+    pub(crate) async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> NgynResponse {
+        self.send_body(method, path, headers, Vec::new()).await
+    }
+
+    /// Sends a request with a body to the engine.
+    pub(crate) async fn send_body(
+        &self,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: impl Into<Vec<u8>>,
+    ) -> NgynResponse {
+        let mut req = Request::builder().method(method).uri(path);
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        self.data.respond(req.body(body.into()).unwrap()).await
+    }
+}
+
+/// Reads the whole body of a response.
+#[cfg(test)]
+pub(crate) async fn read_body(res: NgynResponse) -> Bytes {
+    use crate::server::response::ResponseStream;
+    use http_body_util::BodyExt;
+
+    ResponseStream::into_boxed(res)
+        .into_body()
+        .collect()
+        .await
+        .unwrap()
+        .to_bytes()
 }
 
 pub trait NgynPlatform: Default {
@@ -101,6 +262,9 @@ pub trait NgynPlatform: Default {
 pub trait RouteInstance {
     fn router_mut(&mut self) -> &mut Router<RouteHandler>;
 
+    /// The states required by the handlers of the routes, keyed by route.
+    fn route_states_mut(&mut self) -> &mut Vec<(String, Dependency)>;
+
     /// Mounts the route on a path, defaults to "/"
     fn mount(&self) -> &str {
         "/"
@@ -114,17 +278,22 @@ pub trait RouteInstance {
     /// * `method` - The HTTP method of the route.
     /// * `handler` - The handler function for the route.
     fn add_route(&mut self, path: &str, method: Option<Method>, handler: RouteHandler) {
-        let method = method
-            .map(|method| method.to_string())
-            .unwrap_or_else(|| "{METHOD}".to_string());
-
-        let route = if path.starts_with('/') {
-            method + path
+        let path = if path.starts_with('/') {
+            path.to_string()
         } else {
-            method + self.mount() + path
+            self.mount().to_string() + path
         };
 
-        self.router_mut().insert(route, handler).unwrap();
+        for state in &handler.required_states {
+            let method = method.as_ref().map_or("*", Method::as_str);
+            let route = format!("{} {}", method, path);
+            self.route_states_mut().push((route, *state));
+        }
+
+        let method = method
+            .map(|method| method.to_string())
+            .unwrap_or_else(|| "{METHOD}".to_string());
+        self.router_mut().insert(method + &path, handler).unwrap();
     }
 }
 
@@ -185,26 +354,30 @@ pub trait NgynHttpEngine: NgynPlatform {
         self.route(path, Method::HEAD, handler.into())
     }
 
-    /// Sets up static file routes.
+    /// Serves the files of a directory, for requests that no route matched.
     ///
-    /// This is great for apps tha would want to output files in a specific folder.
-    /// For instance, a `public` directory can be set up and include all files in the directory
-    ///
-    /// The behavior of `use_static` in ngyn is different from other frameworks.
-    /// 1. You can call it multiple times, each call registers a new set of routes
-    /// 2. The files in `path_buf` folder aren't embedded into your binary and must be copied to the location of your binary
+    /// This is a shorthand for adding a [`StaticFiles`] middleware mounted at `/`,
+    /// use the middleware directly to mount the files under a path, or to configure caching.
+    /// Files are read from disk when they are requested, so they aren't embedded into your binary
+    /// and must be deployed along with it.
     ///
     /// ### Arguments
     ///
-    /// - `path_buf` - static folder, relative to Cargo.toml in dev, and the binary in release
+    /// * `path_buf` - The static directory, relative to the working directory.
+    ///
+    /// ### Returns
+    ///
+    /// An error if the directory doesn't exist.
+    ///
+    /// ### Examples
     ///
+    /// ```rust ignore
+    /// use ngyn::prelude::*;
+    ///
+    /// app.use_static(PathBuf::from("public"))?;
+    /// ```
     fn use_static(&mut self, path_buf: std::path::PathBuf) -> std::io::Result<()> {
-        let assets = include!("statics.rs");
-
-        for (file_path, content) in assets {
-            self.get(&file_path, handler(move |_| Bytes::from(content)));
-        }
-
+        self.use_middleware(StaticFiles::new(path_buf)?);
         Ok(())
     }
 }
@@ -219,27 +392,184 @@ pub trait NgynEngine: NgynPlatform {
         let mut group = GroupRouter {
             base_path,
             router: Router::<RouteHandler>::new(),
+            route_states: Vec::new(),
         };
         registry(&mut group);
         self.data_mut().router.merge(group.router).unwrap();
+        self.data_mut().route_states.extend(group.route_states);
     }
 
     /// Adds a middleware to the application.
     ///
     /// ### Arguments
     ///
-    /// * `middleware` - The middleware to add.
-    fn use_middleware(&mut self, middleware: impl NgynMiddleware + 'static) {
+    /// * `middleware` - The middleware to add, either a [`NgynMiddleware`](crate::NgynMiddleware) or a [`Middleware`].
+    fn use_middleware(&mut self, middleware: impl Middleware + 'static) {
         self.data_mut().add_middleware(Box::new(middleware));
     }
 
+    /// Adds a state to the application, the state can be any value that implements [`AppState`].
+    ///
+    /// States are keyed by their type, so an application can have as many states as it needs,
+    /// as long as they are of different types. Adding a state of a type that has already been added replaces it.
+    ///
+    /// ### Arguments
+    ///
+    /// * `state` - The state to add.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn::prelude::*;
+    ///
+    /// #[derive(AppState)]
+    /// struct DbPool { /* ... */ }
+    ///
+    /// #[derive(AppState)]
+    /// struct Config { /* ... */ }
+    ///
+    /// app.add_state(DbPool::connect());
+    /// app.add_state(Config::load());
+    ///
+    /// #[handler]
+    /// fn users(pool: &DbPool, config: &Config) -> String { /* ... */ }
+    /// ```
+    fn add_state(&mut self, state: impl AppState + 'static) {
+        self.data_mut().add_state(state);
+    }
+
     /// Sets the state of the application to any value that implements [`AppState`].
     ///
+    /// This is the same as [`NgynEngine::add_state`].
+    ///
     /// ### Arguments
     ///
     /// * `state` - The state to set.
     fn set_state(&mut self, state: impl AppState + 'static) {
-        self.data_mut().state = Some(Arc::new(Box::new(state)));
+        self.add_state(state);
+    }
+
+    /// Registers a service in the dependency injection container of the application.
+    ///
+    /// Registered services can be extracted in handlers with [`Inject`](super::container::Inject),
+    /// or injected into other services with `#[inject]`.
+    /// The dependency graph is validated when the application starts.
+    ///
+    /// ### Arguments
+    ///
+    /// * `lifetime` - The lifetime of the instances of the service.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn::prelude::*;
+    ///
+    /// app.add_service::<UserRepository>(Lifetime::Singleton);
+    /// app.add_service::<UserService>(Lifetime::Scoped);
+    /// ```
+    fn add_service<S: Injectable + Send + Sync + 'static>(&mut self, lifetime: Lifetime) {
+        self.data_mut().add_service::<S>(lifetime);
+    }
+
+    /// Trusts the given proxies to forward client information.
+    ///
+    /// When a request comes from a trusted proxy, [`ConnectInfo::client_ip`] and [`ConnectInfo::scheme`]
+    /// are resolved from the `Forwarded` or `X-Forwarded-For` and `X-Forwarded-Proto` headers.
+    /// Requests from other peers can't spoof their address with these headers.
+    ///
+    /// ### Arguments
+    ///
+    /// * `proxies` - The networks of the trusted proxies.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn::prelude::*;
+    ///
+    /// app.trust_proxies(TrustedProxies::new(["10.0.0.0/8"])?);
+    /// ```
+    fn trust_proxies(&mut self, proxies: TrustedProxies) {
+        self.data_mut().trusted_proxies = Some(proxies);
+    }
+
+    /// Decompresses the bodies of requests sent with a `Content-Encoding`, before they are handled.
+    ///
+    /// Extractors like [`Body`](crate::server::Body) then see the plain payload.
+    /// The supported encodings are enabled with the `gzip`, `deflate`, `brotli` and `zstd` cargo features.
+    ///
+    /// ### Arguments
+    ///
+    /// * `decompression` - The accepted encodings and the maximum size of decompressed bodies.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn::prelude::*;
+    /// use ngyn::shared::middlewares::compression::RequestDecompression;
+    ///
+    /// app.decompress_requests(RequestDecompression::new().max_size(1024 * 1024));
+    /// ```
+    fn decompress_requests(&mut self, decompression: RequestDecompression) {
+        self.data_mut().decompression = Some(decompression);
+    }
+
+    /// Adds a hook that runs when the application starts, before it serves any request.
+    ///
+    /// Startup hooks run in the order they were added. If a hook fails, the application doesn't start.
+    ///
+    /// ### Arguments
+    ///
+    /// * `hook` - An async function, useful for running migrations or warming up caches.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn::prelude::*;
+    ///
+    /// let pool = DbPool::new();
+    /// let migrations_pool = pool.clone();
+    ///
+    /// app.on_startup(move || async move {
+    ///     migrations_pool.migrate().await?;
+    ///     Ok(())
+    /// });
+    /// app.add_state(pool);
+    /// ```
+    fn on_startup<F, Fut>(&mut self, hook: F)
+    where
+        F: FnOnce() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HookResult> + Send + 'static,
+    {
+        self.data_mut().startup_hooks.push(hook);
+    }
+
+    /// Adds a hook that runs when the application shuts down, after in-flight requests have completed.
+    ///
+    /// Shutdown hooks run in the reverse order they were added, all hooks run even if some of them fail.
+    ///
+    /// ### Arguments
+    ///
+    /// * `hook` - An async function, useful for flushing buffers or closing connection pools.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn::prelude::*;
+    ///
+    /// let pool = DbPool::new();
+    /// let shutdown_pool = pool.clone();
+    ///
+    /// app.on_shutdown(move || async move {
+    ///     shutdown_pool.close().await;
+    ///     Ok(())
+    /// });
+    /// ```
+    fn on_shutdown<F, Fut>(&mut self, hook: F)
+    where
+        F: FnOnce() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HookResult> + Send + 'static,
+    {
+        self.data_mut().shutdown_hooks.push(hook);
     }
 }
 
@@ -254,6 +584,10 @@ impl<T: NgynPlatform> RouteInstance for T {
     fn router_mut(&mut self) -> &mut Router<RouteHandler> {
         &mut self.data_mut().router
     }
+
+    fn route_states_mut(&mut self) -> &mut Vec<(String, Dependency)> {
+        &mut self.data_mut().route_states
+    }
 }
 impl<T: NgynHttpPlatform> NgynHttpEngine for T {}
 
@@ -261,22 +595,21 @@ impl<T: NgynHttpPlatform> NgynHttpEngine for T {}
 mod tests {
     use http::StatusCode;
 
-    use crate::core::handler::Handler;
-    use std::any::Any;
+    use crate::{
+        core::{
+            container::{Inject, Resolver},
+            handler::{handler, Handler},
+        },
+        server::response::ReadBytes,
+        server::Transformer,
+        NgynMiddleware,
+    };
 
     use super::*;
 
     struct MockAppState;
 
-    impl AppState for MockAppState {
-        fn as_any(&self) -> &dyn Any {
-            self
-        }
-
-        fn as_any_mut(&mut self) -> &mut dyn Any {
-            self
-        }
-    }
+    impl AppState for MockAppState {}
 
     struct MockMiddleware;
 
@@ -286,22 +619,11 @@ mod tests {
         }
     }
 
-    #[derive(Default)]
-    struct MockEngine {
-        data: PlatformData,
-    }
-
-    impl NgynPlatform for MockEngine {
-        fn data_mut(&mut self) -> &mut PlatformData {
-            &mut self.data
-        }
-    }
-
     #[tokio::test]
     async fn test_respond_with_state() {
         let mut engine = MockEngine::default();
         let app_state = MockAppState;
-        engine.data_mut().state = Some(Arc::new(Box::new(app_state)));
+        engine.add_state(app_state);
 
         let req = Request::builder()
             .method(Method::GET)
@@ -333,7 +655,7 @@ mod tests {
     async fn test_respond_with_middleware() {
         let mut engine = MockEngine::default();
         let middleware = MockMiddleware;
-        engine.data_mut().add_middleware(Box::new(middleware));
+        engine.data.add_middleware(Box::new(middleware));
 
         let req = Request::builder()
             .method(Method::GET)
@@ -346,11 +668,38 @@ mod tests {
         assert_eq!(res.status(), http::StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_respond_halted() {
+        use http_body_util::BodyExt;
+
+        struct Halt;
+
+        impl NgynMiddleware for Halt {
+            async fn handle(cx: &mut NgynContext<'_>) {
+                *cx.response_mut().status_mut() = http::StatusCode::FORBIDDEN;
+                cx.halt();
+            }
+        }
+
+        let mut engine = MockEngine::default();
+        engine.use_middleware(MockMiddleware);
+        engine.use_middleware(Halt);
+        engine.use_middleware(MockMiddleware);
+        engine.any("/test", handler(|_| "never"));
+
+        let req = Request::builder().uri("/test").body(Vec::new()).unwrap();
+        let res = engine.data.respond(req).await;
+
+        assert_eq!(res.status(), http::StatusCode::FORBIDDEN);
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert!(body.is_empty());
+    }
+
     #[tokio::test]
     async fn test_respond_with_route_handler() {
         let mut engine = MockEngine::default();
         let handler: Box<Handler> = Box::new(|_| Box::new(()) as Box<dyn ToBytes>);
-        engine.add_route("/test", Some(Method::GET), RouteHandler::Sync(handler));
+        engine.add_route("/test", Some(Method::GET), handler.into());
 
         let req = Request::builder()
             .method(Method::GET)
@@ -363,6 +712,31 @@ mod tests {
         assert_eq!(res.status(), http::StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_respond_with_cookies() {
+        let mut engine = MockEngine::default();
+        let handler: Box<Handler> = Box::new(|cx| {
+            let visits = cx.cookies().get("visits").unwrap().value().to_string();
+            cx.cookies_mut().add(("visits", visits + "1"));
+            Box::new(()) as Box<dyn ToBytes>
+        });
+        engine.add_route("/test", Some(Method::GET), handler.into());
+
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/test")
+            .header(http::header::COOKIE, "visits=1")
+            .body(Vec::new())
+            .unwrap();
+
+        let res = engine.data.respond(req).await;
+
+        assert_eq!(
+            res.headers().get(http::header::SET_COOKIE).unwrap(),
+            "visits=11"
+        );
+    }
+
     #[tokio::test]
     async fn test_respond_with_route_handler_not_found() {
         let engine = MockEngine::default();
@@ -401,7 +775,7 @@ mod tests {
     async fn test_add_route() {
         let mut engine = MockEngine::default();
         let handler: Box<Handler> = Box::new(|_| Box::new(()) as Box<dyn ToBytes>);
-        engine.add_route("/test", Some(Method::GET), RouteHandler::Sync(handler));
+        engine.add_route("/test", Some(Method::GET), handler.into());
 
         assert!(engine.data.router.at("GET/test").is_ok());
     }
@@ -410,7 +784,7 @@ mod tests {
     async fn test_add_middleware() {
         let mut engine = MockEngine::default();
         let middleware = MockMiddleware;
-        engine.data_mut().add_middleware(Box::new(middleware));
+        engine.data.add_middleware(Box::new(middleware));
 
         assert_eq!(engine.data.middlewares.len(), 1);
     }
@@ -430,6 +804,138 @@ mod tests {
         let app_state = MockAppState;
         engine.set_state(app_state);
 
-        assert!(engine.data.state.is_some());
+        assert_eq!(engine.data.states.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_add_multiple_states() {
+        struct Config {
+            name: &'static str,
+        }
+        impl AppState for Config {}
+
+        let mut engine = MockEngine::default();
+        engine.add_state(MockAppState);
+        engine.add_state(Config { name: "first" });
+        engine.add_state(Config { name: "ngyn" });
+        assert_eq!(engine.data.states.len(), 2);
+
+        let handler = handler(|cx: &mut NgynContext| {
+            assert!(cx.state::<MockAppState>().is_some());
+            cx.state::<Config>().unwrap().name
+        });
+        engine.add_route("/name", Some(Method::GET), handler.into());
+
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/name")
+            .body(Vec::new())
+            .unwrap();
+        let mut res = engine.data.respond(req).await;
+
+        assert_eq!(res.read_bytes().await.unwrap(), "ngyn");
+    }
+
+    #[tokio::test]
+    async fn test_add_service() {
+        struct Greeter;
+        impl Injectable for Greeter {
+            fn construct(_: &mut Resolver<'_>) -> Self {
+                Greeter
+            }
+        }
+
+        let mut engine = MockEngine::default();
+        engine.add_service::<Greeter>(Lifetime::Singleton);
+        assert!(engine.data.container.contains::<Greeter>());
+        assert!(engine.data.validate().is_ok());
+
+        let handler = handler(|cx: &mut NgynContext| {
+            let first = Inject::<Greeter>::transform(cx);
+            let second = Inject::<Greeter>::transform(cx);
+            Arc::ptr_eq(&first.0, &second.0).to_string()
+        });
+        engine.add_route("/greet", Some(Method::GET), handler.into());
+
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/greet")
+            .body(Vec::new())
+            .unwrap();
+        let mut res = engine.data.respond(req).await;
+
+        assert_eq!(res.read_bytes().await.unwrap(), "true");
+    }
+
+    #[test]
+    fn test_validate_route_states() {
+        use crate::{
+            core::handler::{async_wrap, probe, HandlerBody},
+            server::context::State,
+        };
+        use std::{any::type_name, pin::Pin};
+
+        // what `#[handler]` generates for `fn count(state: State<MockAppState>)`
+        fn count(cx: &mut NgynContext) -> HandlerBody {
+            if probe(
+                cx,
+                &[<State<MockAppState> as Transformer>::required_state()],
+            ) {
+                return HandlerBody(Box::new(()));
+            }
+            HandlerBody(Box::new("count"))
+        }
+
+        fn count_async<'a>(
+            cx: &'a mut NgynContext,
+        ) -> Pin<Box<dyn Future<Output = HandlerBody> + Send + 'a>> {
+            if probe(
+                cx,
+                &[<State<MockAppState> as Transformer>::required_state()],
+            ) {
+                return Box::pin(async { HandlerBody(Box::new(())) });
+            }
+            Box::pin(async { HandlerBody(Box::new("count")) })
+        }
+
+        let mut engine = MockEngine::default();
+        // other handlers aren't run when they are registered
+        engine.any("/other", handler(|_| -> &str { panic!("handler ran") }));
+        engine.group("/api/", |group| {
+            group.add_route("count", None, count.into())
+        });
+        assert_eq!(
+            engine.data.validate(),
+            Err(ContainerError::MissingRouteState {
+                route: "* /api/count".to_string(),
+                state: type_name::<MockAppState>(),
+            })
+        );
+
+        let mut engine = MockEngine::default();
+        engine.add_route("/count", Some(Method::GET), async_wrap(count_async));
+        assert_eq!(
+            engine.data.validate().unwrap_err().to_string(),
+            format!(
+                "route `GET /count` requires state `{}`, which has not been added",
+                type_name::<MockAppState>()
+            )
+        );
+
+        engine.add_state(MockAppState);
+        assert!(engine.data.validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_hooks() {
+        let mut engine = MockEngine::default();
+        engine.on_startup(|| async { Ok(()) });
+        engine.on_shutdown(|| async { Err("pool already closed".into()) });
+
+        assert!(engine.data.take_startup_hooks().run().await.is_ok());
+        assert!(engine.data.take_startup_hooks().is_empty());
+
+        let shutdown = engine.data.take_shutdown_hooks().run_all().await;
+        assert_eq!(shutdown.unwrap_err().to_string(), "pool already closed");
     }
 }
@@ -0,0 +1,332 @@
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+use http::{HeaderMap, StatusCode};
+
+use crate::{
+    server::{response::ReadBytes, NgynContext, NgynResponse},
+    NgynGate,
+};
+
+/// The outcome of a gate, see [`NgynGate::check`].
+#[derive(Debug)]
+pub enum GateOutcome {
+    /// The route can activate.
+    Allow,
+    /// The route can't activate, the request gets an empty response with the given status.
+    Deny(StatusCode),
+    /// The route can't activate, the request gets the given response,
+    /// along with the headers already set on the response of the request, e.g. by middlewares.
+    Respond(NgynResponse),
+}
+
+impl From<bool> for GateOutcome {
+    /// Converts the result of [`NgynGate::can_activate`], denials are `403 Forbidden`.
+    fn from(allowed: bool) -> Self {
+        match allowed {
+            true => GateOutcome::Allow,
+            false => GateOutcome::Deny(StatusCode::FORBIDDEN),
+        }
+    }
+}
+
+impl GateOutcome {
+    /// Checks if the outcome allows the route to activate.
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, GateOutcome::Allow)
+    }
+
+    /// Applies the outcome to the response of a request.
+    ///
+    /// This is how `#[handler(gates = [...])]` handles the outcome of its gates.
+    ///
+    /// ### Arguments
+    ///
+    /// * `cx` - The request context.
+    ///
+    /// ### Returns
+    ///
+    /// `None` if the route can activate, the body of the response otherwise.
+    pub async fn apply(self, cx: &mut NgynContext<'_>) -> Option<Bytes> {
+        match self {
+            GateOutcome::Allow => None,
+            GateOutcome::Deny(status) => {
+                let res = cx.response_mut();
+                *res.status_mut() = status;
+                *res.body_mut() = Default::default();
+                Some(Bytes::new())
+            }
+            GateOutcome::Respond(mut res) => {
+                let body = res.read_bytes().await.unwrap_or_default();
+                *res.body_mut() = body.clone().into();
+                merge_headers(res.headers_mut(), cx.response_mut().headers());
+                *cx.response_mut() = res;
+                Some(body)
+            }
+        }
+    }
+}
+
+/// Copies the headers of `from` which `to` doesn't set.
+pub(crate) fn merge_headers(to: &mut HeaderMap, from: &HeaderMap) {
+    for name in from.keys() {
+        if !to.contains_key(name) {
+            for value in from.get_all(name) {
+                to.append(name.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// A list of gates, a tuple of up to 8 gates.
+pub trait Gates {
+    /// Returns the first outcome that allows the route to activate, or the first denial.
+    #[allow(async_fn_in_trait)]
+    async fn any(cx: &mut NgynContext<'_>) -> GateOutcome;
+
+    /// Returns the first denial, if any.
+    #[allow(async_fn_in_trait)]
+    async fn all(cx: &mut NgynContext<'_>) -> GateOutcome;
+}
+
+macro_rules! impl_gates {
+    ($($gate:ident),+) => {
+        impl<$($gate: NgynGate),+> Gates for ($($gate,)+) {
+            async fn any(cx: &mut NgynContext<'_>) -> GateOutcome {
+                let mut denial = None;
+                $(
+                    match $gate::check(cx).await {
+                        GateOutcome::Allow => return GateOutcome::Allow,
+                        outcome => {
+                            denial.get_or_insert(outcome);
+                        }
+                    }
+                )+
+                denial.unwrap_or(GateOutcome::Allow)
+            }
+
+            async fn all(cx: &mut NgynContext<'_>) -> GateOutcome {
+                $(
+                    let outcome = $gate::check(cx).await;
+                    if !outcome.is_allowed() {
+                        return outcome;
+                    }
+                )+
+                GateOutcome::Allow
+            }
+        }
+    };
+}
+
+impl_gates!(A);
+impl_gates!(A, B);
+impl_gates!(A, B, C);
+impl_gates!(A, B, C, D);
+impl_gates!(A, B, C, D, E);
+impl_gates!(A, B, C, D, E, F);
+impl_gates!(A, B, C, D, E, F, G);
+impl_gates!(A, B, C, D, E, F, G, H);
+
+/// A gate allowing requests allowed by any of the given gates.
+///
+/// Gates are checked in order until one allows the request, a denied request gets the outcome of the first gate.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// #[handler(gates = [AnyOf<(AdminGate, OwnerGate)>])]
+/// async fn update_post(id: Param) -> String { ... }
+/// ```
+pub struct AnyOf<G>(PhantomData<G>);
+
+impl<G: Gates> NgynGate for AnyOf<G> {
+    async fn check(cx: &mut NgynContext<'_>) -> GateOutcome {
+        G::any(cx).await
+    }
+}
+
+/// A gate allowing requests allowed by all the given gates.
+///
+/// Gates are checked in order, a denied request gets the outcome of the first gate denying it.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// #[handler(gates = [AllOf<(JwtGate, AdminGate)>])]
+/// async fn delete_user(id: Param) -> String { ... }
+/// ```
+pub struct AllOf<G>(PhantomData<G>);
+
+impl<G: Gates> NgynGate for AllOf<G> {
+    async fn check(cx: &mut NgynContext<'_>) -> GateOutcome {
+        G::all(cx).await
+    }
+}
+
+/// A gate allowing requests denied by the given gate, other requests are `403 Forbidden`.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// #[handler(gates = [Not<AuthGate>])]
+/// async fn sign_up(body: Body) -> String { ... }
+/// ```
+pub struct Not<G>(PhantomData<G>);
+
+impl<G: NgynGate> NgynGate for Not<G> {
+    async fn check(cx: &mut NgynContext<'_>) -> GateOutcome {
+        match G::check(cx).await {
+            GateOutcome::Allow => GateOutcome::Deny(StatusCode::FORBIDDEN),
+            _ => GateOutcome::Allow,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Request;
+
+    use super::*;
+
+    struct Open;
+
+    impl NgynGate for Open {
+        async fn can_activate(_cx: &mut NgynContext<'_>) -> bool {
+            true
+        }
+    }
+
+    struct Closed;
+
+    impl NgynGate for Closed {
+        async fn can_activate(_cx: &mut NgynContext<'_>) -> bool {
+            false
+        }
+    }
+
+    /// A gate setting its own response before returning `false`.
+    struct Teapot;
+
+    impl NgynGate for Teapot {
+        async fn can_activate(cx: &mut NgynContext<'_>) -> bool {
+            *cx.response_mut().status_mut() = StatusCode::IM_A_TEAPOT;
+            *cx.response_mut().body_mut() = "teapot".into();
+            false
+        }
+    }
+
+    struct Unavailable;
+
+    impl NgynGate for Unavailable {
+        async fn check(_cx: &mut NgynContext<'_>) -> GateOutcome {
+            GateOutcome::Deny(StatusCode::SERVICE_UNAVAILABLE)
+        }
+    }
+
+    async fn check<G: NgynGate>() -> (StatusCode, Option<Bytes>) {
+        let req = Request::builder().uri("/").body(Vec::new()).unwrap();
+        let mut cx = NgynContext::from_request(req);
+        let body = G::check(&mut cx).await.apply(&mut cx).await;
+        (cx.response_mut().status(), body)
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        assert_eq!(check::<Open>().await, (StatusCode::OK, None));
+        assert_eq!(
+            check::<Closed>().await,
+            (StatusCode::FORBIDDEN, Some(Bytes::new()))
+        );
+        assert_eq!(
+            check::<Teapot>().await,
+            (StatusCode::IM_A_TEAPOT, Some(Bytes::from("teapot")))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_denials_keep_headers() {
+        use crate::core::engine::{read_body, MockEngine, NgynEngine};
+        use crate::core::handler::async_wrap;
+        use crate::server::ToBytes;
+        use crate::Middleware;
+        use http::Method;
+        use std::{future::Future, pin::Pin};
+
+        /// A middleware setting a header before the handler runs, like the rate limiter.
+        struct Tagged;
+
+        impl Middleware for Tagged {
+            fn run<'a>(
+                &'a self,
+                cx: &'a mut NgynContext<'_>,
+            ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+                cx.response_mut()
+                    .headers_mut()
+                    .insert("x-tag", "ngyn".parse().unwrap());
+                Box::pin(async {})
+            }
+        }
+
+        // gate futures are only known to be `Send` for concrete gates
+        macro_rules! gated {
+            ($name:ident, $gate:ty) => {
+                fn $name<'a>(
+                    cx: &'a mut NgynContext,
+                ) -> Pin<Box<dyn Future<Output = Box<dyn ToBytes>> + Send + 'a>> {
+                    Box::pin(async move {
+                        match <$gate>::check(cx).await.apply(cx).await {
+                            Some(body) => Box::new(body) as Box<dyn ToBytes>,
+                            None => Box::new("ok"),
+                        }
+                    })
+                }
+            };
+        }
+        gated!(closed, Closed);
+        gated!(teapot, Teapot);
+        gated!(any, AnyOf<(Teapot, Open)>);
+
+        let mut engine = MockEngine::default();
+        engine.use_middleware(Tagged);
+        engine.any("/closed", async_wrap(closed));
+        engine.any("/teapot", async_wrap(teapot));
+        engine.any("/any", async_wrap(any));
+
+        let res = engine.send(Method::GET, "/closed", &[]).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        assert_eq!(res.headers()["x-tag"], "ngyn");
+
+        let res = engine.send(Method::GET, "/teapot", &[]).await;
+        assert_eq!(res.status(), StatusCode::IM_A_TEAPOT);
+        assert_eq!(res.headers()["x-tag"], "ngyn");
+        assert_eq!(read_body(res).await, "teapot");
+
+        let res = engine.send(Method::GET, "/any", &[]).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["x-tag"], "ngyn");
+    }
+
+    #[tokio::test]
+    async fn test_combinators() {
+        assert_eq!(check::<AnyOf<(Closed, Open)>>().await.0, StatusCode::OK);
+        assert_eq!(
+            check::<AnyOf<(Unavailable, Closed)>>().await.0,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        // the response of a denying gate doesn't leak into the next ones
+        assert_eq!(check::<AnyOf<(Teapot, Open)>>().await.0, StatusCode::OK);
+
+        assert_eq!(
+            check::<AllOf<(Open, Teapot, Closed)>>().await.0,
+            StatusCode::IM_A_TEAPOT
+        );
+        assert_eq!(check::<AllOf<(Open, Open)>>().await.0, StatusCode::OK);
+
+        assert_eq!(check::<Not<Closed>>().await.0, StatusCode::OK);
+        assert_eq!(check::<Not<Open>>().await.0, StatusCode::FORBIDDEN);
+        assert_eq!(
+            check::<Not<AnyOf<(Closed, Teapot)>>>().await.0,
+            StatusCode::OK
+        );
+    }
+}
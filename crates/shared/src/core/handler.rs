@@ -1,7 +1,9 @@
 use std::{future::Future, pin::Pin};
 
-use http::{HeaderValue, StatusCode};
+use bytes::Bytes;
+use http::{HeaderValue, Request, StatusCode};
 
+use super::container::Dependency;
 use crate::server::{NgynContext, ToBytes};
 
 /// Represents a handler function that takes in a mutable reference to `NgynContext` and `NgynResponse`.
@@ -13,20 +15,105 @@ pub(crate) type AsyncHandler = dyn for<'a, 'b> Fn(
     + Send
     + Sync;
 
-pub enum RouteHandler {
+/// A route handler, along with the states its arguments require.
+pub struct RouteHandler {
+    pub(crate) kind: HandlerKind,
+    pub(crate) required_states: Vec<Dependency>,
+}
+
+pub(crate) enum HandlerKind {
     Sync(Box<Handler>),
     Async(Box<AsyncHandler>),
 }
 
 impl From<Box<AsyncHandler>> for RouteHandler {
     fn from(f: Box<AsyncHandler>) -> Self {
-        RouteHandler::Async(f)
+        RouteHandler {
+            kind: HandlerKind::Async(f),
+            required_states: Vec::new(),
+        }
     }
 }
 
-impl<F: Fn(&mut NgynContext) -> Box<dyn ToBytes> + Send + Sync + 'static> From<F> for RouteHandler {
+impl<F, O> From<F> for RouteHandler
+where
+    F: Fn(&mut NgynContext) -> O + Send + Sync + 'static,
+    O: HandlerOutput,
+{
     fn from(f: F) -> Self {
-        RouteHandler::Sync(Box::new(f))
+        let required_states = match O::PROBED {
+            true => probe_states(|cx| {
+                f(cx);
+            }),
+            false => Vec::new(),
+        };
+        RouteHandler {
+            kind: HandlerKind::Sync(Box::new(move |cx: &mut NgynContext| f(cx).into_body())),
+            required_states,
+        }
+    }
+}
+
+/// The states listed by a probed handler.
+struct Probe(Vec<Dependency>);
+
+/// Lists the states a handler generated by `#[handler]` requires, by calling it with a probe context.
+fn probe_states(call: impl FnOnce(&mut NgynContext)) -> Vec<Dependency> {
+    let mut cx = NgynContext::from_request(Request::default());
+    cx.extensions_mut().insert(Probe(Vec::new()));
+    call(&mut cx);
+    cx.extensions_mut()
+        .remove::<Probe>()
+        .map(|Probe(states)| states)
+        .unwrap_or_default()
+}
+
+/// Records the states a handler generated by `#[handler]` requires, if the context is a probe.
+///
+/// ### Returns
+///
+/// `true` if the context is a probe, the handler should then return without running.
+#[doc(hidden)]
+pub fn probe(cx: &mut NgynContext, states: &[Option<Dependency>]) -> bool {
+    match cx.extensions_mut().get_mut::<Probe>() {
+        Some(Probe(required)) => {
+            required.extend(states.iter().flatten().copied());
+            true
+        }
+        None => false,
+    }
+}
+
+/// The output of a route handler.
+#[doc(hidden)]
+pub trait HandlerOutput: 'static {
+    /// Whether the handler was generated by `#[handler]`, and can be probed for the states it requires.
+    const PROBED: bool = false;
+
+    fn into_body(self) -> Box<dyn ToBytes>;
+}
+
+impl HandlerOutput for Box<dyn ToBytes> {
+    fn into_body(self) -> Box<dyn ToBytes> {
+        self
+    }
+}
+
+/// The output of a handler generated by `#[handler]`.
+#[doc(hidden)]
+pub struct HandlerBody(pub Box<dyn ToBytes>);
+
+impl HandlerOutput for HandlerBody {
+    const PROBED: bool = true;
+
+    fn into_body(self) -> Box<dyn ToBytes> {
+        self.0
+    }
+}
+
+impl ToBytes for HandlerBody {
+    fn to_bytes(&self) -> Bytes {
+        self.0.to_bytes()
     }
 }
 
@@ -70,15 +157,37 @@ pub fn async_handler<S: ToBytes + 'static, Fut: Future<Output = S> + Send + 'sta
     })
 }
 
-pub fn async_wrap(
-    f: impl for<'a, 'b> Fn(
-            &'a mut NgynContext,
-        ) -> Pin<Box<dyn Future<Output = Box<dyn ToBytes>> + Send + 'a>>
+/// Creates a route handler from an async function, like the ones generated by `#[handler]`.
+///
+/// ### Example
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// #[handler]
+/// async fn hello() -> &'static str {
+///    "Hello, World!"
+/// }
+///
+/// app.get("/hello", async_wrap(hello));
+/// ```
+pub fn async_wrap<O: HandlerOutput>(
+    f: impl for<'a> Fn(&'a mut NgynContext) -> Pin<Box<dyn Future<Output = O> + Send + 'a>>
         + Send
         + Sync
         + 'static,
-) -> Box<AsyncHandler> {
-    Box::new(f)
+) -> RouteHandler {
+    // the states are listed when the future is created, it's never polled
+    let required_states = match O::PROBED {
+        true => probe_states(|cx| drop(f(cx))),
+        false => Vec::new(),
+    };
+    RouteHandler {
+        kind: HandlerKind::Async(Box::new(move |ctx: &mut NgynContext| {
+            let fut = f(ctx);
+            Box::pin(async move { fut.await.into_body() })
+        })),
+        required_states,
+    }
 }
 
 /// Create a not-implemented handler that returns a `501 Not Implemented` status code.
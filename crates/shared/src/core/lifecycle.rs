@@ -0,0 +1,96 @@
+use std::{error::Error, future::Future, pin::Pin};
+
+/// The result of a lifecycle hook.
+pub type HookResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+type Hook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = HookResult> + Send>> + Send + Sync>;
+
+/// A list of async hooks, run when the application starts or shuts down.
+#[derive(Default)]
+pub struct Hooks {
+    hooks: Vec<Hook>,
+}
+
+impl Hooks {
+    /// Adds a hook to the list.
+    pub(crate) fn push<F, Fut>(&mut self, hook: F)
+    where
+        F: FnOnce() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HookResult> + Send + 'static,
+    {
+        self.hooks.push(Box::new(move || Box::pin(hook())));
+    }
+
+    /// Checks if there are no hooks in the list.
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Runs the hooks in the order they were added, stopping at the first hook that fails.
+    ///
+    /// This is how startup hooks are run, the application shouldn't start if one of them fails.
+    pub async fn run(self) -> HookResult {
+        for hook in self.hooks {
+            hook().await?;
+        }
+        Ok(())
+    }
+
+    /// Runs all the hooks in the reverse order they were added, even if some of them fail.
+    ///
+    /// This is how shutdown hooks are run, resources are released in the reverse order they were acquired.
+    ///
+    /// ### Returns
+    ///
+    /// The error of the first hook that failed, if any.
+    pub async fn run_all(self) -> HookResult {
+        let mut result = Ok(());
+        for hook in self.hooks.into_iter().rev() {
+            if let Err(e) = hook().await {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn recording_hooks(order: &Arc<Mutex<Vec<u8>>>, failing: u8) -> Hooks {
+        let mut hooks = Hooks::default();
+        for id in 1..=3 {
+            let order = order.clone();
+            hooks.push(move || async move {
+                order.lock().unwrap().push(id);
+                if id == failing {
+                    return Err(format!("hook {} failed", id).into());
+                }
+                Ok(())
+            });
+        }
+        hooks
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let result = recording_hooks(&order, 2).run().await;
+
+        assert_eq!(result.unwrap_err().to_string(), "hook 2 failed");
+        assert_eq!(*order.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_run_all() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let result = recording_hooks(&order, 2).run_all().await;
+
+        assert_eq!(result.unwrap_err().to_string(), "hook 2 failed");
+        assert_eq!(*order.lock().unwrap(), vec![3, 2, 1]);
+    }
+}
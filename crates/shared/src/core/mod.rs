@@ -1,2 +1,5 @@
+pub mod container;
 pub mod engine;
+pub mod gate;
 pub mod handler;
+pub mod lifecycle;
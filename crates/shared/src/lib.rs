@@ -1,8 +1,13 @@
+pub mod auth;
 pub mod core;
+pub mod middlewares;
 pub mod server;
 
 use std::{future::Future, pin::Pin};
 
+use http::StatusCode;
+
+use crate::core::gate::GateOutcome;
 use server::context::NgynContext;
 
 /// Trait to configure a gate, middleware or related service.
@@ -69,6 +74,23 @@ pub trait WithConfig<C: Default> {
 ///     }
 /// }
 /// ```
+///
+/// Gates can also respond to the requests they deny:
+///
+/// ```rust
+/// # use ngyn_shared::NgynGate;
+/// # use ngyn_shared::core::gate::GateOutcome;
+/// # use ngyn_shared::server::NgynContext;
+/// # use http::StatusCode;
+///
+/// struct MaintenanceGate;
+///
+/// impl NgynGate for MaintenanceGate {
+///     async fn check(_cx: &mut NgynContext<'_>) -> GateOutcome {
+///         GateOutcome::Deny(StatusCode::SERVICE_UNAVAILABLE)
+///     }
+/// }
+/// ```
 pub trait NgynGate {
     /// Determines if the gate can activate for the given request.
     ///
@@ -83,6 +105,35 @@ pub trait NgynGate {
     async fn can_activate(cx: &mut NgynContext<'_>) -> bool {
         true // default implementation
     }
+
+    /// Determines the outcome of the gate for the given request.
+    ///
+    /// This is what `#[handler(gates = [...])]` calls. By default, it runs [`NgynGate::can_activate`]:
+    /// requests it denies are `403 Forbidden`, unless it set an error response on the context.
+    ///
+    /// ### Arguments
+    ///
+    /// * `cx` - The request context to check.
+    ///
+    /// ### Returns
+    ///
+    /// Returns whether the route can activate, or the response of the request if it can't.
+    #[allow(async_fn_in_trait)]
+    async fn check(cx: &mut NgynContext<'_>) -> GateOutcome {
+        // the gate gets a blank response, so what it sets doesn't leak when gates are combined,
+        // and the response of the request keeps what middlewares set
+        let original = std::mem::take(cx.response_mut());
+        let allowed = Self::can_activate(cx).await;
+        let res = std::mem::replace(cx.response_mut(), original);
+        if allowed {
+            core::gate::merge_headers(cx.response_mut().headers_mut(), res.headers());
+            return GateOutcome::Allow;
+        }
+        match res.status() {
+            StatusCode::OK => GateOutcome::Deny(StatusCode::FORBIDDEN),
+            _ => GateOutcome::Respond(res),
+        }
+    }
 }
 
 /// Trait for implementing a middleware.
@@ -118,11 +169,57 @@ pub trait NgynMiddleware: Send + Sync {
         Self: Sized;
 }
 
-pub(crate) trait Middleware: Send + Sync {
+/// Trait for implementing a middleware that holds its own configuration or state.
+///
+/// Every [`NgynMiddleware`] is also a `Middleware`.
+/// Unlike [`NgynMiddleware`], a `Middleware` is used as an instance, and can also process
+/// the request once the route handler has run.
+///
+/// ### Examples
+///
+/// ```rust
+/// # use std::{future::Future, pin::Pin};
+/// # use ngyn_shared::Middleware;
+/// # use ngyn_shared::server::NgynContext;
+///
+/// pub struct PoweredBy(&'static str);
+///
+/// impl Middleware for PoweredBy {
+///     fn run<'a>(
+///         &'a self,
+///         _cx: &'a mut NgynContext<'_>,
+///     ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+///         Box::pin(async {})
+///     }
+///
+///     fn after<'a>(
+///         &'a self,
+///         cx: &'a mut NgynContext<'_>,
+///     ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+///         Box::pin(async move {
+///             cx.response_mut()
+///                 .headers_mut()
+///                 .insert("x-powered-by", self.0.parse().unwrap());
+///         })
+///     }
+/// }
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Handles the request, before the route handler is run.
     fn run<'a>(
         &'a self,
         _cx: &'a mut NgynContext<'_>,
     ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+    /// Handles the request, after the route handler has run.
+    ///
+    /// Middlewares are run here in the reverse order they are added.
+    fn after<'a>(
+        &'a self,
+        _cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(async {})
+    }
 }
 
 impl<'b, T: NgynMiddleware + Send + 'b> Middleware for T {
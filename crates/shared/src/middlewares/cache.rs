@@ -0,0 +1,691 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use http::{
+    header::{AGE, AUTHORIZATION, CACHE_CONTROL, CONTENT_LENGTH, SET_COOKIE, VARY},
+    HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+};
+
+use crate::{
+    server::{
+        response::{ReadBytes, ResponseStream},
+        NgynContext, NgynResponse,
+    },
+    Middleware,
+};
+
+/// The caching policy of a route.
+///
+/// Routes opt into caching with `#[handler(cache = 60)]`, or by inserting a policy in the extensions of the context.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+/// use ngyn::shared::middlewares::cache::CachePolicy;
+///
+/// app.get("/repos", handler(|cx| {
+///     cx.extensions_mut().insert(CachePolicy::new(Duration::from_secs(60)));
+///     fetch_repos()
+/// }));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct CachePolicy {
+    ttl: Duration,
+}
+
+impl CachePolicy {
+    /// Caches the responses of the route for `ttl`, unless they set a `Cache-Control` header.
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl }
+    }
+}
+
+/// A cached response.
+struct Entry {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    /// The request headers the response varies on, with their values in the request that was cached.
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    stored_at: Instant,
+    ttl: Duration,
+    /// How long the entry is served after it expires, while a request revalidates it.
+    stale: Duration,
+    revalidating: bool,
+    last_used: u64,
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        self.body.len()
+            + self
+                .headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum::<usize>()
+    }
+
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| headers.get(name) == value.as_ref())
+    }
+
+    fn is_dead(&self, now: Instant) -> bool {
+        now.duration_since(self.stored_at) >= self.ttl + self.stale
+    }
+}
+
+#[derive(Default)]
+struct Store {
+    /// The entries of a path and query, one for each variant of the response.
+    entries: HashMap<String, Vec<Entry>>,
+    count: usize,
+    size: usize,
+    /// A counter of the lookups, to find the least recently used entries.
+    clock: u64,
+}
+
+impl Store {
+    fn insert(&mut self, key: String, entry: Entry, max_entries: usize, max_size: usize) {
+        self.remove(&key, |cached| cached.vary == entry.vary);
+        self.count += 1;
+        self.size += entry.size();
+        self.entries.entry(key).or_default().push(entry);
+
+        let now = Instant::now();
+        if self.count > max_entries || self.size > max_size {
+            self.retain(|entry| !entry.is_dead(now));
+        }
+        while self.count > max_entries || self.size > max_size {
+            let oldest = self
+                .entries
+                .values()
+                .flatten()
+                .map(|entry| entry.last_used)
+                .min();
+            match oldest {
+                Some(oldest) => self.retain(|entry| entry.last_used != oldest),
+                None => break,
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &str, f: impl Fn(&Entry) -> bool) {
+        if let Some(entries) = self.entries.get_mut(key) {
+            entries.retain(|entry| {
+                if f(entry) {
+                    self.count -= 1;
+                    self.size -= entry.size();
+                    return false;
+                }
+                true
+            });
+            if entries.is_empty() {
+                self.entries.remove(key);
+            }
+        }
+    }
+
+    fn retain(&mut self, f: impl Fn(&Entry) -> bool) {
+        let (count, size) = (&mut self.count, &mut self.size);
+        self.entries.retain(|_, entries| {
+            entries.retain(|entry| {
+                if f(entry) {
+                    return true;
+                }
+                *count -= 1;
+                *size -= entry.size();
+                false
+            });
+            !entries.is_empty()
+        });
+    }
+}
+
+/// The cache key of a request, passed from the `run` to the `after` hook of the middleware.
+struct CacheLookup {
+    key: String,
+    /// Whether this request revalidates a stale entry.
+    revalidating: bool,
+}
+
+/// A middleware that caches the responses of `GET` routes in memory.
+///
+/// Only routes with a [`CachePolicy`] are cached, e.g. with `#[handler(cache = 60)]`.
+/// Responses are cached by path and query, and by the values of the request headers they vary on,
+/// i.e. the headers listed in their `Vary` header and the headers configured with [`CacheMiddleware::vary`].
+///
+/// The `Cache-Control` header of responses is honoured: `no-store`, `no-cache` and `private` responses aren't cached,
+/// `max-age` and `s-maxage` override the TTL of the route, and `stale-while-revalidate` the stale window.
+/// Responses setting cookies aren't cached either.
+///
+/// Responses to requests with an `Authorization` header are only cached when they are explicitly `public` or set `s-maxage`,
+/// and every response varies on `Authorization`: a response cached for a client is never served to a client with other credentials.
+///
+/// Once an entry expires, it is served stale during the stale window while the next request revalidates it,
+/// i.e. runs the route handler to refresh the entry.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// app.use_middleware(
+///     CacheMiddleware::new()
+///         .max_entries(500)
+///         .stale_while_revalidate(Duration::from_secs(30)),
+/// );
+///
+/// #[handler(cache = 60)]
+/// async fn repos() -> String {
+///     github::fetch_repos().await
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CacheMiddleware {
+    store: Arc<Mutex<Store>>,
+    vary: Vec<HeaderName>,
+    max_entries: usize,
+    max_size: usize,
+    stale_while_revalidate: Duration,
+}
+
+impl Default for CacheMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CacheMiddleware {
+    /// Creates a cache of up to 1000 entries and 32MiB, without stale window.
+    pub fn new() -> Self {
+        Self {
+            store: Arc::default(),
+            vary: Vec::new(),
+            max_entries: 1000,
+            max_size: 32 * 1024 * 1024,
+            stale_while_revalidate: Duration::ZERO,
+        }
+    }
+
+    /// Sets request headers every response varies on, e.g. `Accept-Language`.
+    pub fn vary<I: IntoIterator<Item = HeaderName>>(mut self, headers: I) -> Self {
+        self.vary = headers.into_iter().collect();
+        self
+    }
+
+    /// Sets the maximum number of cached responses, the least recently used are evicted first.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Sets the maximum size of the cached responses, in bytes.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Sets how long expired responses are served while they are revalidated.
+    pub fn stale_while_revalidate(mut self, stale: Duration) -> Self {
+        self.stale_while_revalidate = stale;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Store> {
+        self.store.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the TTL and stale window of a response, or `None` if it can't be cached.
+    fn freshness(
+        &self,
+        policy: CachePolicy,
+        headers: &HeaderMap,
+        authorized: bool,
+    ) -> Option<(Duration, Duration)> {
+        let (mut ttl, mut shared_ttl) = (policy.ttl, None);
+        let mut stale = self.stale_while_revalidate;
+        let mut public = false;
+
+        let directives = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|directive| directive.trim().to_ascii_lowercase());
+        for directive in directives {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim().to_string(), value.trim().parse::<u64>().ok()),
+                None => (directive, None),
+            };
+            match (name.as_str(), value) {
+                ("no-store" | "no-cache" | "private", _) => return None,
+                ("public", _) => public = true,
+                ("max-age", Some(secs)) => ttl = Duration::from_secs(secs),
+                ("s-maxage", Some(secs)) => shared_ttl = Some(Duration::from_secs(secs)),
+                ("stale-while-revalidate", Some(secs)) => stale = Duration::from_secs(secs),
+                _ => {}
+            }
+        }
+
+        // responses to authorized requests must explicitly allow shared caches, see RFC 9111 section 3.5
+        if authorized && !public && shared_ttl.is_none() {
+            return None;
+        }
+        // `s-maxage` applies to shared caches, which this cache is
+        let ttl = shared_ttl.unwrap_or(ttl);
+        (!ttl.is_zero()).then_some((ttl, stale))
+    }
+
+    async fn lookup(&self, cx: &mut NgynContext<'_>) {
+        let request = cx.request();
+        let method = request.method().clone();
+        if method != Method::GET && method != Method::HEAD {
+            return;
+        }
+        let key = match request.uri().query() {
+            Some(query) => format!("{}?{}", request.uri().path(), query),
+            None => request.uri().path().to_string(),
+        };
+        // clients can ask for a fresh response, which is then cached
+        let refresh = request
+            .headers()
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .any(|value| value.contains("no-cache") || value.contains("no-store"));
+
+        let mut revalidating = false;
+        let mut cached = None;
+        if !refresh {
+            let now = Instant::now();
+            let mut store = self.lock();
+            store.clock += 1;
+            let clock = store.clock;
+
+            let entry = store.entries.get_mut(&key).and_then(|entries| {
+                entries
+                    .iter_mut()
+                    .find(|entry| entry.matches(request.headers()))
+            });
+            if let Some(entry) = entry {
+                let age = now.duration_since(entry.stored_at);
+                let is_fresh = age < entry.ttl;
+                let is_stale = !is_fresh && !entry.is_dead(now);
+
+                if is_stale && !entry.revalidating {
+                    entry.revalidating = true;
+                    revalidating = true;
+                } else if is_fresh || is_stale {
+                    entry.last_used = clock;
+                    let mut res = NgynResponse::default();
+                    *res.status_mut() = entry.status;
+                    *res.headers_mut() = entry.headers.clone();
+                    res.headers_mut().insert(AGE, age.as_secs().into());
+                    if method == Method::HEAD {
+                        res.headers_mut()
+                            .insert(CONTENT_LENGTH, entry.body.len().into());
+                    } else {
+                        *res.body_mut() = entry.body.clone().into();
+                    }
+                    cached = Some(res);
+                }
+            }
+        }
+
+        match cached {
+            Some(res) => {
+                *cx.response_mut() = res;
+                cx.halt();
+            }
+            None => {
+                cx.extensions_mut()
+                    .insert(CacheLookup { key, revalidating });
+            }
+        }
+    }
+
+    async fn store(&self, cx: &mut NgynContext<'_>) {
+        let Some(lookup) = cx.extensions_mut().remove::<CacheLookup>() else {
+            return;
+        };
+        let policy = cx.extensions().get::<CachePolicy>().copied();
+        let request = cx.request().headers().clone();
+        let is_get = cx.request().method() == Method::GET;
+        let authorized = request.contains_key(AUTHORIZATION);
+
+        let res = cx.response_mut();
+        let headers = res.headers();
+        let vary = headers
+            .get_all(VARY)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+        let freshness = policy
+            .filter(|_| {
+                is_get
+                    && res.status() == StatusCode::OK
+                    && !headers.contains_key(SET_COOKIE)
+                    && !ResponseStream::is_set(res)
+                    && !vary.iter().any(|name| name == "*")
+            })
+            .and_then(|policy| self.freshness(policy, headers, authorized));
+
+        let Some((ttl, stale)) = freshness else {
+            if lookup.revalidating {
+                // let the next request revalidate the entry
+                let mut store = self.lock();
+                if let Some(entry) = store
+                    .entries
+                    .get_mut(&lookup.key)
+                    .and_then(|entries| entries.iter_mut().find(|entry| entry.matches(&request)))
+                {
+                    entry.revalidating = false;
+                }
+            }
+            return;
+        };
+
+        // entries never match requests with other credentials
+        let mut names = vec![AUTHORIZATION];
+        names.extend(self.vary.iter().cloned());
+        names.extend(
+            vary.iter()
+                .filter_map(|name| HeaderName::try_from(name.as_str()).ok()),
+        );
+        names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        names.dedup();
+        let vary = names
+            .into_iter()
+            .map(|name| {
+                let value = request.get(&name).cloned();
+                (name, value)
+            })
+            .collect();
+
+        let body = res.read_bytes().await.unwrap_or_default();
+        *res.body_mut() = body.clone().into();
+
+        let mut store = self.lock();
+        let entry = Entry {
+            status: res.status(),
+            headers: res.headers().clone(),
+            body,
+            vary,
+            stored_at: Instant::now(),
+            ttl,
+            stale,
+            revalidating: false,
+            last_used: store.clock,
+        };
+        if entry.size() <= self.max_size {
+            store.insert(lookup.key, entry, self.max_entries, self.max_size);
+        }
+    }
+}
+
+impl Middleware for CacheMiddleware {
+    fn run<'a>(
+        &'a self,
+        cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(self.lookup(cx))
+    }
+
+    fn after<'a>(
+        &'a self,
+        cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(self.store(cx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use http::header::ACCEPT_LANGUAGE;
+
+    use super::*;
+    use crate::core::{
+        engine::{read_body, MockEngine, NgynEngine},
+        handler::{async_handler, handler},
+    };
+
+    /// Creates an engine with a cached route, returning the number of times its handler ran.
+    fn engine(
+        cache: CacheMiddleware,
+        ttl: Duration,
+        cache_control: Option<&'static str>,
+    ) -> (MockEngine, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+
+        let mut engine = MockEngine::default();
+        engine.use_middleware(cache);
+        engine.any(
+            "/repos",
+            handler(move |cx| {
+                cx.extensions_mut().insert(CachePolicy::new(ttl));
+                if let Some(cache_control) = cache_control {
+                    cx.response_mut()
+                        .headers_mut()
+                        .insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
+                }
+                let calls = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                format!("call {}", calls)
+            }),
+        );
+        engine.any("/uncached", handler(|_| "uncached"));
+        (engine, calls)
+    }
+
+    async fn send(engine: &MockEngine, path: &str, headers: &[(&str, &str)]) -> NgynResponse {
+        engine.send(Method::GET, path, headers).await
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit() {
+        let (engine, calls) = engine(CacheMiddleware::new(), Duration::from_secs(60), None);
+
+        assert_eq!(
+            read_body(send(&engine, "/repos", &[]).await).await,
+            "call 1"
+        );
+        let res = send(&engine, "/repos", &[]).await;
+        assert_eq!(res.headers()[AGE], "0");
+        assert_eq!(read_body(res).await, "call 1");
+
+        // the query is part of the key
+        assert_eq!(
+            read_body(send(&engine, "/repos?page=2", &[]).await).await,
+            "call 2"
+        );
+        // clients can ask for a fresh response
+        let res = send(&engine, "/repos", &[(CACHE_CONTROL.as_str(), "no-cache")]).await;
+        assert_eq!(read_body(res).await, "call 3");
+        assert_eq!(
+            read_body(send(&engine, "/repos", &[]).await).await,
+            "call 3"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // routes without a policy aren't cached
+        let res = send(&engine, "/uncached", &[]).await;
+        assert!(!res.headers().contains_key(AGE));
+        let res = send(&engine, "/uncached", &[]).await;
+        assert!(!res.headers().contains_key(AGE));
+    }
+
+    #[tokio::test]
+    async fn test_cache_control() {
+        for cache_control in ["no-store", "private, max-age=60", "max-age=0"] {
+            let (engine, calls) = engine(
+                CacheMiddleware::new(),
+                Duration::from_secs(60),
+                Some(cache_control),
+            );
+            send(&engine, "/repos", &[]).await;
+            send(&engine, "/repos", &[]).await;
+            assert_eq!(calls.load(Ordering::SeqCst), 2, "{}", cache_control);
+        }
+
+        // `max-age` overrides the TTL of the route
+        let (engine, calls) = engine(
+            CacheMiddleware::new(),
+            Duration::ZERO,
+            Some("public, max-age=60"),
+        );
+        send(&engine, "/repos", &[]).await;
+        send(&engine, "/repos", &[]).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_vary() {
+        let cache = CacheMiddleware::new().vary([ACCEPT_LANGUAGE]);
+        let (engine, calls) = engine(cache, Duration::from_secs(60), None);
+
+        let english = [(ACCEPT_LANGUAGE.as_str(), "en")];
+        let french = [(ACCEPT_LANGUAGE.as_str(), "fr")];
+        assert_eq!(
+            read_body(send(&engine, "/repos", &english).await).await,
+            "call 1"
+        );
+        assert_eq!(
+            read_body(send(&engine, "/repos", &french).await).await,
+            "call 2"
+        );
+        assert_eq!(
+            read_body(send(&engine, "/repos", &english).await).await,
+            "call 1"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_eviction() {
+        let (engine, calls) = engine(
+            CacheMiddleware::new().max_entries(1),
+            Duration::from_secs(60),
+            None,
+        );
+
+        send(&engine, "/repos?page=1", &[]).await;
+        send(&engine, "/repos?page=2", &[]).await;
+        assert_eq!(
+            read_body(send(&engine, "/repos?page=2", &[]).await).await,
+            "call 2"
+        );
+        assert_eq!(
+            read_body(send(&engine, "/repos?page=1", &[]).await).await,
+            "call 3"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    /// Creates an engine with a cached route checking credentials, like a route with gates would.
+    fn gated_engine(cache_control: Option<&'static str>) -> (MockEngine, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+
+        let mut engine = MockEngine::default();
+        engine.use_middleware(CacheMiddleware::new());
+        engine.any(
+            "/account",
+            handler(move |cx| {
+                // the policy is set before the gates run, as `#[handler(cache = 60)]` does
+                cx.extensions_mut()
+                    .insert(CachePolicy::new(Duration::from_secs(60)));
+                let Some(user) = cx.request().headers().get(AUTHORIZATION).cloned() else {
+                    *cx.response_mut().status_mut() = StatusCode::UNAUTHORIZED;
+                    return "unauthorized".to_string();
+                };
+                if let Some(cache_control) = cache_control {
+                    cx.response_mut()
+                        .headers_mut()
+                        .insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+                format!("account of {}", user.to_str().unwrap())
+            }),
+        );
+        (engine, calls)
+    }
+
+    #[tokio::test]
+    async fn test_authorized_requests() {
+        let (engine, calls) = gated_engine(None);
+        let ada = [(AUTHORIZATION.as_str(), "ada")];
+
+        let res = send(&engine, "/account", &ada).await;
+        assert_eq!(read_body(res).await, "account of ada");
+        let res = send(&engine, "/account", &[]).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        send(&engine, "/account", &ada).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // public responses are cached, but only served to the same credentials
+        let (engine, calls) = gated_engine(Some("public"));
+        send(&engine, "/account", &ada).await;
+        let res = send(&engine, "/account", &ada).await;
+        assert!(res.headers().contains_key(AGE));
+        assert_eq!(read_body(res).await, "account of ada");
+        let res = send(&engine, "/account", &[]).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = send(&engine, "/account", &[(AUTHORIZATION.as_str(), "bob")]).await;
+        assert_eq!(read_body(res).await, "account of bob");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stale_while_revalidate() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+
+        let mut engine = MockEngine::default();
+        engine
+            .use_middleware(CacheMiddleware::new().stale_while_revalidate(Duration::from_secs(60)));
+        engine.any(
+            "/repos",
+            async_handler(move |cx| {
+                cx.extensions_mut()
+                    .insert(CachePolicy::new(Duration::from_millis(50)));
+                let calls = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    format!("call {}", calls)
+                }
+            }),
+        );
+
+        assert_eq!(
+            read_body(send(&engine, "/repos", &[]).await).await,
+            "call 1"
+        );
+        tokio::time::sleep(Duration::from_millis(60)).await;
+
+        // the first request revalidates the entry, while the others get the stale one
+        let (revalidated, stale) = tokio::join!(send(&engine, "/repos", &[]), async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            send(&engine, "/repos", &[]).await
+        });
+        assert_eq!(read_body(revalidated).await, "call 2");
+        assert_eq!(read_body(stale).await, "call 1");
+        assert_eq!(
+            read_body(send(&engine, "/repos", &[]).await).await,
+            "call 2"
+        );
+    }
+}
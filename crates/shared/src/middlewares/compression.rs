@@ -0,0 +1,694 @@
+use std::{future::Future, io, pin::Pin, str::FromStr};
+
+use bytes::Bytes;
+use http::{
+    header::{
+        ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG, VARY,
+    },
+    HeaderMap, HeaderValue, Method, StatusCode,
+};
+
+use crate::{
+    server::{
+        response::{ReadBytes, ResponseStream},
+        NgynContext, NgynRequest, NgynResponse,
+    },
+    Middleware,
+};
+
+/// A content encoding supported by ngyn, each encoding is enabled by the cargo feature of the same name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// The `gzip` encoding.
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// The `deflate` encoding, i.e. zlib.
+    #[cfg(feature = "deflate")]
+    Deflate,
+    /// The `br` encoding.
+    #[cfg(feature = "brotli")]
+    Brotli,
+    /// The `zstd` encoding.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Encoding {
+    /// The enabled encodings, from the most to the least preferred.
+    pub const ALL: &'static [Encoding] = &[
+        #[cfg(feature = "brotli")]
+        Encoding::Brotli,
+        #[cfg(feature = "zstd")]
+        Encoding::Zstd,
+        #[cfg(feature = "gzip")]
+        Encoding::Gzip,
+        #[cfg(feature = "deflate")]
+        Encoding::Deflate,
+    ];
+
+    /// Returns the name of the encoding, as used in the `Content-Encoding` header.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "gzip")]
+            Encoding::Gzip => "gzip",
+            #[cfg(feature = "deflate")]
+            Encoding::Deflate => "deflate",
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => "br",
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => "zstd",
+        }
+    }
+
+    /// Compresses bytes with the encoding.
+    #[allow(unused_variables)]
+    pub fn compress(&self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        #[allow(unused_imports)]
+        use std::io::Write;
+
+        match *self {
+            #[cfg(feature = "gzip")]
+            Encoding::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "deflate")]
+            Encoding::Deflate => {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => {
+                let mut output = Vec::new();
+                {
+                    let mut encoder = brotli::CompressorWriter::new(&mut output, 4096, 5, 22);
+                    encoder.write_all(bytes)?;
+                }
+                Ok(output)
+            }
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => zstd::encode_all(bytes, 0),
+        }
+    }
+
+    /// Decompresses bytes encoded with the encoding.
+    ///
+    /// ### Arguments
+    ///
+    /// * `bytes` - The encoded bytes.
+    /// * `limit` - The maximum size of the decompressed bytes.
+    ///
+    /// ### Returns
+    ///
+    /// The decompressed bytes, or an error of kind `InvalidData` if they are corrupt,
+    /// or of kind `InvalidInput` if they exceed the limit.
+    #[allow(unused_variables, unreachable_code)]
+    pub fn decompress(&self, bytes: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+        #[allow(unused_imports)]
+        use std::io::Read;
+
+        let decoder: Box<dyn Read + '_> = match *self {
+            #[cfg(feature = "gzip")]
+            Encoding::Gzip => Box::new(flate2::read::MultiGzDecoder::new(bytes)),
+            #[cfg(feature = "deflate")]
+            Encoding::Deflate => Box::new(flate2::read::ZlibDecoder::new(bytes)),
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => Box::new(brotli::Decompressor::new(bytes, 4096)),
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => Box::new(zstd::stream::read::Decoder::new(bytes)?),
+        };
+
+        // one more byte than the limit is read, to detect bodies exceeding it
+        let mut output = Vec::new();
+        decoder
+            .take(limit as u64 + 1)
+            .read_to_end(&mut output)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        if output.len() > limit {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "decompressed body exceeds the limit",
+            ));
+        }
+        Ok(output)
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Encoding::ALL
+            .iter()
+            .find(|encoding| encoding.as_str().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or(())
+    }
+}
+
+/// Decompresses the bodies of requests sent with a `Content-Encoding`, before they are handled.
+///
+/// Requests are rejected with:
+/// - `415 Unsupported Media Type` if an encoding isn't enabled, the response lists the supported encodings in `Accept-Encoding`.
+/// - `413 Payload Too Large` if the decompressed body exceeds the size limit, which protects against decompression bombs.
+/// - `400 Bad Request` if the body is corrupt.
+///
+/// Bodies larger than 64KiB are decompressed on the blocking thread pool.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+/// use ngyn::shared::middlewares::compression::RequestDecompression;
+///
+/// app.decompress_requests(RequestDecompression::new().max_size(2 * 1024 * 1024));
+/// ```
+#[derive(Clone, Debug)]
+pub struct RequestDecompression {
+    encodings: Vec<Encoding>,
+    max_size: usize,
+}
+
+impl Default for RequestDecompression {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestDecompression {
+    /// Decompresses requests encoded with any enabled encoding, up to 10MiB.
+    pub fn new() -> Self {
+        Self {
+            encodings: Encoding::ALL.to_vec(),
+            max_size: 10 * 1024 * 1024,
+        }
+    }
+
+    /// Sets the encodings accepted in requests.
+    pub fn encodings<I: IntoIterator<Item = Encoding>>(mut self, encodings: I) -> Self {
+        self.encodings = encodings.into_iter().collect();
+        self
+    }
+
+    /// Sets the maximum size of decompressed bodies, in bytes.
+    pub fn max_size(mut self, size: usize) -> Self {
+        self.max_size = size;
+        self
+    }
+
+    /// Decompresses the body of a request in place.
+    ///
+    /// ### Returns
+    ///
+    /// The response to send instead of handling the request, if the body can't be decompressed.
+    pub(crate) async fn decompress(&self, req: &mut NgynRequest) -> Option<NgynResponse> {
+        let value = req.headers().get(CONTENT_ENCODING)?;
+
+        // encodings are listed in the order they were applied
+        let mut encodings = Vec::new();
+        for name in value.to_str().unwrap_or_default().split(',') {
+            let name = name.trim();
+            if name.is_empty() || name.eq_ignore_ascii_case("identity") {
+                continue;
+            }
+            match name.parse::<Encoding>() {
+                Ok(encoding) if self.encodings.contains(&encoding) => encodings.push(encoding),
+                _ => return Some(self.unsupported()),
+            }
+        }
+
+        for encoding in encodings.into_iter().rev() {
+            let body = std::mem::take(req.body_mut());
+            match decompress_body(encoding, body, self.max_size).await {
+                Ok(body) => *req.body_mut() = body,
+                Err(e) => {
+                    let status = match e.kind() {
+                        io::ErrorKind::InvalidInput => StatusCode::PAYLOAD_TOO_LARGE,
+                        _ => StatusCode::BAD_REQUEST,
+                    };
+                    return Some(error_response(status));
+                }
+            }
+        }
+
+        let len = req.body().len();
+        let headers = req.headers_mut();
+        headers.remove(CONTENT_ENCODING);
+        headers.insert(CONTENT_LENGTH, len.into());
+        None
+    }
+
+    fn unsupported(&self) -> NgynResponse {
+        let mut res = error_response(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let accepted = self
+            .encodings
+            .iter()
+            .map(Encoding::as_str)
+            .chain(["identity"])
+            .collect::<Vec<_>>()
+            .join(", ");
+        if let Ok(accepted) = HeaderValue::try_from(accepted) {
+            res.headers_mut().insert(ACCEPT_ENCODING, accepted);
+        }
+        res
+    }
+}
+
+fn error_response(status: StatusCode) -> NgynResponse {
+    let mut res = NgynResponse::default();
+    *res.status_mut() = status;
+    res
+}
+
+/// Parses the `Accept-Encoding` header of a request, into the accepted encoding names and their quality.
+pub(crate) fn accepted_encodings(headers: &HeaderMap) -> Vec<(&str, f32)> {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|value| {
+            let mut parts = value.split(';');
+            let name = parts.next().unwrap_or_default().trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            Some((name, quality))
+        })
+        .collect()
+}
+
+/// Picks the encoding of a response, from the `Accept-Encoding` header of its request.
+///
+/// The encoding with the highest quality wins, ties are broken by the order of `encodings`.
+fn negotiate(headers: &HeaderMap, encodings: &[Encoding]) -> Option<Encoding> {
+    let mut wildcard = None;
+    let mut qualities = Vec::new();
+
+    for (name, quality) in accepted_encodings(headers) {
+        if name == "*" {
+            wildcard = Some(quality);
+        } else if let Ok(encoding) = name.parse::<Encoding>() {
+            qualities.push((encoding, quality));
+        }
+    }
+
+    encodings
+        .iter()
+        .filter_map(|encoding| {
+            let quality = qualities
+                .iter()
+                .find(|(e, _)| e == encoding)
+                .map(|(_, q)| *q)
+                .or(wildcard)?;
+            (quality > 0.0).then_some((*encoding, quality))
+        })
+        // `max_by` keeps the last maximum, so the encodings are reversed to prefer the first one
+        .rev()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(encoding, _)| encoding)
+}
+
+/// Checks if a content type is worth compressing, i.e. isn't already compressed.
+pub fn is_compressible(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    if essence == "image/svg+xml" {
+        return true;
+    }
+    !(essence.starts_with("image/")
+        || essence.starts_with("audio/")
+        || essence.starts_with("video/")
+        || essence.starts_with("font/woff")
+        || essence == "text/event-stream"
+        || matches!(
+            essence.as_str(),
+            "application/zip"
+                | "application/gzip"
+                | "application/x-gzip"
+                | "application/zstd"
+                | "application/x-bzip2"
+                | "application/x-7z-compressed"
+                | "application/x-rar-compressed"
+                | "application/pdf"
+                | "application/wasm"
+        ))
+}
+
+/// A middleware that compresses response bodies, following the `Accept-Encoding` header of the request.
+///
+/// Responses that are empty, partial, smaller than the minimum size, of an already compressed content type,
+/// already encoded, or marked `Cache-Control: no-transform` aren't compressed.
+/// The encodings are enabled with the `gzip`, `deflate`, `brotli` and `zstd` cargo features.
+/// Bodies larger than 64KiB are compressed on the blocking thread pool.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+/// use ngyn::shared::middlewares::compression::CompressionMiddleware;
+///
+/// app.use_middleware(CompressionMiddleware::new().min_size(512));
+/// ```
+pub struct CompressionMiddleware {
+    encodings: Vec<Encoding>,
+    min_size: usize,
+}
+
+impl Default for CompressionMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CompressionMiddleware {
+    /// Creates a compression middleware using every enabled encoding, for bodies of at least 1KiB.
+    pub fn new() -> Self {
+        Self {
+            encodings: Encoding::ALL.to_vec(),
+            min_size: 1024,
+        }
+    }
+
+    /// Sets the encodings to use, from the most to the least preferred.
+    pub fn encodings<I: IntoIterator<Item = Encoding>>(mut self, encodings: I) -> Self {
+        self.encodings = encodings.into_iter().collect();
+        self
+    }
+
+    /// Sets the minimum size of the bodies to compress, in bytes.
+    pub fn min_size(mut self, size: usize) -> Self {
+        self.min_size = size;
+        self
+    }
+
+    async fn compress(&self, cx: &mut NgynContext<'_>) {
+        if cx.request().method() == Method::HEAD {
+            return;
+        }
+        let accepted = negotiate(cx.request().headers(), &self.encodings);
+
+        let res = cx.response_mut();
+        let status = res.status();
+        let headers = res.headers();
+        if status.is_informational()
+            || ResponseStream::is_set(res)
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::PARTIAL_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+            || headers.contains_key(CONTENT_ENCODING)
+            || headers
+                .get(CACHE_CONTROL)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.to_ascii_lowercase().contains("no-transform"))
+            || headers
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| !is_compressible(value))
+        {
+            return;
+        }
+
+        let Ok(body) = res.read_bytes().await else {
+            return;
+        };
+        if body.len() < self.min_size {
+            *res.body_mut() = body.into();
+            return;
+        }
+
+        // the response depends on the encodings accepted by the client, even if it isn't compressed
+        res.headers_mut()
+            .append(VARY, HeaderValue::from_static("accept-encoding"));
+
+        let compressed = match accepted {
+            Some(encoding) => compress_body(encoding, body.clone())
+                .await
+                .ok()
+                .filter(|compressed| compressed.len() < body.len())
+                .map(|compressed| (encoding, compressed)),
+            None => None,
+        };
+        let Some((encoding, compressed)) = compressed else {
+            *res.body_mut() = body.into();
+            return;
+        };
+
+        let headers = res.headers_mut();
+        headers.insert(
+            CONTENT_ENCODING,
+            HeaderValue::from_static(encoding.as_str()),
+        );
+        headers.remove(CONTENT_LENGTH);
+        // the compressed body isn't byte-for-byte identical anymore
+        if let Some(etag) = headers.get(ETAG).and_then(|value| value.to_str().ok()) {
+            if !etag.starts_with("W/") {
+                if let Ok(weak) = HeaderValue::try_from(format!("W/{}", etag)) {
+                    headers.insert(ETAG, weak);
+                }
+            }
+        }
+        *res.body_mut() = compressed.into();
+    }
+}
+
+/// The size above which bodies are compressed on the blocking thread pool.
+const BLOCKING_SIZE: usize = 64 * 1024;
+
+/// Compresses a body, on the blocking thread pool if it's large enough to stall other requests.
+async fn compress_body(encoding: Encoding, body: Bytes) -> io::Result<Vec<u8>> {
+    if body.len() < BLOCKING_SIZE {
+        return encoding.compress(&body);
+    }
+    tokio::task::spawn_blocking(move || encoding.compress(&body))
+        .await
+        .map_err(io::Error::other)?
+}
+
+/// Decompresses a body, on the blocking thread pool if it's large enough to stall other requests.
+async fn decompress_body(encoding: Encoding, body: Vec<u8>, limit: usize) -> io::Result<Vec<u8>> {
+    if body.len() < BLOCKING_SIZE {
+        return encoding.decompress(&body, limit);
+    }
+    tokio::task::spawn_blocking(move || encoding.decompress(&body, limit))
+        .await
+        .map_err(io::Error::other)?
+}
+
+impl Middleware for CompressionMiddleware {
+    fn run<'a>(
+        &'a self,
+        _cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(async {})
+    }
+
+    fn after<'a>(
+        &'a self,
+        cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(self.compress(cx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    use std::io::Read;
+
+    use http::Method;
+
+    use super::*;
+    use crate::{
+        core::engine::{read_body, MockEngine, NgynEngine},
+        core::handler::handler,
+        server::NgynResponse,
+    };
+
+    fn engine() -> MockEngine {
+        let mut engine = MockEngine::default();
+        engine.use_middleware(CompressionMiddleware::new());
+        engine.any("/large", handler(|_| "ngyn ".repeat(1000)));
+        engine.any("/small", handler(|_| "ngyn"));
+        engine.any("/huge", handler(|_| "ngyn ".repeat(20_000)));
+        engine.any(
+            "/image",
+            handler(|cx| {
+                cx.response_mut()
+                    .headers_mut()
+                    .insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
+                "ngyn ".repeat(1000)
+            }),
+        );
+        engine
+    }
+
+    async fn send(engine: &MockEngine, path: &str, accept: &str) -> NgynResponse {
+        engine
+            .send(Method::GET, path, &[(ACCEPT_ENCODING.as_str(), accept)])
+            .await
+    }
+
+    #[cfg(all(feature = "gzip", feature = "brotli", feature = "zstd"))]
+    #[test]
+    fn test_negotiate() {
+        let negotiate = |accept: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT_ENCODING, accept.parse().unwrap());
+            negotiate(&headers, Encoding::ALL)
+        };
+
+        assert_eq!(negotiate("gzip, br"), Some(Encoding::Brotli));
+        assert_eq!(negotiate("gzip;q=1.0, br;q=0.5"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("*;q=0.1, br;q=0"), Some(Encoding::Zstd));
+        assert_eq!(negotiate("identity"), None);
+        assert_eq!(negotiate("gzip;q=0"), None);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[tokio::test]
+    async fn test_gzip() {
+        let res = send(&engine(), "/large", "gzip").await;
+
+        assert_eq!(res.headers()[CONTENT_ENCODING], "gzip");
+        assert_eq!(res.headers()[VARY], "accept-encoding");
+
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(read_body(res).await.as_ref())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "ngyn ".repeat(1000));
+
+        // large bodies are compressed on the blocking thread pool
+        let res = send(&engine(), "/huge", "gzip").await;
+        assert_eq!(res.headers()[CONTENT_ENCODING], "gzip");
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(read_body(res).await.as_ref())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "ngyn ".repeat(20_000));
+    }
+
+    #[cfg(feature = "brotli")]
+    #[tokio::test]
+    async fn test_brotli() {
+        let res = send(&engine(), "/large", "gzip, deflate, br").await;
+        assert_eq!(res.headers()[CONTENT_ENCODING], "br");
+
+        let mut decoded = String::new();
+        brotli::Decompressor::new(read_body(res).await.as_ref(), 4096)
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "ngyn ".repeat(1000));
+    }
+
+    #[tokio::test]
+    async fn test_skipped() {
+        let engine = engine();
+
+        let res = send(&engine, "/small", "gzip").await;
+        assert!(!res.headers().contains_key(CONTENT_ENCODING));
+        assert_eq!(read_body(res).await, "ngyn");
+
+        let res = send(&engine, "/image", "gzip").await;
+        assert!(!res.headers().contains_key(CONTENT_ENCODING));
+        assert!(!res.headers().contains_key(VARY));
+
+        let res = send(&engine, "/large", "identity").await;
+        assert!(!res.headers().contains_key(CONTENT_ENCODING));
+        assert_eq!(res.headers()[VARY], "accept-encoding");
+    }
+
+    #[cfg(feature = "gzip")]
+    fn decompressing_engine(decompression: RequestDecompression) -> MockEngine {
+        let mut engine = MockEngine::default();
+        engine.decompress_requests(decompression);
+        engine.any(
+            "/echo",
+            handler(|cx| String::from_utf8_lossy(cx.request().body()).to_string()),
+        );
+        engine
+    }
+
+    #[cfg(feature = "gzip")]
+    async fn post(engine: &MockEngine, encoding: &str, body: Vec<u8>) -> NgynResponse {
+        let headers = [(CONTENT_ENCODING.as_str(), encoding)];
+        engine
+            .send_body(Method::POST, "/echo", &headers, body)
+            .await
+    }
+
+    #[cfg(all(feature = "gzip", feature = "brotli"))]
+    #[tokio::test]
+    async fn test_decompress() {
+        let engine = decompressing_engine(RequestDecompression::new());
+        let payload = br#"{"name": "ngyn"}"#;
+
+        for encoding in Encoding::ALL {
+            let res = post(
+                &engine,
+                encoding.as_str(),
+                encoding.compress(payload).unwrap(),
+            )
+            .await;
+            assert_eq!(read_body(res).await, payload[..], "{}", encoding.as_str());
+        }
+
+        // encodings are undone in the reverse order they were applied
+        let stacked = Encoding::Brotli
+            .compress(&Encoding::Gzip.compress(payload).unwrap())
+            .unwrap();
+        let res = post(&engine, "gzip, br", stacked).await;
+        assert_eq!(read_body(res).await, payload[..]);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[tokio::test]
+    async fn test_decompress_large() {
+        let engine = decompressing_engine(RequestDecompression::new());
+        // a payload that doesn't compress well, so it's decompressed on the blocking thread pool
+        let mut seed = 1u64;
+        let payload = (0..40_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                format!("{:x}", seed >> 48)
+            })
+            .collect::<String>();
+
+        let compressed = Encoding::Gzip.compress(payload.as_bytes()).unwrap();
+        assert!(compressed.len() > BLOCKING_SIZE);
+        let res = post(&engine, "gzip", compressed).await;
+        assert_eq!(read_body(res).await, payload);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[tokio::test]
+    async fn test_decompress_rejected() {
+        let engine = decompressing_engine(
+            RequestDecompression::new()
+                .encodings([Encoding::Gzip])
+                .max_size(100),
+        );
+
+        let bomb = Encoding::Gzip.compress(&[0; 1000]).unwrap();
+        let res = post(&engine, "gzip", bomb).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let res = post(&engine, "gzip", b"not gzip".to_vec()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let res = post(&engine, "br", Vec::new()).await;
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(res.headers()[ACCEPT_ENCODING], "gzip, identity");
+    }
+}
@@ -0,0 +1,497 @@
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use http::{
+    header::{
+        ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+        ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS,
+        ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD,
+        ORIGIN, VARY,
+    },
+    HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+};
+
+use crate::{
+    server::{NgynContext, NgynRequest},
+    Middleware,
+};
+
+type OriginPredicate = dyn Fn(&str, &NgynRequest) -> bool + Send + Sync;
+
+/// An origin allowed by a [`CorsMiddleware`].
+enum AllowedOrigin {
+    Exact(String),
+    /// An origin with a single `*` wildcard, e.g. `https://*.example.com`.
+    Wildcard(String, String),
+    Predicate(Arc<OriginPredicate>),
+}
+
+impl AllowedOrigin {
+    fn matches(&self, origin: &str, req: &NgynRequest) -> bool {
+        match self {
+            AllowedOrigin::Exact(allowed) => allowed == origin,
+            AllowedOrigin::Wildcard(prefix, suffix) => {
+                origin.len() > prefix.len() + suffix.len()
+                    && origin.starts_with(prefix.as_str())
+                    && origin.ends_with(suffix.as_str())
+                    // the wildcard matches a single label of the host
+                    && !origin[prefix.len()..origin.len() - suffix.len()].contains(['/', '.', ':'])
+            }
+            AllowedOrigin::Predicate(predicate) => predicate(origin, req),
+        }
+    }
+}
+
+/// A middleware that handles Cross-Origin Resource Sharing (CORS).
+///
+/// Preflight requests from allowed origins are answered with `204 No Content` before they reach the route handlers,
+/// other requests from allowed origins get the CORS headers added to their response.
+/// Add it before other middlewares, so preflight requests are answered before they run.
+///
+/// No origin is allowed by default.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+/// use ngyn::shared::middlewares::cors::CorsMiddleware;
+///
+/// app.use_middleware(
+///     CorsMiddleware::new()
+///         .allow_origin("https://example.com")
+///         .allow_origin("https://*.example.com")
+///         .allow_headers([CONTENT_TYPE, AUTHORIZATION])
+///         .allow_credentials(true)
+///         .max_age(Duration::from_secs(3600)),
+/// );
+/// ```
+pub struct CorsMiddleware {
+    any_origin: bool,
+    origins: Vec<AllowedOrigin>,
+    methods: Vec<Method>,
+    any_header: bool,
+    headers: Vec<HeaderName>,
+    expose_headers: Vec<HeaderName>,
+    credentials: bool,
+    max_age: Option<Duration>,
+}
+
+impl Default for CorsMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CorsMiddleware {
+    /// Creates a CORS middleware that doesn't allow any origin.
+    ///
+    /// Methods default to `GET`, `HEAD`, `POST`, `PUT`, `PATCH` and `DELETE`.
+    pub fn new() -> Self {
+        Self {
+            any_origin: false,
+            origins: Vec::new(),
+            methods: vec![
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ],
+            any_header: false,
+            headers: Vec::new(),
+            expose_headers: Vec::new(),
+            credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Creates a CORS middleware that allows any origin to send any header.
+    pub fn permissive() -> Self {
+        Self::new().allow_any_origin().allow_any_header()
+    }
+
+    /// Allows requests from any origin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if credentials are allowed, as any website could then make requests with the credentials of its visitors.
+    pub fn allow_any_origin(mut self) -> Self {
+        self.any_origin = true;
+        self.ensure_valid();
+        self
+    }
+
+    /// Allows requests from an origin.
+    ///
+    /// ### Arguments
+    ///
+    /// * `origin` - An exact origin, e.g. `https://example.com`,
+    ///   or an origin with a `*` wildcard matching a single subdomain, e.g. `https://*.example.com`.
+    ///   `*` alone allows any origin, like [`CorsMiddleware::allow_any_origin`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `origin` is `*` and credentials are allowed.
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        if origin == "*" {
+            return self.allow_any_origin();
+        }
+        let origin = match origin.split_once('*') {
+            Some((prefix, suffix)) => AllowedOrigin::Wildcard(prefix.into(), suffix.into()),
+            None => AllowedOrigin::Exact(origin.into()),
+        };
+        self.origins.push(origin);
+        self
+    }
+
+    /// Allows requests from the origins accepted by a predicate.
+    ///
+    /// ### Arguments
+    ///
+    /// * `predicate` - A function called with the origin and the request.
+    pub fn allow_origin_fn<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&str, &NgynRequest) -> bool + Send + Sync + 'static,
+    {
+        self.origins
+            .push(AllowedOrigin::Predicate(Arc::new(predicate)));
+        self
+    }
+
+    /// Sets the methods allowed in cross-origin requests.
+    pub fn allow_methods<I: IntoIterator<Item = Method>>(mut self, methods: I) -> Self {
+        self.methods = methods.into_iter().collect();
+        self
+    }
+
+    /// Sets the headers allowed in cross-origin requests.
+    pub fn allow_headers<I: IntoIterator<Item = HeaderName>>(mut self, headers: I) -> Self {
+        self.headers = headers.into_iter().collect();
+        self
+    }
+
+    /// Allows any header in cross-origin requests.
+    pub fn allow_any_header(mut self) -> Self {
+        self.any_header = true;
+        self
+    }
+
+    /// Sets the response headers exposed to the scripts of allowed origins.
+    pub fn expose_headers<I: IntoIterator<Item = HeaderName>>(mut self, headers: I) -> Self {
+        self.expose_headers = headers.into_iter().collect();
+        self
+    }
+
+    /// Sets whether cross-origin requests may include credentials, i.e. cookies and the `Authorization` header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `enabled` is `true` and any origin is allowed.
+    pub fn allow_credentials(mut self, enabled: bool) -> Self {
+        self.credentials = enabled;
+        self.ensure_valid();
+        self
+    }
+
+    /// Sets how long browsers may cache the result of a preflight request.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Panics if the configuration lets any origin make requests with credentials.
+    fn ensure_valid(&self) {
+        assert!(
+            !(self.any_origin && self.credentials),
+            "CorsMiddleware: credentials can't be allowed along with any origin, allow specific origins instead"
+        );
+    }
+
+    /// Returns the value of `Access-Control-Allow-Origin` for the request, if its origin is allowed.
+    fn allowed_origin(&self, req: &NgynRequest) -> Option<HeaderValue> {
+        let origin = req.headers().get(ORIGIN)?;
+        if self.any_origin {
+            return Some(HeaderValue::from_static("*"));
+        }
+
+        let value = origin.to_str().ok()?;
+        let allowed = self.origins.iter().any(|o| o.matches(value, req));
+        allowed.then(|| origin.clone())
+    }
+
+    fn is_preflight(req: &NgynRequest) -> bool {
+        req.method() == Method::OPTIONS
+            && req.headers().contains_key(ORIGIN)
+            && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+    }
+
+    /// Sets the headers shared by preflight and actual responses.
+    fn set_common_headers(&self, headers: &mut HeaderMap, origin: HeaderValue) {
+        if origin != "*" {
+            headers.append(VARY, HeaderValue::from_static("origin"));
+        }
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        if self.credentials {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+
+    fn preflight(&self, cx: &mut NgynContext<'_>) {
+        let origin = self.allowed_origin(cx.request());
+        let request_headers = cx
+            .request()
+            .headers()
+            .get(ACCESS_CONTROL_REQUEST_HEADERS)
+            .cloned();
+
+        let res = cx.response_mut();
+        *res.status_mut() = StatusCode::NO_CONTENT;
+        let headers = res.headers_mut();
+        headers.append(
+            VARY,
+            HeaderValue::from_static(
+                "access-control-request-method, access-control-request-headers",
+            ),
+        );
+
+        // disallowed origins get no CORS headers, and the browser blocks the request
+        if let Some(origin) = origin {
+            self.set_common_headers(headers, origin);
+
+            headers.insert(ACCESS_CONTROL_ALLOW_METHODS, join(&self.methods));
+            let allow_headers = if self.any_header {
+                request_headers
+            } else if self.headers.is_empty() {
+                None
+            } else {
+                Some(join(&self.headers))
+            };
+            if let Some(allow_headers) = allow_headers {
+                headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, allow_headers);
+            }
+            if let Some(max_age) = self.max_age {
+                headers.insert(ACCESS_CONTROL_MAX_AGE, max_age.as_secs().into());
+            }
+        }
+
+        cx.halt();
+    }
+}
+
+/// Joins values into a comma-separated header value.
+fn join<T: AsRef<str>>(values: &[T]) -> HeaderValue {
+    let joined = values
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(", ");
+    HeaderValue::try_from(joined).expect("methods and header names are valid header values")
+}
+
+impl Middleware for CorsMiddleware {
+    fn run<'a>(
+        &'a self,
+        cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(async move {
+            if Self::is_preflight(cx.request()) {
+                self.preflight(cx);
+            }
+        })
+    }
+
+    fn after<'a>(
+        &'a self,
+        cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(async move {
+            if Self::is_preflight(cx.request()) {
+                return;
+            }
+            let Some(origin) = self.allowed_origin(cx.request()) else {
+                return;
+            };
+
+            let headers = cx.response_mut().headers_mut();
+            self.set_common_headers(headers, origin);
+            if !self.expose_headers.is_empty() {
+                headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, join(&self.expose_headers));
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::CONTENT_TYPE;
+
+    use super::*;
+    use crate::{
+        core::engine::{read_body, MockEngine, NgynEngine},
+        core::handler::handler,
+        server::NgynResponse,
+    };
+
+    fn engine(cors: CorsMiddleware) -> MockEngine {
+        let mut engine = MockEngine::default();
+        engine.use_middleware(cors);
+        engine.any("/items", handler(|_| "items"));
+        engine
+    }
+
+    async fn send(
+        engine: &MockEngine,
+        method: Method,
+        origin: &str,
+        headers: &[(&str, &str)],
+    ) -> NgynResponse {
+        let mut headers = headers.to_vec();
+        headers.push((ORIGIN.as_str(), origin));
+        engine.send(method, "/items", &headers).await
+    }
+
+    fn header(res: &NgynResponse, name: HeaderName) -> Option<&str> {
+        res.headers().get(name).map(|value| value.to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_preflight() {
+        let engine = engine(
+            CorsMiddleware::new()
+                .allow_origin("https://example.com")
+                .allow_methods([Method::GET, Method::POST])
+                .allow_headers([CONTENT_TYPE])
+                .max_age(Duration::from_secs(600)),
+        );
+        let res = send(
+            &engine,
+            Method::OPTIONS,
+            "https://example.com",
+            &[("access-control-request-method", "POST")],
+        )
+        .await;
+
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            header(&res, ACCESS_CONTROL_ALLOW_ORIGIN),
+            Some("https://example.com")
+        );
+        assert_eq!(
+            header(&res, ACCESS_CONTROL_ALLOW_METHODS),
+            Some("GET, POST")
+        );
+        assert_eq!(
+            header(&res, ACCESS_CONTROL_ALLOW_HEADERS),
+            Some("content-type")
+        );
+        assert_eq!(header(&res, ACCESS_CONTROL_MAX_AGE), Some("600"));
+        // the route handler isn't run
+        let body = read_body(res).await;
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_preflight_disallowed_origin() {
+        let engine = engine(CorsMiddleware::new().allow_origin("https://example.com"));
+        let res = send(
+            &engine,
+            Method::OPTIONS,
+            "https://evil.com",
+            &[("access-control-request-method", "POST")],
+        )
+        .await;
+
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(header(&res, ACCESS_CONTROL_ALLOW_ORIGIN), None);
+    }
+
+    #[tokio::test]
+    async fn test_simple_request() {
+        let engine = engine(
+            CorsMiddleware::permissive().expose_headers([HeaderName::from_static("x-total")]),
+        );
+        let res = send(&engine, Method::GET, "https://example.com", &[]).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(header(&res, ACCESS_CONTROL_ALLOW_ORIGIN), Some("*"));
+        assert_eq!(header(&res, ACCESS_CONTROL_EXPOSE_HEADERS), Some("x-total"));
+        assert_eq!(header(&res, VARY), None);
+    }
+
+    #[tokio::test]
+    async fn test_credentials() {
+        let engine = engine(
+            CorsMiddleware::new()
+                .allow_origin("https://example.com")
+                .allow_any_header()
+                .allow_credentials(true),
+        );
+        let res = send(
+            &engine,
+            Method::OPTIONS,
+            "https://example.com",
+            &[
+                ("access-control-request-method", "PUT"),
+                ("access-control-request-headers", "x-token"),
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            header(&res, ACCESS_CONTROL_ALLOW_ORIGIN),
+            Some("https://example.com")
+        );
+        assert_eq!(header(&res, ACCESS_CONTROL_ALLOW_CREDENTIALS), Some("true"));
+        assert_eq!(header(&res, ACCESS_CONTROL_ALLOW_HEADERS), Some("x-token"));
+    }
+
+    #[test]
+    #[should_panic(expected = "credentials can't be allowed along with any origin")]
+    fn test_credentials_with_any_origin() {
+        let _ = CorsMiddleware::permissive().allow_credentials(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "credentials can't be allowed along with any origin")]
+    fn test_any_origin_with_credentials() {
+        let _ = CorsMiddleware::new()
+            .allow_credentials(true)
+            .allow_origin("*");
+    }
+
+    #[tokio::test]
+    async fn test_star_origin() {
+        let engine = engine(CorsMiddleware::new().allow_origin("*"));
+        let res = send(&engine, Method::GET, "https://example.com", &[]).await;
+
+        assert_eq!(header(&res, ACCESS_CONTROL_ALLOW_ORIGIN), Some("*"));
+    }
+
+    #[tokio::test]
+    async fn test_wildcard_and_predicate() {
+        let engine = engine(
+            CorsMiddleware::new()
+                .allow_origin("https://*.example.com")
+                .allow_origin_fn(|origin, _| origin.ends_with(".localhost:3000")),
+        );
+
+        for (origin, allowed) in [
+            ("https://app.example.com", true),
+            ("https://a.b.example.com", false),
+            ("https://example.com", false),
+            ("http://app.example.com", false),
+            ("http://app.localhost:3000", true),
+        ] {
+            let res = send(&engine, Method::GET, origin, &[]).await;
+            assert_eq!(
+                header(&res, ACCESS_CONTROL_ALLOW_ORIGIN).is_some(),
+                allowed,
+                "{}",
+                origin
+            );
+        }
+    }
+}
@@ -0,0 +1,500 @@
+use std::{any::type_name, convert::Infallible, future::Future, pin::Pin};
+
+use bytes::Bytes;
+use http::{header::CONTENT_TYPE, HeaderName, Method, StatusCode};
+use rand::RngCore;
+
+use crate::{
+    auth::constant_time_eq,
+    middlewares::session::Session,
+    server::{Cookie, NgynContext, SameSite, Transformer},
+    Middleware,
+};
+
+/// The session key holding the token of the synchronizer token pattern.
+const SESSION_KEY: &str = "csrf_token";
+
+/// Where a [`CsrfMiddleware`] keeps the expected token of a client.
+#[derive(Clone, Copy, PartialEq)]
+enum Storage {
+    Cookie,
+    Session,
+}
+
+/// The CSRF token of a request, to embed in the forms of a page.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// #[handler]
+/// fn edit_profile(csrf: CsrfToken) -> String {
+///     format!(
+///         "<form method=\"post\" action=\"/profile\">{}<button>Save</button></form>",
+///         csrf.hidden_input()
+///     )
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsrfToken {
+    value: String,
+    field_name: String,
+}
+
+impl CsrfToken {
+    /// The value of the token.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The name of the form field the token is read from.
+    pub fn field_name(&self) -> &str {
+        &self.field_name
+    }
+
+    /// Returns a hidden `<input>` holding the token, to add to a form.
+    pub fn hidden_input(&self) -> String {
+        format!(
+            "<input type=\"hidden\" name=\"{}\" value=\"{}\">",
+            escape(&self.field_name),
+            escape(&self.value)
+        )
+    }
+}
+
+impl std::fmt::Display for CsrfToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.value)
+    }
+}
+
+impl Transformer<'_> for CsrfToken {
+    /// Transforms the given `NgynContext` into the `CsrfToken` of the request.
+    ///
+    /// # Panics
+    /// Panics if the [`CsrfMiddleware`] hasn't been registered, or if it is exempted for the route.
+    fn transform(cx: &mut NgynContext) -> Self {
+        match cx.extensions().get::<CsrfToken>() {
+            Some(token) => token.clone(),
+            None => panic!("`{}` requires the `CsrfMiddleware`", type_name::<Self>()),
+        }
+    }
+}
+
+/// A middleware protecting forms against Cross-Site Request Forgery (CSRF).
+///
+/// Every client gets a random token, available to handlers as a [`CsrfToken`].
+/// Requests with an unsafe method (`POST`, `PUT`, `PATCH`, `DELETE`) must send it back,
+/// in a form field of an `application/x-www-form-urlencoded` or `multipart/form-data` body, or in a header,
+/// other requests get a `403 Forbidden` response.
+///
+/// By default, the token is kept in a cookie (double-submit cookie pattern),
+/// which is signed when the app state provides a cookie key.
+/// With [`CsrfMiddleware::session`], it is kept in the [`Session`] instead (synchronizer token pattern),
+/// the `SessionMiddleware` then has to be registered before this middleware.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+/// use ngyn::shared::middlewares::session::{MemoryStore, SessionMiddleware};
+///
+/// app.use_middleware(SessionMiddleware::new(MemoryStore::new()));
+/// app.use_middleware(
+///     CsrfMiddleware::new()
+///         .session()
+///         // API routes are authenticated with tokens, not cookies
+///         .exempt("/api"),
+/// );
+/// ```
+pub struct CsrfMiddleware {
+    storage: Storage,
+    cookie_name: String,
+    secure: bool,
+    field_name: String,
+    header_name: HeaderName,
+    exempt: Vec<String>,
+}
+
+impl Default for CsrfMiddleware {
+    fn default() -> Self {
+        Self {
+            storage: Storage::Cookie,
+            cookie_name: "ngyn.csrf".to_string(),
+            secure: false,
+            field_name: "_csrf".to_string(),
+            header_name: HeaderName::from_static("x-csrf-token"),
+            exempt: Vec::new(),
+        }
+    }
+}
+
+impl CsrfMiddleware {
+    /// Creates a middleware keeping tokens in a cookie.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps tokens in the session of the client instead of a cookie.
+    ///
+    /// # Panics
+    /// Requests panic if the `SessionMiddleware` isn't registered before this middleware.
+    pub fn session(mut self) -> Self {
+        self.storage = Storage::Session;
+        self
+    }
+
+    /// Sets the name of the cookie holding the token. Defaults to `ngyn.csrf`.
+    pub fn cookie_name(mut self, name: &str) -> Self {
+        self.cookie_name = name.to_string();
+        self
+    }
+
+    /// Sets the `Secure` attribute of the token cookie.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Sets the name of the form field holding the submitted token. Defaults to `_csrf`.
+    pub fn field_name(mut self, name: &str) -> Self {
+        self.field_name = name.to_string();
+        self
+    }
+
+    /// Sets the name of the header holding the submitted token. Defaults to `x-csrf-token`.
+    ///
+    /// # Panics
+    /// Panics if the name isn't a valid header name.
+    pub fn header_name(mut self, name: &str) -> Self {
+        self.header_name = HeaderName::try_from(name).expect("Invalid header name");
+        self
+    }
+
+    /// Exempts the routes under a path from CSRF protection, e.g. `/api`.
+    ///
+    /// `/` only exempts the root route, not every route.
+    pub fn exempt(mut self, path: &str) -> Self {
+        let path = match path.trim_end_matches('/') {
+            "" => "/",
+            path => path,
+        };
+        self.exempt.push(path.to_string());
+        self
+    }
+
+    fn is_exempt(&self, path: &str) -> bool {
+        self.exempt.iter().any(|exempt| {
+            path.strip_prefix(exempt.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+
+    /// Returns the expected token of the client, issuing a new one if it has none.
+    fn token(&self, cx: &mut NgynContext<'_>) -> (String, bool) {
+        match self.storage {
+            Storage::Cookie => {
+                // a signed cookie can't be planted by a sibling domain
+                let signed = cx.cookies().has_key();
+                let cookie = match signed {
+                    true => cx.cookies().signed().get(&self.cookie_name),
+                    false => cx.cookies().get(&self.cookie_name).cloned(),
+                };
+                if let Some(cookie) = cookie {
+                    return (cookie.value().to_string(), true);
+                }
+                let token = generate_token();
+                let cookie = Cookie::build((self.cookie_name.clone(), token.clone()))
+                    .path("/")
+                    .secure(self.secure)
+                    .http_only(true)
+                    .same_site(SameSite::Lax);
+                match signed {
+                    true => cx.cookies_mut().signed_mut().add(cookie),
+                    false => cx.cookies_mut().add(cookie),
+                }
+                (token, false)
+            }
+            Storage::Session => {
+                let session = Session::transform(cx);
+                if let Some(token) = session.get::<String>(SESSION_KEY) {
+                    return (token, true);
+                }
+                let token = generate_token();
+                session.set(SESSION_KEY, &token);
+                (token, false)
+            }
+        }
+    }
+
+    /// Returns the token submitted with a request, from the header or the form body.
+    async fn submitted_token(&self, cx: &NgynContext<'_>) -> Option<String> {
+        let req = cx.request();
+        if let Some(token) = req.headers().get(&self.header_name) {
+            return token.to_str().ok().map(str::to_string);
+        }
+        let content_type = req.headers().get(CONTENT_TYPE)?.to_str().ok()?;
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        match essence.as_str() {
+            "application/x-www-form-urlencoded" => url::form_urlencoded::parse(req.body())
+                .find(|(name, _)| *name == self.field_name)
+                .map(|(_, value)| value.into_owned()),
+            "multipart/form-data" => {
+                let boundary = multer::parse_boundary(content_type).ok()?;
+                let body = Bytes::copy_from_slice(req.body());
+                let stream = futures_util::stream::once(async { Ok::<_, Infallible>(body) });
+                let mut multipart = multer::Multipart::new(stream, boundary);
+                while let Ok(Some(field)) = multipart.next_field().await {
+                    if field.name() == Some(self.field_name.as_str()) {
+                        return field.text().await.ok();
+                    }
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    async fn validate(&self, cx: &mut NgynContext<'_>) {
+        if self.is_exempt(cx.request().uri().path()) {
+            return;
+        }
+        let (token, existing) = self.token(cx);
+        cx.extensions_mut().insert(CsrfToken {
+            value: token.clone(),
+            field_name: self.field_name.clone(),
+        });
+
+        if !is_unsafe(cx.request().method()) {
+            return;
+        }
+        let valid = existing
+            && self
+                .submitted_token(cx)
+                .await
+                .is_some_and(|submitted| constant_time_eq(submitted.as_bytes(), token.as_bytes()));
+        if !valid {
+            let res = cx.response_mut();
+            *res.status_mut() = StatusCode::FORBIDDEN;
+            *res.body_mut() = "Invalid CSRF token".into();
+            cx.halt();
+        }
+    }
+}
+
+impl Middleware for CsrfMiddleware {
+    fn run<'a>(
+        &'a self,
+        cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(self.validate(cx))
+    }
+}
+
+/// Checks if a method can change the state of the server.
+fn is_unsafe(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    )
+}
+
+/// Generates a random token, 256 bits long.
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Escapes a value for an HTML attribute.
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::{COOKIE, SET_COOKIE};
+
+    use super::*;
+    use crate::{
+        core::engine::{read_body, MockEngine, NgynEngine},
+        core::handler::handler,
+        middlewares::session::{MemoryStore, SessionMiddleware},
+        server::{context::AppState, cookies::Key, NgynResponse},
+    };
+
+    fn engine(middleware: CsrfMiddleware, session: bool) -> MockEngine {
+        let mut engine = MockEngine::default();
+        if session {
+            engine.use_middleware(SessionMiddleware::new(MemoryStore::new()));
+        }
+        engine.use_middleware(middleware.exempt("/api/"));
+        engine.any(
+            "/form",
+            handler(|cx| CsrfToken::transform(cx).value().to_string()),
+        );
+        engine.any("/api/posts", handler(|_| "ok"));
+        engine
+    }
+
+    async fn send(
+        engine: &MockEngine,
+        method: Method,
+        path: &str,
+        cookie: Option<&str>,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> NgynResponse {
+        let mut headers = headers.to_vec();
+        if let Some(cookie) = cookie {
+            headers.push((COOKIE.as_str(), cookie));
+        }
+        engine.send_body(method, path, &headers, body).await
+    }
+
+    /// Fetches the form, returning the cookie of the client and its token.
+    async fn token(engine: &MockEngine) -> (String, String) {
+        let res = send(engine, Method::GET, "/form", None, &[], "").await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let cookie = res.headers()[SET_COOKIE].to_str().unwrap();
+        let cookie = cookie.split(';').next().unwrap().to_string();
+        let token = read_body(res).await;
+        (cookie, String::from_utf8(token.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_double_submit_cookie() {
+        let engine = engine(CsrfMiddleware::new(), false);
+        let (cookie, token) = token(&engine).await;
+        assert_eq!(cookie, format!("ngyn.csrf={}", token));
+
+        let res = send(&engine, Method::POST, "/form", Some(&cookie), &[], "").await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let header = [("x-csrf-token", token.as_str())];
+        let res = send(&engine, Method::POST, "/form", Some(&cookie), &header, "").await;
+        assert_eq!(res.status(), StatusCode::OK);
+        // the cookie is kept
+        assert!(res.headers().get(SET_COOKIE).is_none());
+
+        let form = [("content-type", "application/x-www-form-urlencoded")];
+        let body = format!("name=John&_csrf={}", token);
+        let res = send(&engine, Method::PUT, "/form", Some(&cookie), &form, &body).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let multipart = [("content-type", "multipart/form-data; boundary=ngyn")];
+        let body = format!(
+            "--ngyn\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\nJohn\r\n\
+             --ngyn\r\nContent-Disposition: form-data; name=\"_csrf\"\r\n\r\n{}\r\n--ngyn--\r\n",
+            token
+        );
+        let res = send(
+            &engine,
+            Method::POST,
+            "/form",
+            Some(&cookie),
+            &multipart,
+            &body,
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = send(
+            &engine,
+            Method::DELETE,
+            "/form",
+            Some(&cookie),
+            &form,
+            "_csrf=x",
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        // a token without the cookie is rejected
+        let res = send(&engine, Method::POST, "/form", None, &header, "").await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_signed_cookie() {
+        struct Keys(Key);
+        impl AppState for Keys {
+            fn cookie_key(&self) -> Option<&Key> {
+                Some(&self.0)
+            }
+        }
+
+        let mut engine = engine(CsrfMiddleware::new(), false);
+        engine.add_state(Keys(Key::generate()));
+        let (cookie, token) = token(&engine).await;
+        let planted = format!("ngyn.csrf={}", token);
+        assert_ne!(cookie, planted);
+
+        let header = [("x-csrf-token", token.as_str())];
+        let res = send(&engine, Method::POST, "/form", Some(&cookie), &header, "").await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // an unsigned cookie, e.g. set by a sibling domain, is rejected
+        let res = send(&engine, Method::POST, "/form", Some(&planted), &header, "").await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_synchronizer_token() {
+        let engine = engine(CsrfMiddleware::new().session(), true);
+        let (cookie, token) = token(&engine).await;
+        assert!(cookie.starts_with("ngyn.sid="));
+
+        let header = [("x-csrf-token", token.as_str())];
+        let res = send(&engine, Method::PATCH, "/form", Some(&cookie), &header, "").await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = send(&engine, Method::PATCH, "/form", None, &header, "").await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_exempt() {
+        let engine = engine(CsrfMiddleware::new(), false);
+        let res = send(&engine, Method::POST, "/api/posts", None, &[], "").await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().get(SET_COOKIE).is_none());
+
+        let middleware = CsrfMiddleware::new().exempt("/api");
+        assert!(middleware.is_exempt("/api"));
+        assert!(!middleware.is_exempt("/apis"));
+    }
+
+    #[test]
+    fn test_exempt_root() {
+        let middleware = CsrfMiddleware::new().exempt("/");
+        assert!(middleware.is_exempt("/"));
+        assert!(!middleware.is_exempt("/posts"));
+
+        let middleware = CsrfMiddleware::new().exempt("/api/");
+        assert!(middleware.is_exempt("/api/posts"));
+        assert!(!middleware.is_exempt("/"));
+    }
+
+    #[test]
+    fn test_hidden_input() {
+        let token = CsrfToken {
+            value: "abc".to_string(),
+            field_name: "\"_csrf".to_string(),
+        };
+        assert_eq!(
+            token.hidden_input(),
+            "<input type=\"hidden\" name=\"&quot;_csrf\" value=\"abc\">"
+        );
+    }
+}
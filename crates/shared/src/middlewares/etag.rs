@@ -0,0 +1,480 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use http::{
+    header::{
+        CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+        IF_UNMODIFIED_SINCE, LAST_MODIFIED,
+    },
+    HeaderMap, HeaderValue, Method, StatusCode,
+};
+
+use crate::{
+    server::{
+        response::{ReadBytes, ResponseStream},
+        NgynContext, Transformer,
+    },
+    Middleware,
+};
+
+/// The headers of a request that make it conditional.
+const CONDITIONAL_HEADERS: [http::HeaderName; 4] = [
+    IF_MATCH,
+    IF_NONE_MATCH,
+    IF_MODIFIED_SINCE,
+    IF_UNMODIFIED_SINCE,
+];
+
+/// Hashes bytes with FNV-1a, the hashes are used in the `ETag`s generated by ngyn.
+pub fn hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Evaluates the preconditions of a request against the current validators of a resource.
+///
+/// Preconditions are evaluated in the order of RFC 9110: `If-Match`, `If-Unmodified-Since`,
+/// `If-None-Match`, then `If-Modified-Since`. A resource without validators is considered missing.
+///
+/// ### Arguments
+///
+/// * `method` - The method of the request.
+/// * `headers` - The headers of the request.
+/// * `etag` - The current `ETag` of the resource.
+/// * `last_modified` - The last modification date of the resource.
+///
+/// ### Returns
+///
+/// `304 Not Modified` or `412 Precondition Failed` if the request shouldn't proceed.
+pub fn evaluate_preconditions(
+    method: &Method,
+    headers: &HeaderMap,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> Option<StatusCode> {
+    let exists = etag.is_some() || last_modified.is_some();
+    let is_safe = method == Method::GET || method == Method::HEAD;
+
+    if let Some(if_match) = header(headers, &IF_MATCH) {
+        // `If-Match` uses the strong comparison
+        let matches = match etag {
+            Some(etag) if !is_weak(etag) => {
+                tags(if_match).any(|tag| tag == "*" || (!is_weak(tag) && tag == etag))
+            }
+            _ => exists && tags(if_match).any(|tag| tag == "*"),
+        };
+        if !matches {
+            return Some(StatusCode::PRECONDITION_FAILED);
+        }
+    } else if let (Some(since), Some(last_modified)) =
+        (date(headers, &IF_UNMODIFIED_SINCE), last_modified)
+    {
+        if secs(last_modified) > secs(since) {
+            return Some(StatusCode::PRECONDITION_FAILED);
+        }
+    }
+
+    if let Some(if_none_match) = header(headers, &IF_NONE_MATCH) {
+        // `If-None-Match` uses the weak comparison
+        let matches = tags(if_none_match).any(|tag| {
+            (tag == "*" && exists) || etag.is_some_and(|etag| strip_weak(tag) == strip_weak(etag))
+        });
+        if matches {
+            return Some(match is_safe {
+                true => StatusCode::NOT_MODIFIED,
+                false => StatusCode::PRECONDITION_FAILED,
+            });
+        }
+    } else if let (true, Some(since), Some(last_modified)) =
+        (is_safe, date(headers, &IF_MODIFIED_SINCE), last_modified)
+    {
+        if secs(last_modified) <= secs(since) {
+            return Some(StatusCode::NOT_MODIFIED);
+        }
+    }
+
+    None
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &http::HeaderName) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn date(headers: &HeaderMap, name: &http::HeaderName) -> Option<SystemTime> {
+    header(headers, name).and_then(|value| httpdate::parse_http_date(value).ok())
+}
+
+fn tags(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+}
+
+fn is_weak(tag: &str) -> bool {
+    tag.starts_with("W/")
+}
+
+fn strip_weak(tag: &str) -> &str {
+    tag.trim_start_matches("W/")
+}
+
+/// Dates in headers have a precision of a second.
+fn secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// The preconditions of a request, checked by handlers against the current state of a resource.
+///
+/// This enables optimistic concurrency: a client sends the `ETag` it last saw in `If-Match`,
+/// and the update is rejected with `412 Precondition Failed` if the resource changed since.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// #[handler]
+/// async fn update_article(preconditions: Preconditions, res: &mut NgynResponse) -> String {
+///     let article = Article::find(1).await;
+///     if let Err(status) = preconditions.check(Some(&article.etag()), Some(article.updated_at)) {
+///         *res.status_mut() = status;
+///         return String::new();
+///     }
+///     article.update().await
+/// }
+/// ```
+pub struct Preconditions {
+    method: Method,
+    headers: HeaderMap,
+}
+
+impl Preconditions {
+    /// Checks the preconditions against the current validators of the resource, see [`evaluate_preconditions`].
+    ///
+    /// ### Arguments
+    ///
+    /// * `etag` - The current `ETag` of the resource, including its quotes.
+    /// * `last_modified` - The last modification date of the resource.
+    ///
+    /// ### Returns
+    ///
+    /// The status to respond with if the request shouldn't proceed.
+    pub fn check(
+        &self,
+        etag: Option<&str>,
+        last_modified: Option<SystemTime>,
+    ) -> Result<(), StatusCode> {
+        match evaluate_preconditions(&self.method, &self.headers, etag, last_modified) {
+            Some(status) => Err(status),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true if the request has any precondition.
+    pub fn is_conditional(&self) -> bool {
+        !self.headers.is_empty()
+    }
+}
+
+impl Transformer<'_> for Preconditions {
+    fn transform(cx: &mut NgynContext) -> Self {
+        let request = cx.request();
+        let mut headers = HeaderMap::new();
+        for name in CONDITIONAL_HEADERS {
+            for value in request.headers().get_all(&name) {
+                headers.append(name.clone(), value.clone());
+            }
+        }
+
+        Preconditions {
+            method: request.method().clone(),
+            headers,
+        }
+    }
+}
+
+/// A middleware that adds `ETag`s to responses, and answers conditional requests.
+///
+/// Successful `GET` responses without an `ETag` get one from a hash of their body,
+/// `HEAD` responses have no body to hash, so only an `ETag` set by their handler is used.
+/// The preconditions of both are then evaluated against the `ETag` and `Last-Modified` headers of the response,
+/// and the response is replaced with `304 Not Modified` or `412 Precondition Failed` if they don't pass.
+///
+/// Requests with other methods change the resource, so their handlers check preconditions with [`Preconditions`].
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// app.use_middleware(ETagMiddleware::new());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ETagMiddleware {
+    weak: bool,
+}
+
+impl ETagMiddleware {
+    /// Creates a middleware generating strong `ETag`s.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generates weak `ETag`s, for responses that are equivalent but not byte-for-byte identical,
+    /// e.g. when they are compressed afterwards.
+    pub fn weak(mut self, weak: bool) -> Self {
+        self.weak = weak;
+        self
+    }
+
+    async fn evaluate(&self, cx: &mut NgynContext<'_>) {
+        let method = cx.request().method().clone();
+        if method != Method::GET && method != Method::HEAD {
+            return;
+        }
+        let request = cx.request().headers().clone();
+
+        let res = cx.response_mut();
+        // preconditions only apply to successful responses
+        if res.status() != StatusCode::OK {
+            return;
+        }
+
+        // the body of HEAD responses has been stripped, and streamed bodies aren't read into memory to be hashed
+        if method == Method::GET
+            && !res.headers().contains_key(ETAG)
+            && !ResponseStream::is_set(res)
+        {
+            let body = res.read_bytes().await.unwrap_or_default();
+            let etag = match self.weak {
+                true => format!("W/\"{}\"", hash(&body)),
+                false => format!("\"{}\"", hash(&body)),
+            };
+            res.headers_mut().insert(
+                ETAG,
+                HeaderValue::try_from(etag).expect("hex digits are valid header values"),
+            );
+            *res.body_mut() = body.into();
+        }
+
+        let headers = res.headers();
+        let etag = headers.get(ETAG).and_then(|value| value.to_str().ok());
+        let last_modified = headers
+            .get(LAST_MODIFIED)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| httpdate::parse_http_date(value).ok());
+
+        if let Some(status) = evaluate_preconditions(&method, &request, etag, last_modified) {
+            *res.status_mut() = status;
+            *res.body_mut() = Default::default();
+            ResponseStream::take(res);
+            let headers = res.headers_mut();
+            headers.remove(CONTENT_LENGTH);
+            headers.remove(CONTENT_TYPE);
+        }
+    }
+}
+
+impl Middleware for ETagMiddleware {
+    fn run<'a>(
+        &'a self,
+        _cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(async {})
+    }
+
+    fn after<'a>(
+        &'a self,
+        cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(self.evaluate(cx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{
+            engine::{read_body, MockEngine, NgynEngine, NgynHttpEngine},
+            handler::handler,
+        },
+        server::NgynResponse,
+    };
+
+    async fn send(engine: &MockEngine, method: Method, headers: &[(&str, &str)]) -> NgynResponse {
+        engine.send(method, "/article", headers).await
+    }
+
+    fn headers(headers: &[(&'static str, &str)]) -> HeaderMap {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    http::HeaderName::from_static(name),
+                    HeaderValue::from_str(value).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_evaluate_preconditions() {
+        let modified = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").ok();
+        let evaluate = |method: Method, pairs: &[(&'static str, &str)]| {
+            evaluate_preconditions(&method, &headers(pairs), Some("\"v2\""), modified)
+        };
+
+        assert_eq!(
+            evaluate(Method::GET, &[("if-none-match", "W/\"v2\"")]),
+            Some(StatusCode::NOT_MODIFIED)
+        );
+        assert_eq!(
+            evaluate(Method::GET, &[("if-none-match", "\"v1\", \"v3\"")]),
+            None
+        );
+        assert_eq!(
+            evaluate(Method::PUT, &[("if-none-match", "*")]),
+            Some(StatusCode::PRECONDITION_FAILED)
+        );
+        assert_eq!(evaluate(Method::PUT, &[("if-match", "\"v2\"")]), None);
+        assert_eq!(
+            evaluate(Method::PUT, &[("if-match", "W/\"v2\"")]),
+            Some(StatusCode::PRECONDITION_FAILED)
+        );
+        assert_eq!(
+            evaluate(Method::PUT, &[("if-match", "\"v1\"")]),
+            Some(StatusCode::PRECONDITION_FAILED)
+        );
+        assert_eq!(
+            evaluate(
+                Method::GET,
+                &[("if-modified-since", "Wed, 21 Oct 2015 07:28:00 GMT")]
+            ),
+            Some(StatusCode::NOT_MODIFIED)
+        );
+        assert_eq!(
+            evaluate(
+                Method::GET,
+                &[("if-modified-since", "Tue, 20 Oct 2015 07:28:00 GMT")]
+            ),
+            None
+        );
+        // `If-None-Match` takes precedence over `If-Modified-Since`
+        assert_eq!(
+            evaluate(
+                Method::GET,
+                &[
+                    ("if-none-match", "\"v1\""),
+                    ("if-modified-since", "Wed, 21 Oct 2015 07:28:00 GMT")
+                ]
+            ),
+            None
+        );
+        assert_eq!(
+            evaluate(
+                Method::DELETE,
+                &[("if-unmodified-since", "Tue, 20 Oct 2015 07:28:00 GMT")]
+            ),
+            Some(StatusCode::PRECONDITION_FAILED)
+        );
+
+        // a missing resource doesn't match `*`
+        let missing =
+            evaluate_preconditions(&Method::PUT, &headers(&[("if-match", "*")]), None, None);
+        assert_eq!(missing, Some(StatusCode::PRECONDITION_FAILED));
+        let missing = evaluate_preconditions(
+            &Method::PUT,
+            &headers(&[("if-none-match", "*")]),
+            None,
+            None,
+        );
+        assert_eq!(missing, None);
+    }
+
+    #[tokio::test]
+    async fn test_etag_middleware() {
+        let mut engine = MockEngine::default();
+        engine.use_middleware(ETagMiddleware::new());
+        engine.any("/article", handler(|_| "ngyn"));
+
+        let res = send(&engine, Method::GET, &[]).await;
+        let etag = res.headers()[ETAG].to_str().unwrap().to_string();
+        assert_eq!(etag, format!("\"{}\"", hash(b"ngyn")));
+        assert_eq!(read_body(res).await, "ngyn");
+
+        let res = send(&engine, Method::GET, &[("if-none-match", &etag)]).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()[ETAG], etag.as_str());
+        assert!(read_body(res).await.is_empty());
+
+        let res = send(&engine, Method::GET, &[("if-match", "\"stale\"")]).await;
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+
+        // other methods are left to their handlers
+        let res = send(&engine, Method::POST, &[("if-none-match", &etag)]).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!res.headers().contains_key(ETAG));
+    }
+
+    #[tokio::test]
+    async fn test_etag_head() {
+        let mut engine = MockEngine::default();
+        engine.use_middleware(ETagMiddleware::new());
+        engine.head(
+            "/article",
+            handler(|cx| {
+                let headers = cx.response_mut().headers_mut();
+                headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+                "ngyn"
+            }),
+        );
+        engine.head("/draft", handler(|_| "ngyn"));
+
+        // HEAD responses don't get the `ETag` of their stripped body
+        let res = engine.send(Method::HEAD, "/draft", &[]).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!res.headers().contains_key(ETAG));
+
+        let res = send(&engine, Method::HEAD, &[("if-none-match", "\"v1\"")]).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()[ETAG], "\"v1\"");
+
+        let res = send(&engine, Method::HEAD, &[]).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[ETAG], "\"v1\"");
+    }
+
+    #[tokio::test]
+    async fn test_preconditions() {
+        let mut engine = MockEngine::default();
+        engine.any(
+            "/article",
+            handler(|cx| {
+                let preconditions = Preconditions::transform(cx);
+                match preconditions.check(Some("\"v2\""), None) {
+                    Ok(()) => "updated",
+                    Err(status) => {
+                        *cx.response_mut().status_mut() = status;
+                        ""
+                    }
+                }
+            }),
+        );
+
+        let res = send(&engine, Method::PUT, &[("if-match", "\"v1\"")]).await;
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+
+        let res = send(&engine, Method::PUT, &[("if-match", "\"v2\"")]).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(read_body(res).await, "updated");
+    }
+}
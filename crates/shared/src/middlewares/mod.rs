@@ -0,0 +1,8 @@
+pub mod cache;
+pub mod compression;
+pub mod cors;
+pub mod csrf;
+pub mod etag;
+pub mod rate_limit;
+pub mod session;
+pub mod static_files;
@@ -0,0 +1,600 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use http::{header::RETRY_AFTER, HeaderName, HeaderValue, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    server::{ConnectInfo, NgynContext},
+    Middleware,
+};
+
+const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+const RATELIMIT_POLICY: HeaderName = HeaderName::from_static("ratelimit-policy");
+
+/// The algorithm a [`RateLimiter`] counts requests with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    /// A bucket of `capacity` tokens, refilled over `period`.
+    ///
+    /// Each request takes a token, allowing bursts of up to `capacity` requests.
+    TokenBucket { capacity: u32, period: Duration },
+    /// Up to `limit` requests in any `window`.
+    ///
+    /// The count of the previous window is weighted by its overlap with the sliding window.
+    SlidingWindow { limit: u32, window: Duration },
+}
+
+/// The state of a rate limited key, as kept by a [`RateLimitStore`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitState {
+    /// The tokens left in the bucket, or the count of the current window.
+    value: f64,
+    /// The count of the previous window.
+    previous: f64,
+    /// When the bucket was last refilled, or when the current window started, in seconds since the epoch.
+    at: f64,
+}
+
+/// The outcome of a request counted by a [`RateLimiter`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimitDecision {
+    /// Whether the request is allowed.
+    pub allowed: bool,
+    /// The number of requests allowed by the quota.
+    pub limit: u32,
+    /// The number of requests left in the quota.
+    pub remaining: u32,
+    /// How long until the quota is fully available again.
+    pub reset: Duration,
+    /// How long until a request is allowed again, zero when the request is allowed.
+    pub retry_after: Duration,
+}
+
+impl Algorithm {
+    /// The number of requests allowed by the quota.
+    pub fn limit(&self) -> u32 {
+        match *self {
+            Algorithm::TokenBucket { capacity, .. } => capacity,
+            Algorithm::SlidingWindow { limit, .. } => limit,
+        }
+    }
+
+    /// The period the quota applies to.
+    pub fn window(&self) -> Duration {
+        match *self {
+            Algorithm::TokenBucket { period, .. } => period,
+            Algorithm::SlidingWindow { window, .. } => window,
+        }
+    }
+
+    /// Counts a request against the state of a key.
+    ///
+    /// Stores call it with the state they keep for the key, or `None` for a new key,
+    /// and keep the returned state for the next request.
+    ///
+    /// ### Arguments
+    ///
+    /// * `state` - The state of the key.
+    /// * `now` - The time of the request.
+    ///
+    /// ### Returns
+    ///
+    /// The new state of the key and the decision for the request.
+    pub fn acquire(
+        &self,
+        state: Option<RateLimitState>,
+        now: SystemTime,
+    ) -> (RateLimitState, RateLimitDecision) {
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let limit = self.limit();
+        let window = self.window().as_secs_f64().max(f64::EPSILON);
+        let limit_f = f64::from(limit);
+
+        match *self {
+            Algorithm::TokenBucket { .. } => {
+                let rate = limit_f / window;
+                let tokens = match state {
+                    Some(state) => (state.value + (now - state.at).max(0.0) * rate).min(limit_f),
+                    None => limit_f,
+                };
+
+                let allowed = tokens >= 1.0;
+                let tokens = if allowed { tokens - 1.0 } else { tokens };
+                let decision = RateLimitDecision {
+                    allowed,
+                    limit,
+                    remaining: tokens.floor() as u32,
+                    reset: seconds((limit_f - tokens) / rate),
+                    retry_after: match allowed {
+                        true => Duration::ZERO,
+                        false => seconds((1.0 - tokens) / rate),
+                    },
+                };
+                let state = RateLimitState {
+                    value: tokens,
+                    previous: 0.0,
+                    at: now,
+                };
+                (state, decision)
+            }
+            Algorithm::SlidingWindow { .. } => {
+                let start = (now / window).floor() * window;
+                let (current, previous) = match state {
+                    Some(state) if state.at == start => (state.value, state.previous),
+                    Some(state) if state.at == start - window => (0.0, state.value),
+                    _ => (0.0, 0.0),
+                };
+
+                let elapsed = now - start;
+                let count = previous * (1.0 - elapsed / window) + current;
+                let allowed = count + 1.0 <= limit_f;
+                let current = if allowed { current + 1.0 } else { current };
+                let count = if allowed { count + 1.0 } else { count };
+
+                // the time until the weighted count leaves room for a request
+                let retry_after = if allowed {
+                    0.0
+                } else if current + 1.0 > limit_f {
+                    // not before the next window, where the current count becomes the previous one
+                    let next = window - elapsed;
+                    next + window * (1.0 - (limit_f - 1.0) / current.max(1.0))
+                } else {
+                    window * (1.0 - (limit_f - 1.0 - current) / previous.max(1.0)) - elapsed
+                };
+                let decision = RateLimitDecision {
+                    allowed,
+                    limit,
+                    remaining: (limit_f - count).max(0.0).floor() as u32,
+                    reset: seconds(window - elapsed),
+                    retry_after: seconds(retry_after),
+                };
+                let state = RateLimitState {
+                    value: current,
+                    previous,
+                    at: start,
+                };
+                (state, decision)
+            }
+        }
+    }
+
+    /// Whether the state of a key is back to a full quota, i.e. it can be forgotten.
+    fn is_idle(&self, state: &RateLimitState, now: SystemTime) -> bool {
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let window = self.window().as_secs_f64();
+        match self {
+            Algorithm::TokenBucket { .. } => now - state.at >= window,
+            Algorithm::SlidingWindow { .. } => now - state.at >= 2.0 * window,
+        }
+    }
+}
+
+fn seconds(secs: f64) -> Duration {
+    // a quota of zero is never available again, i.e. an infinite wait
+    Duration::try_from_secs_f64(secs.max(0.0)).unwrap_or(Duration::MAX)
+}
+
+/// Storage of the state of rate limited keys.
+///
+/// Implement it to share limits between instances of an application, e.g. in Redis.
+/// [`Algorithm::acquire`] computes the new state of a key, it should be called atomically for a key.
+pub trait RateLimitStore: Send + Sync {
+    /// Counts a request of a key.
+    ///
+    /// ### Arguments
+    ///
+    /// * `key` - The key of the request.
+    /// * `algorithm` - The algorithm of the rate limiter.
+    ///
+    /// ### Returns
+    ///
+    /// The decision for the request.
+    fn acquire<'a>(
+        &'a self,
+        key: &'a str,
+        algorithm: &'a Algorithm,
+    ) -> Pin<Box<dyn Future<Output = RateLimitDecision> + Send + 'a>>;
+}
+
+/// The in-memory [`RateLimitStore`] of rate limiters, the state of keys is forgotten once their quota is full again.
+#[derive(Default)]
+pub struct MemoryStore {
+    states: Mutex<HashMap<String, RateLimitState>>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, RateLimitState>> {
+        self.states.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl RateLimitStore for MemoryStore {
+    fn acquire<'a>(
+        &'a self,
+        key: &'a str,
+        algorithm: &'a Algorithm,
+    ) -> Pin<Box<dyn Future<Output = RateLimitDecision> + Send + 'a>> {
+        Box::pin(async move {
+            let now = SystemTime::now();
+            let mut states = self.lock();
+            let (state, decision) = algorithm.acquire(states.get(key).copied(), now);
+
+            // forget idle keys once the store grows, amortized over the insertions
+            if !states.contains_key(key) && states.len() >= 1024 && states.len().is_power_of_two() {
+                states.retain(|_, state| !algorithm.is_idle(state, now));
+            }
+            states.insert(key.to_string(), state);
+            decision
+        })
+    }
+}
+
+/// Computes the key of a request.
+type KeyFn = dyn Fn(&NgynContext<'_>) -> Option<String> + Send + Sync;
+
+/// What requests are counted by.
+#[derive(Clone)]
+pub enum RateLimitKey {
+    /// The IP address of the client, see [`ConnectInfo::client_ip`].
+    ClientIp,
+    /// The value of a request header, e.g. an API key.
+    Header(HeaderName),
+    /// A key computed from the request, e.g. the id of the authenticated user.
+    Custom(Arc<KeyFn>),
+}
+
+impl RateLimitKey {
+    /// Counts requests by a key computed from the request.
+    ///
+    /// Requests without a key aren't limited.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// let key = RateLimitKey::custom(|cx| cx.extensions().get::<User>().map(|user| user.id.to_string()));
+    /// ```
+    pub fn custom<F: Fn(&NgynContext<'_>) -> Option<String> + Send + Sync + 'static>(f: F) -> Self {
+        RateLimitKey::Custom(Arc::new(f))
+    }
+
+    fn resolve(&self, cx: &NgynContext<'_>) -> Option<String> {
+        match self {
+            RateLimitKey::ClientIp => cx
+                .request()
+                .extensions()
+                .get::<ConnectInfo>()
+                .and_then(|info| info.client_ip())
+                .map(|ip| ip.to_string()),
+            RateLimitKey::Header(name) => cx
+                .request()
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string()),
+            RateLimitKey::Custom(f) => f(cx),
+        }
+    }
+}
+
+impl fmt::Debug for RateLimitKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateLimitKey::ClientIp => f.write_str("ClientIp"),
+            RateLimitKey::Header(name) => f.debug_tuple("Header").field(name).finish(),
+            RateLimitKey::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// Limits the rate of requests of clients.
+///
+/// Requests over the limit get a `429 Too Many Requests` response with a `Retry-After` header,
+/// and every counted response has the `RateLimit-Limit`, `RateLimit-Remaining`, `RateLimit-Reset` and `RateLimit-Policy` headers.
+/// Requests are counted by client IP by default, requests without a key aren't limited.
+///
+/// As a middleware, a rate limiter limits every request of the application.
+/// For per-route limits, use it in a gate, it is cheap to clone and can be kept in the state of the application.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// app.use_middleware(RateLimiter::sliding_window(100, Duration::from_secs(60)));
+///
+/// #[derive(AppState)]
+/// struct Limits {
+///     login: RateLimiter,
+/// }
+///
+/// struct LoginLimit;
+///
+/// impl NgynGate for LoginLimit {
+///     async fn can_activate(cx: &mut NgynContext<'_>) -> bool {
+///         let limiter = cx.state::<Limits>().unwrap().login.clone();
+///         limiter.check(cx).await
+///     }
+/// }
+///
+/// #[handler(gates = [LoginLimit])]
+/// async fn login(body: Body) -> String { ... }
+/// ```
+#[derive(Clone)]
+pub struct RateLimiter {
+    algorithm: Algorithm,
+    key: RateLimitKey,
+    prefix: String,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("algorithm", &self.algorithm)
+            .field("key", &self.key)
+            .field("prefix", &self.prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter with an in-memory store.
+    pub fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            key: RateLimitKey::ClientIp,
+            prefix: String::new(),
+            store: Arc::new(MemoryStore::new()),
+        }
+    }
+
+    /// Creates a token bucket rate limiter, allowing bursts of `capacity` requests and `capacity` requests per `period`.
+    pub fn token_bucket(capacity: u32, period: Duration) -> Self {
+        Self::new(Algorithm::TokenBucket { capacity, period })
+    }
+
+    /// Creates a sliding window rate limiter, allowing `limit` requests in any `window`.
+    pub fn sliding_window(limit: u32, window: Duration) -> Self {
+        Self::new(Algorithm::SlidingWindow { limit, window })
+    }
+
+    /// Sets what requests are counted by.
+    pub fn key(mut self, key: RateLimitKey) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Sets the store of the rate limiter.
+    pub fn store<S: RateLimitStore + 'static>(mut self, store: S) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+
+    /// Sets a prefix of the keys, to share a store between rate limiters.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Counts a request, setting a `429 Too Many Requests` response when it is over the limit.
+    ///
+    /// ### Arguments
+    ///
+    /// * `cx` - The request context.
+    ///
+    /// ### Returns
+    ///
+    /// Returns `true` if the request is allowed, `false` otherwise.
+    pub async fn check(&self, cx: &mut NgynContext<'_>) -> bool {
+        let Some(key) = self.key.resolve(cx) else {
+            return true;
+        };
+        let key = format!("{}{}", self.prefix, key);
+        let decision = self.store.acquire(&key, &self.algorithm).await;
+
+        let res = cx.response_mut();
+        let headers = res.headers_mut();
+        headers.insert(RATELIMIT_LIMIT, decision.limit.into());
+        headers.insert(RATELIMIT_REMAINING, decision.remaining.into());
+        headers.insert(RATELIMIT_RESET, ceil_secs(decision.reset).into());
+        if let Ok(policy) = HeaderValue::from_str(&format!(
+            "{};w={}",
+            decision.limit,
+            ceil_secs(self.algorithm.window())
+        )) {
+            headers.insert(RATELIMIT_POLICY, policy);
+        }
+
+        if !decision.allowed {
+            headers.insert(RETRY_AFTER, ceil_secs(decision.retry_after).max(1).into());
+            *res.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+            *res.body_mut() = "Too Many Requests".into();
+        }
+        decision.allowed
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration
+        .as_secs()
+        .saturating_add(u64::from(duration.subsec_nanos() > 0))
+}
+
+impl Middleware for RateLimiter {
+    fn run<'a>(
+        &'a self,
+        cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(async move {
+            if !self.check(cx).await {
+                cx.halt();
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Request;
+
+    use super::*;
+    use crate::{
+        core::{
+            engine::{MockEngine, NgynEngine},
+            handler::handler,
+        },
+        server::NgynResponse,
+    };
+
+    fn at(secs: f64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs_f64(1_000_000.0 + secs)
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let algorithm = Algorithm::TokenBucket {
+            capacity: 2,
+            period: Duration::from_secs(10),
+        };
+
+        let (state, decision) = algorithm.acquire(None, at(0.0));
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 1);
+        let (state, decision) = algorithm.acquire(Some(state), at(0.0));
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(decision.reset, Duration::from_secs(10));
+
+        let (state, decision) = algorithm.acquire(Some(state), at(1.0));
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, Duration::from_secs(4));
+
+        // a token is back after 5 seconds
+        let (_, decision) = algorithm.acquire(Some(state), at(5.0));
+        assert!(decision.allowed);
+    }
+
+    #[test]
+    fn test_zero_quota() {
+        for algorithm in [
+            Algorithm::TokenBucket {
+                capacity: 0,
+                period: Duration::from_secs(10),
+            },
+            Algorithm::SlidingWindow {
+                limit: 0,
+                window: Duration::from_secs(10),
+            },
+        ] {
+            let (_, decision) = algorithm.acquire(None, at(0.0));
+            assert!(!decision.allowed);
+            assert_eq!(decision.remaining, 0);
+            assert!(decision.retry_after > Duration::ZERO);
+        }
+        assert_eq!(ceil_secs(Duration::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        let algorithm = Algorithm::SlidingWindow {
+            limit: 2,
+            window: Duration::from_secs(10),
+        };
+
+        let (state, _) = algorithm.acquire(None, at(5.0));
+        let (state, decision) = algorithm.acquire(Some(state), at(6.0));
+        assert!(decision.allowed);
+        assert_eq!(decision.reset, Duration::from_secs(4));
+
+        let (state, decision) = algorithm.acquire(Some(state), at(7.0));
+        assert!(!decision.allowed);
+        // in the next window, the previous count weighs 2 * (1 - 5 / 10) = 1
+        assert_eq!(decision.retry_after, Duration::from_secs(8));
+
+        let (state, decision) = algorithm.acquire(Some(state), at(12.0));
+        assert!(!decision.allowed);
+        let (state, decision) = algorithm.acquire(Some(state), at(15.0));
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+
+        // the counts are forgotten after two windows
+        let (_, decision) = algorithm.acquire(Some(state), at(30.0));
+        assert_eq!(decision.remaining, 1);
+    }
+
+    async fn send(engine: &MockEngine, ip: &str, api_key: Option<&str>) -> NgynResponse {
+        let mut req = Request::builder().uri("/");
+        if let Some(api_key) = api_key {
+            req = req.header("x-api-key", api_key);
+        }
+        let mut req = req.body(Vec::new()).unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo::new().with_peer_addr(format!("{}:443", ip).parse().unwrap()));
+        engine.data.respond(req).await
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter() {
+        let mut engine = MockEngine::default();
+        engine.use_middleware(RateLimiter::sliding_window(2, Duration::from_secs(60)));
+        engine.any("/", handler(|_| "ok"));
+
+        let res = send(&engine, "10.0.0.1", None).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[RATELIMIT_LIMIT], "2");
+        assert_eq!(res.headers()[RATELIMIT_REMAINING], "1");
+        assert_eq!(res.headers()[RATELIMIT_POLICY], "2;w=60");
+        send(&engine, "10.0.0.1", None).await;
+
+        let res = send(&engine, "10.0.0.1", None).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()[RATELIMIT_REMAINING], "0");
+        assert!(res.headers().contains_key(RETRY_AFTER));
+
+        // other clients have their own quota
+        let res = send(&engine, "10.0.0.2", None).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_key() {
+        let mut engine = MockEngine::default();
+        engine.use_middleware(
+            RateLimiter::token_bucket(1, Duration::from_secs(60))
+                .key(RateLimitKey::Header(HeaderName::from_static("x-api-key"))),
+        );
+        engine.any("/", handler(|_| "ok"));
+
+        let res = send(&engine, "10.0.0.1", Some("a")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = send(&engine, "10.0.0.2", Some("a")).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        let res = send(&engine, "10.0.0.1", Some("b")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // requests without a key aren't limited
+        let res = send(&engine, "10.0.0.1", None).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!res.headers().contains_key(RATELIMIT_LIMIT));
+    }
+}
@@ -0,0 +1,481 @@
+mod store;
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use http::StatusCode;
+use rand::RngCore;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+pub use store::{FileStore, MemoryStore, SessionRecord, SessionStore};
+
+use crate::{
+    server::{cookies::time, Cookie, NgynContext, SameSite, Transformer},
+    Middleware,
+};
+
+/// Configure a [`SessionMiddleware`]
+pub struct SessionConfig {
+    /// The name of the cookie holding the session id. Defaults to `ngyn.sid`.
+    pub cookie_name: String,
+    /// The path of the session cookie. Defaults to `/`.
+    pub path: String,
+    /// The domain of the session cookie.
+    pub domain: Option<String>,
+    /// Sets the `Secure` attribute of the session cookie.
+    pub secure: bool,
+    /// Sets the `HttpOnly` attribute of the session cookie. Defaults to `true`.
+    pub http_only: bool,
+    /// The `SameSite` attribute of the session cookie. Defaults to `SameSite::Lax`.
+    pub same_site: SameSite,
+    /// How long a session lives after it was last saved. Defaults to 24 hours.
+    pub max_age: Duration,
+    /// Extends the expiry of existing sessions on every request, not only when they change.
+    pub rolling: bool,
+    /// Signs the session cookie with the cookie key of the app state.
+    pub signed: bool,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            cookie_name: "ngyn.sid".to_string(),
+            path: "/".to_string(),
+            domain: None,
+            secure: false,
+            http_only: true,
+            same_site: SameSite::Lax,
+            max_age: Duration::from_secs(60 * 60 * 24),
+            rolling: false,
+            signed: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SessionStatus {
+    Unchanged,
+    Changed,
+    Rotated,
+    Destroyed,
+}
+
+struct SessionInner {
+    id: Option<String>,
+    previous_id: Option<String>,
+    data: HashMap<String, Value>,
+    status: SessionStatus,
+}
+
+/// Represents the session of a request.
+///
+/// Sessions are loaded by the [`SessionMiddleware`], changes made to a session are saved once the request has been handled.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// #[handler]
+/// fn login(session: Session) -> &'static str {
+///     // prevents session fixation
+///     session.rotate();
+///     session.set("user_id", 42);
+///     "Logged in"
+/// }
+///
+/// #[handler]
+/// fn profile(session: Session) -> String {
+///     match session.get::<u32>("user_id") {
+///         Some(id) => format!("Hello user {}", id),
+///         None => "Please log in".to_string(),
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Session {
+    inner: Arc<Mutex<SessionInner>>,
+}
+
+impl Session {
+    fn new(id: Option<String>, data: HashMap<String, Value>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(SessionInner {
+                id,
+                previous_id: None,
+                data,
+                status: SessionStatus::Unchanged,
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SessionInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn mark_changed(inner: &mut SessionInner) {
+        if inner.status == SessionStatus::Unchanged {
+            inner.status = SessionStatus::Changed;
+        }
+    }
+
+    /// Returns the id of the session, `None` if the session has not been saved yet.
+    pub fn id(&self) -> Option<String> {
+        self.lock().id.clone()
+    }
+
+    /// Retrieves the value associated with the given key from the session.
+    ///
+    /// Returns `None` if the key is not found or if the value can't be deserialized to the specified type.
+    pub fn get<V: DeserializeOwned>(&self, key: &str) -> Option<V> {
+        let inner = self.lock();
+        inner
+            .data
+            .get(key)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
+
+    /// Sets the value associated with the given key in the session.
+    pub fn set<V: Serialize>(&self, key: &str, value: V) {
+        if let Ok(value) = serde_json::to_value(value) {
+            let mut inner = self.lock();
+            inner.data.insert(key.to_string(), value);
+            Self::mark_changed(&mut inner);
+        }
+    }
+
+    /// Removes the value associated with the given key from the session.
+    pub fn remove(&self, key: &str) {
+        let mut inner = self.lock();
+        if inner.data.remove(key).is_some() {
+            Self::mark_changed(&mut inner);
+        }
+    }
+
+    /// Removes all values from the session.
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.data.clear();
+        Self::mark_changed(&mut inner);
+    }
+
+    /// Checks if the session contains a value for the given key.
+    pub fn has(&self, key: &str) -> bool {
+        self.lock().data.contains_key(key)
+    }
+
+    /// Assigns a new id to the session, keeping its values.
+    ///
+    /// This should be done whenever the privileges of a session change (e.g. on login),
+    /// to prevent session fixation attacks.
+    pub fn rotate(&self) {
+        let mut inner = self.lock();
+        if inner.status != SessionStatus::Destroyed {
+            inner.status = SessionStatus::Rotated;
+        }
+    }
+
+    /// Destroys the session, removing it from the store and the client.
+    pub fn destroy(&self) {
+        let mut inner = self.lock();
+        inner.data.clear();
+        inner.status = SessionStatus::Destroyed;
+    }
+}
+
+impl Transformer<'_> for Session {
+    /// Transforms the given `NgynContext` into a `Session` instance.
+    ///
+    /// # Panics
+    /// Panics if the [`SessionMiddleware`] hasn't been registered.
+    fn transform(cx: &mut NgynContext) -> Self {
+        cx.extensions()
+            .get::<Session>()
+            .cloned()
+            .expect("Extracting a session requires the `SessionMiddleware` to be registered.")
+    }
+}
+
+/// A middleware that loads and saves sessions using a [`SessionStore`].
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+/// use ngyn::shared::middlewares::session::{MemoryStore, SessionMiddleware};
+///
+/// let mut app = HyperApplication::default();
+/// app.use_middleware(SessionMiddleware::new(MemoryStore::new()));
+/// ```
+pub struct SessionMiddleware<S: SessionStore> {
+    store: S,
+    config: SessionConfig,
+}
+
+impl<S: SessionStore> SessionMiddleware<S> {
+    /// Creates a new `SessionMiddleware` with the default configuration.
+    pub fn new(store: S) -> Self {
+        Self::with_config(store, SessionConfig::default())
+    }
+
+    /// Creates a new `SessionMiddleware` with the given configuration.
+    pub fn with_config(store: S, config: SessionConfig) -> Self {
+        Self { store, config }
+    }
+
+    fn session_id(&self, cx: &NgynContext) -> Option<String> {
+        let name = self.config.cookie_name.as_str();
+        let cookie = if self.config.signed {
+            cx.cookies().signed().get(name)
+        } else {
+            cx.cookies().get(name).cloned()
+        };
+        cookie.map(|cookie| cookie.value().to_string())
+    }
+
+    async fn load(&self, cx: &mut NgynContext<'_>) {
+        let mut session = None;
+
+        if let Some(id) = self.session_id(cx) {
+            if let Ok(Some(record)) = self.store.load(&id).await {
+                if !record.is_expired() {
+                    session = Some(Session::new(Some(id), record.data));
+                }
+            }
+        }
+
+        cx.extensions_mut()
+            .insert(session.unwrap_or_else(|| Session::new(None, HashMap::new())));
+    }
+
+    async fn save(&self, cx: &mut NgynContext<'_>) {
+        let Some(session) = cx.extensions().get::<Session>().cloned() else {
+            return;
+        };
+        let (id, data, status) = {
+            let mut inner = session.lock();
+            let status = inner.status;
+
+            if status == SessionStatus::Rotated {
+                inner.previous_id = inner.id.take();
+            }
+            // anonymous requests don't get a session until something is stored in it
+            let modified = matches!(status, SessionStatus::Changed | SessionStatus::Rotated);
+            if inner.id.is_none() && modified {
+                inner.id = Some(generate_id());
+            }
+            inner.status = SessionStatus::Unchanged;
+
+            (inner.id.clone(), inner.data.clone(), status)
+        };
+        let Some(id) = id else {
+            return;
+        };
+
+        let result = match status {
+            SessionStatus::Destroyed => {
+                let mut cookie = Cookie::from(self.config.cookie_name.clone());
+                cookie.set_path(self.config.path.clone());
+                if let Some(domain) = &self.config.domain {
+                    cookie.set_domain(domain.clone());
+                }
+                cx.cookies_mut().remove(cookie);
+                self.store.destroy(&id).await
+            }
+            SessionStatus::Unchanged if !self.config.rolling => return,
+            _ => {
+                let previous_id = session.lock().previous_id.take();
+                if let Some(previous_id) = previous_id {
+                    let _ = self.store.destroy(&previous_id).await;
+                }
+                let record = SessionRecord {
+                    data,
+                    expires_at: store::unix_now() + self.config.max_age.as_secs(),
+                };
+                self.set_cookie(cx, &id);
+                self.store.save(&id, &record).await
+            }
+        };
+
+        if result.is_err() {
+            *cx.response_mut().status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+
+    fn set_cookie(&self, cx: &mut NgynContext, id: &str) {
+        let mut cookie = Cookie::build((self.config.cookie_name.clone(), id.to_string()))
+            .path(self.config.path.clone())
+            .secure(self.config.secure)
+            .http_only(self.config.http_only)
+            .same_site(self.config.same_site)
+            .max_age(time::Duration::seconds(
+                self.config.max_age.as_secs().try_into().unwrap_or(i64::MAX),
+            ));
+        if let Some(domain) = &self.config.domain {
+            cookie = cookie.domain(domain.clone());
+        }
+
+        if self.config.signed {
+            cx.cookies_mut().signed_mut().add(cookie);
+        } else {
+            cx.cookies_mut().add(cookie);
+        }
+    }
+}
+
+impl<S: SessionStore> Middleware for SessionMiddleware<S> {
+    fn run<'a>(
+        &'a self,
+        cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(self.load(cx))
+    }
+
+    fn after<'a>(
+        &'a self,
+        cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(self.save(cx))
+    }
+}
+
+/// Generates a random session id, 256 bits long.
+fn generate_id() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{header::SET_COOKIE, Method};
+
+    use super::*;
+    use crate::{
+        core::engine::{read_body, MockEngine, NgynEngine},
+        core::handler::handler,
+        server::NgynResponse,
+    };
+
+    fn engine() -> MockEngine {
+        engine_with(SessionConfig::default())
+    }
+
+    fn engine_with(config: SessionConfig) -> MockEngine {
+        let mut engine = MockEngine::default();
+        engine.use_middleware(SessionMiddleware::with_config(MemoryStore::new(), config));
+        engine.any(
+            "/login",
+            handler(|cx| {
+                let session = Session::transform(cx);
+                session.rotate();
+                session.set("user", "John");
+                "ok"
+            }),
+        );
+        engine.any(
+            "/me",
+            handler(|cx| {
+                Session::transform(cx)
+                    .get::<String>("user")
+                    .unwrap_or_default()
+            }),
+        );
+        engine.any(
+            "/logout",
+            handler(|cx| {
+                Session::transform(cx).destroy();
+                "ok"
+            }),
+        );
+        engine
+    }
+
+    async fn request(engine: &MockEngine, path: &str, cookie: Option<&str>) -> NgynResponse {
+        let headers = match cookie {
+            Some(cookie) => vec![("cookie", cookie)],
+            None => Vec::new(),
+        };
+        engine.send(Method::GET, path, &headers).await
+    }
+
+    fn session_cookie(res: &NgynResponse) -> String {
+        let header = res.headers().get(SET_COOKIE).unwrap().to_str().unwrap();
+        header.split(';').next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_session_is_not_saved_when_unchanged() {
+        let engine = engine();
+        let res = request(&engine, "/me", None).await;
+
+        assert!(res.headers().get(SET_COOKIE).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rolling_session() {
+        let engine = engine_with(SessionConfig {
+            rolling: true,
+            ..Default::default()
+        });
+        // anonymous and unknown sessions aren't created
+        let res = request(&engine, "/me", None).await;
+        assert!(res.headers().get(SET_COOKIE).is_none());
+        let res = request(&engine, "/me", Some("ngyn.sid=unknown")).await;
+        assert!(res.headers().get(SET_COOKIE).is_none());
+
+        let cookie = session_cookie(&request(&engine, "/login", None).await);
+        let res = request(&engine, "/me", Some(&cookie)).await;
+        assert_eq!(session_cookie(&res), cookie);
+        assert_eq!(read_body(res).await, "John");
+    }
+
+    #[tokio::test]
+    async fn test_session_persists_between_requests() {
+        let engine = engine();
+        let res = request(&engine, "/login", None).await;
+        let cookie = session_cookie(&res);
+        assert!(cookie.starts_with("ngyn.sid="));
+
+        let res = request(&engine, "/me", Some(&cookie)).await;
+        assert_eq!(read_body(res).await, "John");
+    }
+
+    #[tokio::test]
+    async fn test_session_rotate() {
+        let engine = engine();
+        let first = session_cookie(&request(&engine, "/login", None).await);
+        let second = session_cookie(&request(&engine, "/login", Some(&first)).await);
+        assert_ne!(first, second);
+
+        // the previous id can no longer be used
+        let res = request(&engine, "/me", Some(&first)).await;
+        assert_eq!(read_body(res).await, "");
+    }
+
+    #[tokio::test]
+    async fn test_session_destroy() {
+        let engine = engine();
+        let cookie = session_cookie(&request(&engine, "/login", None).await);
+
+        let res = request(&engine, "/logout", Some(&cookie)).await;
+        let header = res.headers().get(SET_COOKIE).unwrap().to_str().unwrap();
+        assert!(header.contains("Max-Age=0"));
+
+        let res = request(&engine, "/me", Some(&cookie)).await;
+        assert_eq!(read_body(res).await, "");
+    }
+
+    #[test]
+    fn test_generate_id() {
+        let id = generate_id();
+        assert_eq!(id.len(), 64);
+        assert_ne!(id, generate_id());
+    }
+}
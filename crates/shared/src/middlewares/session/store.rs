@@ -0,0 +1,255 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Represents the data of a session, as persisted by a [`SessionStore`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SessionRecord {
+    /// The values stored in the session.
+    pub data: HashMap<String, Value>,
+    /// The time the session expires, in seconds since the unix epoch.
+    pub expires_at: u64,
+}
+
+impl SessionRecord {
+    /// Checks if the session has expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= unix_now()
+    }
+}
+
+/// Trait for implementing a session store.
+///
+/// A session store persists sessions between requests.
+/// Ngyn provides a [`MemoryStore`] and a [`FileStore`], other stores (e.g. Redis)
+/// can be used by implementing this trait.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn_shared::middlewares::session::{SessionRecord, SessionStore};
+///
+/// struct RedisStore {
+///     client: redis::Client,
+/// }
+///
+/// impl SessionStore for RedisStore {
+///     async fn load(&self, id: &str) -> std::io::Result<Option<SessionRecord>> {
+///         // fetch and deserialize the record
+///     }
+///
+///     async fn save(&self, id: &str, record: &SessionRecord) -> std::io::Result<()> {
+///         // serialize the record and set it with an expiry of `record.expires_at`
+///     }
+///
+///     async fn destroy(&self, id: &str) -> std::io::Result<()> {
+///         // delete the record
+///     }
+/// }
+/// ```
+pub trait SessionStore: Send + Sync + 'static {
+    /// Loads the session with the given id.
+    ///
+    /// Returns `None` if the session doesn't exist or has expired.
+    fn load(&self, id: &str) -> impl Future<Output = io::Result<Option<SessionRecord>>> + Send;
+
+    /// Saves the session with the given id, replacing any existing session with that id.
+    fn save(&self, id: &str, record: &SessionRecord)
+        -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Removes the session with the given id.
+    fn destroy(&self, id: &str) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+/// A session store that keeps sessions in memory.
+///
+/// Sessions are lost when the application restarts, and aren't shared between instances of the application.
+#[derive(Default)]
+pub struct MemoryStore {
+    sessions: Mutex<HashMap<String, SessionRecord>>,
+}
+
+impl MemoryStore {
+    /// Creates a new, empty `MemoryStore`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, SessionRecord>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl SessionStore for MemoryStore {
+    async fn load(&self, id: &str) -> io::Result<Option<SessionRecord>> {
+        let mut sessions = self.lock();
+        match sessions.get(id) {
+            Some(record) if record.is_expired() => {
+                sessions.remove(id);
+                Ok(None)
+            }
+            record => Ok(record.cloned()),
+        }
+    }
+
+    async fn save(&self, id: &str, record: &SessionRecord) -> io::Result<()> {
+        let mut sessions = self.lock();
+        // forget expired sessions once the store grows, amortized over the insertions
+        if !sessions.contains_key(id) && sessions.len() >= 1024 && sessions.len().is_power_of_two()
+        {
+            sessions.retain(|_, record| !record.is_expired());
+        }
+        sessions.insert(id.to_string(), record.clone());
+        Ok(())
+    }
+
+    async fn destroy(&self, id: &str) -> io::Result<()> {
+        let mut sessions = self.lock();
+        sessions.remove(id);
+        Ok(())
+    }
+}
+
+/// A session store that keeps each session as a json file in a directory.
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    /// Creates a new `FileStore`, the directory is created if it doesn't exist.
+    ///
+    /// ### Arguments
+    ///
+    /// * `dir` - The directory the sessions are stored in.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, id: &str) -> io::Result<PathBuf> {
+        // session ids come from clients, so they must never be able to escape the directory
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid session id",
+            ));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+}
+
+impl SessionStore for FileStore {
+    async fn load(&self, id: &str) -> io::Result<Option<SessionRecord>> {
+        let path = self.path(id)?;
+        let content = match tokio::fs::read(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let record: SessionRecord = serde_json::from_slice(&content)?;
+
+        if record.is_expired() {
+            tokio::fs::remove_file(path).await?;
+            return Ok(None);
+        }
+        Ok(Some(record))
+    }
+
+    async fn save(&self, id: &str, record: &SessionRecord) -> io::Result<()> {
+        tokio::fs::write(self.path(id)?, serde_json::to_vec(record)?).await
+    }
+
+    async fn destroy(&self, id: &str) -> io::Result<()> {
+        match tokio::fs::remove_file(self.path(id)?).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(expires_at: u64) -> SessionRecord {
+        let mut data = HashMap::new();
+        data.insert("name".to_string(), Value::from("John"));
+        SessionRecord { data, expires_at }
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = MemoryStore::new();
+        assert!(store.load("abc").await.unwrap().is_none());
+
+        store.save("abc", &record(unix_now() + 60)).await.unwrap();
+        let loaded = store.load("abc").await.unwrap().unwrap();
+        assert_eq!(loaded.data["name"], "John");
+
+        store.destroy("abc").await.unwrap();
+        assert!(store.load("abc").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_expired() {
+        let store = MemoryStore::new();
+        store.save("abc", &record(unix_now() - 1)).await.unwrap();
+
+        assert!(store.load("abc").await.unwrap().is_none());
+        assert!(store.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_sweep() {
+        let store = MemoryStore::new();
+        for id in 0..1024 {
+            store
+                .save(&id.to_string(), &record(unix_now() - 1))
+                .await
+                .unwrap();
+        }
+        store.save("abc", &record(unix_now() + 60)).await.unwrap();
+
+        // the expired sessions are swept once the store grows
+        assert_eq!(store.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_file_store() {
+        let dir = std::env::temp_dir().join(format!("ngyn-sessions-{}", unix_now()));
+        let store = FileStore::new(&dir).unwrap();
+
+        store.save("abc", &record(unix_now() + 60)).await.unwrap();
+        let loaded = store.load("abc").await.unwrap().unwrap();
+        assert_eq!(loaded.data["name"], "John");
+
+        store
+            .save("expired", &record(unix_now() - 1))
+            .await
+            .unwrap();
+        assert!(store.load("expired").await.unwrap().is_none());
+        assert!(!dir.join("expired.json").exists());
+
+        store.destroy("abc").await.unwrap();
+        assert!(store.load("abc").await.unwrap().is_none());
+
+        assert!(store.load("../abc").await.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
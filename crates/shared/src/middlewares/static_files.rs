@@ -0,0 +1,914 @@
+use std::{
+    fs::Metadata,
+    future::Future,
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+    pin::Pin,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use http::{
+    header::{
+        ACCEPT_RANGES, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE,
+        CONTENT_TYPE, ETAG, IF_RANGE, LAST_MODIFIED, LOCATION, RANGE, VARY,
+    },
+    HeaderMap, HeaderValue, Method, StatusCode, Uri,
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    middlewares::{compression::accepted_encodings, etag::evaluate_preconditions},
+    server::{response::ResponseStream, NgynContext},
+    Middleware,
+};
+
+/// The precompressed variants of a file, from the most to the least preferred.
+const PRECOMPRESSED: &[(&str, &str)] = &[("br", "br"), ("gzip", "gz")];
+
+/// A middleware that serves the files of a directory, for requests that no route matched.
+///
+/// Files are streamed from disk when they are requested, or embedded into the binary with `embed_static!`, with:
+/// - a `Content-Type` guessed from their extension.
+/// - `ETag` and `Last-Modified` validators, answering conditional requests with `304 Not Modified`
+///   or `412 Precondition Failed`.
+/// - single `Range` requests, answered with `206 Partial Content`.
+/// - index files served for directories, and `.br` or `.gz` variants served to clients that accept them.
+///
+/// Paths can't escape the directory, and hidden files, i.e. files starting with a `.`, aren't served.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// app.use_middleware(
+///     StaticFiles::new("public")?
+///         .mount("/assets")
+///         .precompressed(true)
+///         .cache_control(HeaderValue::from_static("public, max-age=3600")),
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct StaticFiles {
+    source: Source,
+    mount: String,
+    index_files: Vec<String>,
+    precompressed: bool,
+    cache_control: Option<HeaderValue>,
+}
+
+/// Where the files of a [`StaticFiles`] middleware are read from.
+#[derive(Clone, Debug)]
+enum Source {
+    Disk(PathBuf),
+    Embedded(&'static [EmbeddedFile]),
+}
+
+/// A file embedded into the binary by the `embed_static!` macro.
+pub struct EmbeddedFile {
+    /// The path of the file, relative to the embedded directory, e.g. `docs/index.html`.
+    pub path: &'static str,
+    /// The content of the file.
+    pub content: &'static [u8],
+    /// The content type of the file.
+    pub content_type: &'static str,
+    /// A hash of the content of the file, used in its `ETag`.
+    pub hash: &'static str,
+    /// The content compressed with `br` at build time, if it's smaller.
+    pub brotli: Option<&'static [u8]>,
+    /// The content compressed with `gzip` at build time, if it's smaller.
+    pub gzip: Option<&'static [u8]>,
+}
+
+impl std::fmt::Debug for EmbeddedFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmbeddedFile")
+            .field("path", &self.path)
+            .field("len", &self.content.len())
+            .field("content_type", &self.content_type)
+            .field("hash", &self.hash)
+            .finish()
+    }
+}
+
+/// A file resolved from a request path.
+struct StaticFile {
+    content: Content,
+    /// The content type of the file, rather than of its precompressed variant.
+    content_type: HeaderValue,
+    /// The encoding of a precompressed variant of the file.
+    encoding: Option<&'static str>,
+    len: u64,
+    etag: HeaderValue,
+    modified: Option<SystemTime>,
+}
+
+enum Content {
+    Disk(PathBuf),
+    Embedded(&'static [u8]),
+}
+
+/// The body of a served file.
+enum FileBody {
+    Buffered(Bytes),
+    /// A stream of the file, with its length.
+    Streamed(ReaderStream<tokio::io::Take<tokio::fs::File>>, u64),
+}
+
+impl StaticFile {
+    fn from_disk(
+        path: PathBuf,
+        content_type: HeaderValue,
+        encoding: Option<&'static str>,
+        metadata: &Metadata,
+    ) -> Self {
+        let modified = metadata.modified().ok();
+        let since_epoch = modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        let etag = format!("\"{:x}-{:x}\"", metadata.len(), since_epoch.as_nanos());
+
+        Self {
+            content: Content::Disk(path),
+            content_type,
+            encoding,
+            len: metadata.len(),
+            etag: HeaderValue::try_from(etag).expect("hex digits are valid header values"),
+            modified,
+        }
+    }
+
+    fn last_modified(&self) -> Option<HeaderValue> {
+        let date = httpdate::fmt_http_date(self.modified?);
+        HeaderValue::try_from(date).ok()
+    }
+}
+
+impl StaticFiles {
+    /// Creates a middleware serving the files of a directory, mounted at `/`.
+    ///
+    /// ### Arguments
+    ///
+    /// * `root` - The directory to serve, relative to the working directory.
+    ///
+    /// ### Returns
+    ///
+    /// The middleware, or an error if the directory doesn't exist.
+    pub fn new<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        let root = root.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("static path `{}` is not a directory", root.display()),
+            ));
+        }
+
+        Ok(Self {
+            source: Source::Disk(root),
+            mount: String::new(),
+            index_files: vec!["index.html".to_string()],
+            precompressed: false,
+            cache_control: None,
+        })
+    }
+
+    /// Creates a middleware serving files embedded into the binary, mounted at `/`.
+    ///
+    /// The files are usually embedded with `embed_static!`, their variants compressed at build time are served.
+    ///
+    /// ### Arguments
+    ///
+    /// * `files` - The embedded files.
+    pub fn embedded(files: &'static [EmbeddedFile]) -> Self {
+        Self {
+            source: Source::Embedded(files),
+            mount: String::new(),
+            index_files: vec!["index.html".to_string()],
+            precompressed: true,
+            cache_control: None,
+        }
+    }
+
+    /// Sets the path the files are served under, e.g. `/assets`.
+    pub fn mount(mut self, path: &str) -> Self {
+        self.mount = path.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sets the files served for requests to a directory, defaults to `index.html`.
+    pub fn index_files<I, S>(mut self, files: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.index_files = files.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Serves the `.br` and `.gz` variants of files, to clients accepting these encodings.
+    ///
+    /// Variants are looked up next to the files on disk, e.g. `app.js.br` for `app.js`.
+    pub fn precompressed(mut self, enabled: bool) -> Self {
+        self.precompressed = enabled;
+        self
+    }
+
+    /// Sets the `Cache-Control` header of the served files.
+    pub fn cache_control(mut self, value: HeaderValue) -> Self {
+        self.cache_control = Some(value);
+        self
+    }
+
+    /// Splits the path of a request into the segments of a path in the directory.
+    ///
+    /// ### Returns
+    ///
+    /// `None` if the path isn't under the mount path, or isn't allowed.
+    fn segments(&self, path: &str) -> Option<Vec<String>> {
+        let path = path.strip_prefix(self.mount.as_str())?;
+        if !path.is_empty() && !path.starts_with('/') {
+            return None;
+        }
+
+        let mut segments = Vec::new();
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            let segment = percent_encoding::percent_decode_str(segment)
+                .decode_utf8()
+                .ok()?;
+            // rejects `.` and `..`, hidden files and separators smuggled in an encoded segment
+            if segment.starts_with('.') || segment.contains(['/', '\\', '\0']) {
+                return None;
+            }
+            segments.push(segment.into_owned());
+        }
+        Some(segments)
+    }
+
+    /// Resolves the file served for a request.
+    ///
+    /// ### Returns
+    ///
+    /// The file, or a redirect if a directory is requested without a trailing slash.
+    async fn resolve(
+        &self,
+        uri: &Uri,
+        headers: &HeaderMap,
+    ) -> Option<Result<StaticFile, HeaderValue>> {
+        let segments = self.segments(uri.path())?;
+        let accepted = if self.precompressed {
+            accepted_encodings(headers)
+        } else {
+            Vec::new()
+        };
+
+        match &self.source {
+            Source::Disk(root) => self.resolve_disk(root, segments, uri, &accepted).await,
+            Source::Embedded(files) => self.resolve_embedded(files, segments, uri, &accepted),
+        }
+    }
+
+    async fn resolve_disk(
+        &self,
+        root: &Path,
+        segments: Vec<String>,
+        uri: &Uri,
+        accepted: &[(&str, f32)],
+    ) -> Option<Result<StaticFile, HeaderValue>> {
+        let local = segments
+            .iter()
+            .fold(root.to_path_buf(), |path, segment| path.join(segment));
+        // symlinks can point out of the directory
+        let local = tokio::fs::canonicalize(local).await.ok()?;
+        if !local.starts_with(root) {
+            return None;
+        }
+
+        let mut metadata = tokio::fs::metadata(&local).await.ok()?;
+        let mut file = local;
+        if metadata.is_dir() {
+            if !uri.path().ends_with('/') {
+                return redirect(uri);
+            }
+            let mut index = None;
+            for name in &self.index_files {
+                if let Some((found, metadata)) = resolve_within(root, file.join(name)).await {
+                    index = Some((found, metadata));
+                    break;
+                }
+            }
+            (file, metadata) = index?;
+        }
+        if !metadata.is_file() {
+            return None;
+        }
+
+        for (encoding, extension) in PRECOMPRESSED {
+            if !accepts(accepted, encoding) {
+                continue;
+            }
+            let mut variant = file.clone().into_os_string();
+            variant.push(".");
+            variant.push(extension);
+            if let Some((variant, found)) = resolve_within(root, variant.into()).await {
+                return Some(Ok(StaticFile::from_disk(
+                    variant,
+                    content_type(&file),
+                    Some(encoding),
+                    &found,
+                )));
+            }
+        }
+
+        Some(Ok(StaticFile::from_disk(
+            file.clone(),
+            content_type(&file),
+            None,
+            &metadata,
+        )))
+    }
+
+    fn resolve_embedded(
+        &self,
+        files: &'static [EmbeddedFile],
+        segments: Vec<String>,
+        uri: &Uri,
+        accepted: &[(&str, f32)],
+    ) -> Option<Result<StaticFile, HeaderValue>> {
+        let path = segments.join("/");
+        let find = |path: &str| files.iter().find(|file| file.path == path);
+
+        let file = match find(&path) {
+            Some(file) => file,
+            None => {
+                // directories aren't embedded, they are the prefixes of the embedded files
+                let dir = if path.is_empty() { path } else { path + "/" };
+                if !files.iter().any(|file| file.path.starts_with(&dir)) {
+                    return None;
+                }
+                if !uri.path().ends_with('/') {
+                    return redirect(uri);
+                }
+                self.index_files
+                    .iter()
+                    .find_map(|name| find(&format!("{}{}", dir, name)))?
+            }
+        };
+
+        let variant = PRECOMPRESSED.iter().find_map(|(encoding, _)| {
+            let content = match *encoding {
+                "br" => file.brotli,
+                "gzip" => file.gzip,
+                _ => None,
+            };
+            content
+                .filter(|_| accepts(accepted, encoding))
+                .map(|content| (content, *encoding))
+        });
+        let (content, encoding, etag) = match variant {
+            Some((content, encoding)) => (
+                content,
+                Some(encoding),
+                format!("\"{}-{}\"", file.hash, encoding),
+            ),
+            None => (file.content, None, format!("\"{}\"", file.hash)),
+        };
+
+        Some(Ok(StaticFile {
+            content: Content::Embedded(content),
+            content_type: HeaderValue::from_static(file.content_type),
+            encoding,
+            len: content.len() as u64,
+            etag: HeaderValue::try_from(etag).ok()?,
+            modified: None,
+        }))
+    }
+
+    async fn serve(&self, cx: &mut NgynContext<'_>) {
+        let method = cx.request().method().clone();
+        // routes take precedence over files
+        if cx.params().is_some() || (method != Method::GET && method != Method::HEAD) {
+            return;
+        }
+
+        let uri = cx.request().uri().clone();
+        let file = match self.resolve(&uri, cx.request().headers()).await {
+            Some(Ok(file)) => file,
+            Some(Err(location)) => {
+                let res = cx.response_mut();
+                *res.status_mut() = StatusCode::PERMANENT_REDIRECT;
+                res.headers_mut().insert(LOCATION, location);
+                cx.halt();
+                return;
+            }
+            None => return,
+        };
+
+        let etag = file.etag.clone();
+        let last_modified = file.last_modified();
+        let request = cx.request().headers();
+
+        let precondition =
+            evaluate_preconditions(&method, request, etag.to_str().ok(), file.modified);
+        let range = match request.get(RANGE).and_then(|value| value.to_str().ok()) {
+            Some(range)
+                if method == Method::GET && if_range(request, &etag, last_modified.as_ref()) =>
+            {
+                parse_range(range, file.len)
+            }
+            _ => Ok(None),
+        };
+        let read = match precondition {
+            Some(status) => Err(status),
+            None => read_file(&file, &method, range).await,
+        };
+
+        let res = cx.response_mut();
+        let headers = res.headers_mut();
+        headers.insert(ETAG, etag);
+        if let Some(last_modified) = last_modified {
+            headers.insert(LAST_MODIFIED, last_modified);
+        }
+        if let Some(cache_control) = &self.cache_control {
+            headers.insert(CACHE_CONTROL, cache_control.clone());
+        }
+        if self.precompressed {
+            headers.insert(VARY, HeaderValue::from_static("accept-encoding"));
+        }
+
+        *res.status_mut() = match read {
+            Ok((status, body, content_range)) => {
+                let headers = res.headers_mut();
+                headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+                headers.insert(CONTENT_TYPE, file.content_type);
+                if let Some(encoding) = file.encoding {
+                    headers.insert(CONTENT_ENCODING, HeaderValue::from_static(encoding));
+                }
+                if let Some(content_range) = content_range {
+                    headers.insert(CONTENT_RANGE, content_range);
+                }
+                // HEAD responses have no body, but the length of the one GET would get
+                if method == Method::HEAD {
+                    headers.insert(CONTENT_LENGTH, file.len.into());
+                }
+                match body {
+                    FileBody::Buffered(body) => *res.body_mut() = body.into(),
+                    FileBody::Streamed(stream, len) => {
+                        res.headers_mut().insert(CONTENT_LENGTH, len.into());
+                        ResponseStream::set(res, stream);
+                    }
+                }
+                status
+            }
+            Err(status) => {
+                if status == StatusCode::RANGE_NOT_SATISFIABLE {
+                    let content_range = format!("bytes */{}", file.len);
+                    res.headers_mut().insert(
+                        CONTENT_RANGE,
+                        HeaderValue::try_from(content_range)
+                            .expect("digits are valid header values"),
+                    );
+                }
+                status
+            }
+        };
+        cx.halt();
+    }
+}
+
+/// Resolves a file which must be inside `root`, once its symlinks are followed.
+///
+/// ### Returns
+///
+/// The canonical path of the file and its metadata, if it's a file inside `root`.
+async fn resolve_within(root: &Path, path: PathBuf) -> Option<(PathBuf, std::fs::Metadata)> {
+    let path = tokio::fs::canonicalize(path).await.ok()?;
+    if !path.starts_with(root) {
+        return None;
+    }
+    let metadata = tokio::fs::metadata(&path).await.ok()?;
+    metadata.is_file().then_some((path, metadata))
+}
+
+/// Opens the body of a file, or of the requested range of it.
+///
+/// Files on disk are streamed, they are never read into memory as a whole.
+///
+/// ### Returns
+///
+/// The status, body and `Content-Range` of the response, or the status of the error.
+async fn read_file(
+    file: &StaticFile,
+    method: &Method,
+    range: Result<Option<(u64, u64)>, ()>,
+) -> Result<(StatusCode, FileBody, Option<HeaderValue>), StatusCode> {
+    let range = range.map_err(|_| StatusCode::RANGE_NOT_SATISFIABLE)?;
+    if method == Method::HEAD {
+        return Ok((StatusCode::OK, FileBody::Buffered(Bytes::new()), None));
+    }
+
+    let path = match &file.content {
+        Content::Disk(path) => path,
+        Content::Embedded(content) => {
+            let (status, body, content_range) = match range {
+                Some((start, end)) => (
+                    StatusCode::PARTIAL_CONTENT,
+                    &content[start as usize..=end as usize],
+                    Some(content_range(start, end, file.len)),
+                ),
+                None => (StatusCode::OK, *content, None),
+            };
+            return Ok((
+                status,
+                FileBody::Buffered(Bytes::from_static(body)),
+                content_range,
+            ));
+        }
+    };
+
+    let mut handle = tokio::fs::File::open(path)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let Some((start, end)) = range else {
+        let stream = ReaderStream::new(handle.take(file.len));
+        return Ok((StatusCode::OK, FileBody::Streamed(stream, file.len), None));
+    };
+
+    handle
+        .seek(SeekFrom::Start(start))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let len = end - start + 1;
+    let stream = ReaderStream::new(handle.take(len));
+
+    Ok((
+        StatusCode::PARTIAL_CONTENT,
+        FileBody::Streamed(stream, len),
+        Some(content_range(start, end, file.len)),
+    ))
+}
+
+fn content_range(start: u64, end: u64, len: u64) -> HeaderValue {
+    HeaderValue::try_from(format!("bytes {}-{}/{}", start, end, len))
+        .expect("digits are valid header values")
+}
+
+/// Redirects a request for a directory to the same path with a trailing slash.
+fn redirect(uri: &Uri) -> Option<Result<StaticFile, HeaderValue>> {
+    let location = match uri.query() {
+        Some(query) => format!("{}/?{}", uri.path(), query),
+        None => format!("{}/", uri.path()),
+    };
+    HeaderValue::try_from(location).ok().map(Err)
+}
+
+/// Checks if an encoding is accepted, from the parsed `Accept-Encoding` header of a request.
+fn accepts(accepted: &[(&str, f32)], encoding: &str) -> bool {
+    accepted
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(encoding))
+        .or_else(|| accepted.iter().find(|(name, _)| *name == "*"))
+        .is_some_and(|(_, quality)| *quality > 0.0)
+}
+
+/// Guesses the content type of a file from its extension, text files are assumed to be UTF-8.
+pub fn content_type(file: &Path) -> HeaderValue {
+    let mime = mime_guess::from_path(file).first_or_octet_stream();
+    let value = match mime.type_() {
+        mime_guess::mime::TEXT => format!("{}; charset=utf-8", mime.essence_str()),
+        _ => mime.essence_str().to_string(),
+    };
+    HeaderValue::try_from(value).expect("mime types are valid header values")
+}
+
+/// Checks if the `Range` header of a request applies, following its `If-Range` header.
+fn if_range(headers: &HeaderMap, etag: &HeaderValue, last_modified: Option<&HeaderValue>) -> bool {
+    match headers.get(IF_RANGE) {
+        // `If-Range` uses the strong comparison
+        Some(value) => value == etag || Some(value) == last_modified,
+        None => true,
+    }
+}
+
+/// Parses a `Range` header, for a file of `len` bytes.
+///
+/// ### Returns
+///
+/// The first and last bytes of the range, `None` if the header should be ignored,
+/// i.e. it's invalid or has several ranges, or an error if the range can't be satisfied.
+fn parse_range(value: &str, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(range) = value.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if range.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = range.trim().split_once('-') else {
+        return Ok(None);
+    };
+
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+        (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return Err(());
+            }
+            (len.saturating_sub(suffix), len.saturating_sub(1))
+        }
+        _ => return Ok(None),
+    };
+
+    if range.0 >= len {
+        return Err(());
+    }
+    Ok(Some(range))
+}
+
+impl Middleware for StaticFiles {
+    fn run<'a>(
+        &'a self,
+        cx: &'a mut NgynContext<'_>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(self.serve(cx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        engine::{read_body, MockEngine, NgynEngine, NgynHttpEngine},
+        handler::handler,
+    };
+    use crate::server::NgynResponse;
+
+    /// Creates a directory of static files, unique to a test.
+    fn public_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ngyn-static-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("public/docs")).unwrap();
+        std::fs::write(dir.join("public/app.js"), "console.log('ngyn');").unwrap();
+        std::fs::write(dir.join("public/app.js.br"), "brotli").unwrap();
+        std::fs::write(dir.join("public/docs/index.html"), "<h1>docs</h1>").unwrap();
+        std::fs::write(dir.join("public/.env"), "SECRET=1").unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+        dir
+    }
+
+    async fn send(engine: &MockEngine, path: &str, headers: &[(&str, &str)]) -> NgynResponse {
+        engine.send(Method::GET, path, headers).await
+    }
+
+    #[tokio::test]
+    async fn test_serve_file() {
+        let dir = public_dir("serve");
+        let mut engine = MockEngine::default();
+        engine.get("/docs/", handler(|_| "route"));
+        engine.use_middleware(StaticFiles::new(dir.join("public")).unwrap());
+
+        let res = send(&engine, "/app.js", &[]).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()[CONTENT_TYPE],
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(res.headers()[ACCEPT_RANGES], "bytes");
+        assert!(res.headers().contains_key(ETAG));
+        assert!(res.headers().contains_key(LAST_MODIFIED));
+        // files on disk are streamed
+        assert!(ResponseStream::is_set(&res));
+        assert_eq!(res.headers()[CONTENT_LENGTH], "20");
+        assert_eq!(read_body(res).await, "console.log('ngyn');");
+
+        // routes take precedence over files
+        let res = send(&engine, "/docs/", &[]).await;
+        assert_eq!(read_body(res).await, "route");
+
+        let res = send(&engine, "/missing.js", &[]).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_directory_index() {
+        let dir = public_dir("index");
+        let mut engine = MockEngine::default();
+        engine.use_middleware(
+            StaticFiles::new(dir.join("public"))
+                .unwrap()
+                .mount("/static"),
+        );
+
+        let res = send(&engine, "/static/docs", &[]).await;
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(res.headers()[LOCATION], "/static/docs/");
+
+        let res = send(&engine, "/static/docs/", &[]).await;
+        assert_eq!(res.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        assert_eq!(read_body(res).await, "<h1>docs</h1>");
+
+        let res = send(&engine, "/app.js", &[]).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_path_traversal() {
+        let dir = public_dir("traversal");
+        let mut engine = MockEngine::default();
+        engine.use_middleware(StaticFiles::new(dir.join("public")).unwrap());
+
+        for path in [
+            "/../secret.txt",
+            "/%2e%2e/secret.txt",
+            "/docs/..%2f..%2fsecret.txt",
+            "/.env",
+        ] {
+            let res = send(&engine, path, &[]).await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", path);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlinks() {
+        let dir = public_dir("symlinks");
+        std::fs::create_dir(dir.join("public/blog")).unwrap();
+        std::os::unix::fs::symlink(dir.join("secret.txt"), dir.join("public/app.js.gz")).unwrap();
+        std::os::unix::fs::symlink(dir.join("secret.txt"), dir.join("public/blog/index.html"))
+            .unwrap();
+        let mut engine = MockEngine::default();
+        engine.use_middleware(
+            StaticFiles::new(dir.join("public"))
+                .unwrap()
+                .precompressed(true),
+        );
+
+        // precompressed variants and index files can't point out of the directory either
+        let res = send(&engine, "/app.js", &[("accept-encoding", "gzip")]).await;
+        assert!(!res.headers().contains_key(CONTENT_ENCODING));
+        assert_eq!(read_body(res).await, "console.log('ngyn');");
+
+        let res = send(&engine, "/blog/", &[]).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_requests() {
+        let dir = public_dir("conditional");
+        let mut engine = MockEngine::default();
+        engine.use_middleware(
+            StaticFiles::new(dir.join("public"))
+                .unwrap()
+                .cache_control(HeaderValue::from_static("public, max-age=60")),
+        );
+
+        let res = send(&engine, "/app.js", &[]).await;
+        let etag = res.headers()[ETAG].to_str().unwrap().to_string();
+        let last_modified = res.headers()[LAST_MODIFIED].to_str().unwrap().to_string();
+        assert_eq!(res.headers()[CACHE_CONTROL], "public, max-age=60");
+
+        let res = send(
+            &engine,
+            "/app.js",
+            &[("if-none-match", &format!("W/{}", etag))],
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()[CACHE_CONTROL], "public, max-age=60");
+        assert!(read_body(res).await.is_empty());
+
+        let res = send(&engine, "/app.js", &[("if-modified-since", &last_modified)]).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+        let res = send(&engine, "/app.js", &[("if-none-match", "\"other\"")]).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_range_requests() {
+        let dir = public_dir("range");
+        let mut engine = MockEngine::default();
+        engine.use_middleware(StaticFiles::new(dir.join("public")).unwrap());
+
+        let res = send(&engine, "/app.js", &[("range", "bytes=0-6")]).await;
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.headers()[CONTENT_RANGE], "bytes 0-6/20");
+        assert_eq!(res.headers()[CONTENT_LENGTH], "7");
+        assert!(ResponseStream::is_set(&res));
+        assert_eq!(read_body(res).await, "console");
+
+        let res = send(&engine, "/app.js", &[("range", "bytes=8-")]).await;
+        assert_eq!(res.headers()[CONTENT_RANGE], "bytes 8-19/20");
+        assert_eq!(read_body(res).await, "log('ngyn');");
+
+        let res = send(&engine, "/app.js", &[("range", "bytes=-3")]).await;
+        assert_eq!(read_body(res).await, "');");
+
+        let res = send(&engine, "/app.js", &[("range", "bytes=20-")]).await;
+        assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(res.headers()[CONTENT_RANGE], "bytes */20");
+
+        // a stale `If-Range` gets the whole file
+        let res = send(
+            &engine,
+            "/app.js",
+            &[("range", "bytes=0-6"), ("if-range", "\"stale\"")],
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_precompressed() {
+        let dir = public_dir("precompressed");
+        let mut engine = MockEngine::default();
+        engine.use_middleware(
+            StaticFiles::new(dir.join("public"))
+                .unwrap()
+                .precompressed(true),
+        );
+
+        let res = send(&engine, "/app.js", &[("accept-encoding", "gzip, br")]).await;
+        assert_eq!(res.headers()[CONTENT_ENCODING], "br");
+        assert_eq!(
+            res.headers()[CONTENT_TYPE],
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(res.headers()[VARY], "accept-encoding");
+        assert_eq!(read_body(res).await, "brotli");
+
+        let res = send(&engine, "/app.js", &[("accept-encoding", "gzip")]).await;
+        assert!(!res.headers().contains_key(CONTENT_ENCODING));
+        assert_eq!(read_body(res).await, "console.log('ngyn');");
+    }
+
+    #[tokio::test]
+    async fn test_embedded() {
+        static FILES: &[EmbeddedFile] = &[
+            EmbeddedFile {
+                path: "app.js",
+                content: b"console.log('ngyn');",
+                content_type: "text/javascript; charset=utf-8",
+                hash: "3f2a",
+                brotli: Some(b"brotli"),
+                gzip: None,
+            },
+            EmbeddedFile {
+                path: "docs/index.html",
+                content: b"<h1>docs</h1>",
+                content_type: "text/html; charset=utf-8",
+                hash: "9c1b",
+                brotli: None,
+                gzip: None,
+            },
+        ];
+        let mut engine = MockEngine::default();
+        engine.use_middleware(StaticFiles::embedded(FILES));
+
+        let res = send(&engine, "/app.js", &[("range", "bytes=0-6")]).await;
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.headers()[ETAG], "\"3f2a\"");
+        assert_eq!(read_body(res).await, "console");
+
+        let res = send(&engine, "/app.js", &[("accept-encoding", "br")]).await;
+        assert_eq!(res.headers()[CONTENT_ENCODING], "br");
+        assert_eq!(res.headers()[ETAG], "\"3f2a-br\"");
+        assert_eq!(read_body(res).await, "brotli");
+
+        let res = send(&engine, "/app.js", &[("if-none-match", "\"3f2a\"")]).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+        let res = send(&engine, "/docs", &[]).await;
+        assert_eq!(res.headers()[LOCATION], "/docs/");
+        let res = send(&engine, "/docs/", &[]).await;
+        assert_eq!(res.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        assert_eq!(read_body(res).await, "<h1>docs</h1>");
+
+        let res = send(&engine, "/do", &[]).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-499", 1000), Ok(Some((0, 499))));
+        assert_eq!(parse_range("bytes=500-", 1000), Ok(Some((500, 999))));
+        assert_eq!(parse_range("bytes=900-2000", 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_range("bytes=-2000", 1000), Ok(Some((0, 999))));
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), Ok(None));
+        assert_eq!(parse_range("bytes=5-1", 1000), Ok(None));
+        assert_eq!(parse_range("items=0-1", 1000), Ok(None));
+        assert_eq!(parse_range("bytes=1000-", 1000), Err(()));
+        assert_eq!(parse_range("bytes=-0", 1000), Err(()));
+    }
+
+    #[test]
+    fn test_use_static() {
+        let dir = public_dir("use-static");
+        let mut engine = MockEngine::default();
+
+        assert!(engine.use_static(dir.join("public")).is_ok());
+        assert!(engine.use_static(dir.join("missing")).is_err());
+        assert!(engine.use_static(dir.join("secret.txt")).is_err());
+    }
+}
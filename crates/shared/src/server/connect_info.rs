@@ -0,0 +1,448 @@
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+
+use http::{uri::Scheme, HeaderMap, Version};
+
+use crate::server::{NgynContext, Transformer};
+
+/// Information about the connection a request was received on.
+///
+/// Platforms insert it into the extensions of the `http::Request` they pass to the application,
+/// handlers extract it with the [`Transformer`] implementation.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// #[handler]
+/// fn whoami(info: ConnectInfo) -> String {
+///     match info.client_ip() {
+///         Some(ip) => format!("Hello {}", ip),
+///         None => "Hello stranger".to_string(),
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ConnectInfo {
+    peer_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+    scheme: Scheme,
+    version: Version,
+    server_name: Option<String>,
+    forwarded: Option<Forwarded>,
+}
+
+/// The client information resolved from the forwarding headers of trusted proxies.
+#[derive(Clone, Debug)]
+struct Forwarded {
+    client_ip: Option<IpAddr>,
+    scheme: Option<Scheme>,
+}
+
+impl ConnectInfo {
+    /// Creates the connection info of a plain HTTP/1.1 connection.
+    pub fn new() -> Self {
+        Self {
+            peer_addr: None,
+            local_addr: None,
+            scheme: Scheme::HTTP,
+            version: Version::HTTP_11,
+            server_name: None,
+            forwarded: None,
+        }
+    }
+
+    /// Sets the HTTP version of the request.
+    pub fn with_version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the address of the remote end of the connection.
+    pub fn with_peer_addr(mut self, addr: SocketAddr) -> Self {
+        self.peer_addr = Some(addr);
+        self
+    }
+
+    /// Sets the address of the local end of the connection.
+    pub fn with_local_addr(mut self, addr: SocketAddr) -> Self {
+        self.local_addr = Some(addr);
+        self
+    }
+
+    /// Sets the scheme of the connection, `https` for TLS connections.
+    pub fn with_scheme(mut self, scheme: Scheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    /// Sets the server name the client requested with TLS SNI.
+    pub fn with_server_name(mut self, name: impl Into<String>) -> Self {
+        self.server_name = Some(name.into());
+        self
+    }
+
+    /// Returns the address of the remote end of the connection.
+    ///
+    /// This is the address of the last proxy when the application runs behind one, see [`ConnectInfo::client_ip`].
+    /// `None` when the connection has no IP address, e.g. a Unix domain socket.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Returns the address of the local end of the connection.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Returns the scheme of the request.
+    ///
+    /// When the request was forwarded by a trusted proxy, this is the scheme the client used to connect to the proxy.
+    pub fn scheme(&self) -> &Scheme {
+        self.forwarded
+            .as_ref()
+            .and_then(|forwarded| forwarded.scheme.as_ref())
+            .unwrap_or(&self.scheme)
+    }
+
+    /// Returns the HTTP version of the request.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns the server name the client requested with TLS SNI, if any.
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    /// Returns the IP address of the client.
+    ///
+    /// When the application trusts proxies (see [`NgynEngine::trust_proxies`](crate::core::engine::NgynEngine::trust_proxies))
+    /// and the request was forwarded by one of them, the address is resolved from the `Forwarded` or `X-Forwarded-For` headers.
+    /// Otherwise, this is the IP address of the peer.
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        match &self.forwarded {
+            Some(Forwarded {
+                client_ip: Some(ip),
+                ..
+            }) => Some(*ip),
+            _ => self.peer_addr.map(|addr| addr.ip()),
+        }
+    }
+
+    /// Resolves the client information from the forwarding headers, if the peer is a trusted proxy.
+    ///
+    /// Connections without a peer address, e.g. Unix domain sockets, are local and considered trusted.
+    pub(crate) fn resolve_forwarded(&mut self, headers: &HeaderMap, proxies: &TrustedProxies) {
+        if let Some(addr) = self.peer_addr {
+            if !proxies.contains(addr.ip()) {
+                return;
+            }
+        }
+
+        let (hops, schemes) = if headers.contains_key(http::header::FORWARDED) {
+            parse_forwarded(headers)
+        } else {
+            parse_x_forwarded(headers)
+        };
+
+        // walk the hops from the closest proxy, the client is the first untrusted address
+        let mut client_ip = None;
+        for hop in hops.iter().rev() {
+            match hop {
+                Some(ip) => {
+                    client_ip = Some(*ip);
+                    if !proxies.contains(*ip) {
+                        break;
+                    }
+                }
+                // obfuscated or unknown addresses can't be trusted to forward anything further
+                None => break,
+            }
+        }
+
+        self.forwarded = Some(Forwarded {
+            client_ip,
+            scheme: schemes.last().cloned(),
+        });
+    }
+}
+
+impl Default for ConnectInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transformer<'_> for ConnectInfo {
+    /// Transforms the given `NgynContext` into a `ConnectInfo` instance.
+    ///
+    /// # Panics
+    /// Panics if the platform doesn't provide connection info.
+    fn transform(cx: &mut NgynContext) -> Self {
+        cx.request()
+            .extensions()
+            .get::<ConnectInfo>()
+            .cloned()
+            .expect("ConnectInfo is not provided by this platform")
+    }
+}
+
+/// Parses the hops and schemes of the `Forwarded` headers.
+fn parse_forwarded(headers: &HeaderMap) -> (Vec<Option<IpAddr>>, Vec<Scheme>) {
+    let mut hops = Vec::new();
+    let mut schemes = Vec::new();
+
+    let elements = headers
+        .get_all(http::header::FORWARDED)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+
+    for element in elements {
+        for pair in element.split(';') {
+            let Some((name, value)) = pair.split_once('=') else {
+                continue;
+            };
+            let value = value.trim().trim_matches('"');
+            match name.trim().to_ascii_lowercase().as_str() {
+                "for" => hops.push(parse_node(value)),
+                "proto" => schemes.extend(Scheme::from_str(value).ok()),
+                _ => {}
+            }
+        }
+    }
+
+    (hops, schemes)
+}
+
+/// Parses the hops and schemes of the `X-Forwarded-For` and `X-Forwarded-Proto` headers.
+fn parse_x_forwarded(headers: &HeaderMap) -> (Vec<Option<IpAddr>>, Vec<Scheme>) {
+    let values = |name: &'static str| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>()
+    };
+
+    let hops = values("x-forwarded-for")
+        .into_iter()
+        .map(parse_node)
+        .collect();
+    let schemes = values("x-forwarded-proto")
+        .into_iter()
+        .filter_map(|value| Scheme::from_str(value).ok())
+        .collect();
+
+    (hops, schemes)
+}
+
+/// Parses a forwarded node, an IP address optionally followed by a port.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Some(rest) = node.strip_prefix('[') {
+        // an IPv6 address, with or without a port
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    node.parse::<IpAddr>()
+        .or_else(|_| node.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .ok()
+}
+
+/// The networks of the proxies an application trusts to forward client information.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// app.trust_proxies(TrustedProxies::new(["10.0.0.0/8", "::1"])?);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    networks: Vec<(IpAddr, u8)>,
+}
+
+impl TrustedProxies {
+    /// Creates trusted proxies from a list of networks.
+    ///
+    /// ### Arguments
+    ///
+    /// * `networks` - IP addresses, or networks in CIDR notation, e.g. `10.0.0.0/8`.
+    ///
+    /// ### Returns
+    ///
+    /// An error if one of the networks is invalid.
+    pub fn new<I, S>(networks: I) -> Result<Self, InvalidNetwork>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let networks = networks
+            .into_iter()
+            .map(|network| parse_network(network.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { networks })
+    }
+
+    /// Trusts proxies running on the same host, i.e. loopback addresses.
+    pub fn loopback() -> Self {
+        Self {
+            networks: vec![
+                (IpAddr::from([127, 0, 0, 0]), 8),
+                (IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1]), 128),
+            ],
+        }
+    }
+
+    /// Checks if an address belongs to one of the trusted networks.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.networks
+            .iter()
+            .any(|(network, prefix)| match (network, ip) {
+                (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                    let mask = u32::MAX.checked_shl(32 - *prefix as u32).unwrap_or(0);
+                    u32::from(*network) & mask == u32::from(ip) & mask
+                }
+                (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                    let mask = u128::MAX.checked_shl(128 - *prefix as u32).unwrap_or(0);
+                    u128::from(*network) & mask == u128::from(ip) & mask
+                }
+                _ => false,
+            })
+    }
+}
+
+/// Parses a network in CIDR notation, or a single IP address.
+fn parse_network(network: &str) -> Result<(IpAddr, u8), InvalidNetwork> {
+    let invalid = || InvalidNetwork(network.to_string());
+    let (ip, prefix) = match network.split_once('/') {
+        Some((ip, prefix)) => (ip, Some(prefix)),
+        None => (network, None),
+    };
+
+    let ip = ip.trim().parse::<IpAddr>().map_err(|_| invalid())?;
+    let max = if ip.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix.trim().parse::<u8>().map_err(|_| invalid())?,
+        None => max,
+    };
+
+    if prefix > max {
+        return Err(invalid());
+    }
+    Ok((ip.to_canonical(), prefix))
+}
+
+/// An error returned when a trusted proxy network can't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidNetwork(String);
+
+impl fmt::Display for InvalidNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid proxy network `{}`", self.0)
+    }
+}
+
+impl std::error::Error for InvalidNetwork {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(
+        peer: &str,
+        headers: &[(&'static str, &str)],
+        proxies: &TrustedProxies,
+    ) -> ConnectInfo {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(*name, value.parse().unwrap());
+        }
+        let mut info = ConnectInfo::new().with_peer_addr(peer.parse().unwrap());
+        info.resolve_forwarded(&map, proxies);
+        info
+    }
+
+    #[test]
+    fn test_trusted_proxies() {
+        let proxies = TrustedProxies::new(["10.0.0.0/8", "192.168.1.1", "fd00::/8"]).unwrap();
+
+        assert!(proxies.contains("10.1.2.3".parse().unwrap()));
+        assert!(proxies.contains("192.168.1.1".parse().unwrap()));
+        assert!(!proxies.contains("192.168.1.2".parse().unwrap()));
+        assert!(proxies.contains("fd12::1".parse().unwrap()));
+        assert!(proxies.contains("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(!proxies.contains("11.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_invalid_network() {
+        assert_eq!(
+            TrustedProxies::new(["10.0.0.0/33"])
+                .unwrap_err()
+                .to_string(),
+            "invalid proxy network `10.0.0.0/33`"
+        );
+        assert!(TrustedProxies::new(["localhost"]).is_err());
+    }
+
+    #[test]
+    fn test_untrusted_peer() {
+        let info = resolve(
+            "203.0.113.1:4000",
+            &[("x-forwarded-for", "198.51.100.7")],
+            &TrustedProxies::loopback(),
+        );
+        assert_eq!(info.client_ip(), Some("203.0.113.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_x_forwarded_for() {
+        let proxies = TrustedProxies::new(["127.0.0.1", "10.0.0.0/8"]).unwrap();
+        let info = resolve(
+            "127.0.0.1:4000",
+            &[
+                ("x-forwarded-for", "1.1.1.1, 198.51.100.7"),
+                ("x-forwarded-for", "10.0.0.2"),
+                ("x-forwarded-proto", "https"),
+            ],
+            &proxies,
+        );
+
+        // the spoofable leftmost address is ignored
+        assert_eq!(info.client_ip(), Some("198.51.100.7".parse().unwrap()));
+        assert_eq!(info.scheme(), &Scheme::HTTPS);
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let info = resolve(
+            "127.0.0.1:4000",
+            &[(
+                "forwarded",
+                "for=198.51.100.7;proto=https, for=\"[2001:db8::1]:4711\"",
+            )],
+            &TrustedProxies::loopback(),
+        );
+        assert_eq!(info.client_ip(), Some("2001:db8::1".parse().unwrap()));
+        assert_eq!(info.scheme(), &Scheme::HTTPS);
+    }
+
+    #[test]
+    fn test_unknown_hop() {
+        let info = resolve(
+            "127.0.0.1:4000",
+            &[("forwarded", "for=198.51.100.7, for=unknown")],
+            &TrustedProxies::loopback(),
+        );
+        assert_eq!(info.client_ip(), Some("127.0.0.1".parse().unwrap()));
+    }
+}
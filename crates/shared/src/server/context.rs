@@ -1,9 +1,20 @@
 use http::Request;
 use matchit::Params;
 use serde::{Deserialize, Serialize};
-use std::{any::Any, collections::HashMap, mem::ManuallyDrop, sync::Arc};
-
-use crate::server::{NgynRequest, NgynResponse, Transformer};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    ops::Deref,
+    sync::Arc,
+};
+
+use crate::{
+    core::container::{Container, Dependency, Resolver},
+    server::{
+        cookies::{CookieJar, Key},
+        Extensions, NgynRequest, NgynResponse, Transformer,
+    },
+};
 
 /// Represents the value of a context in Ngyn
 #[derive(Serialize, Deserialize)]
@@ -18,39 +29,75 @@ impl<V> NgynContextValue<V> {
 }
 
 /// Represents the state of an application in Ngyn
-
+///
+/// States are shared between concurrent requests, so they can only be accessed through shared references.
+/// Values that need to change should use interior mutability (e.g. `Mutex`, `RwLock` or atomics).
 pub trait AppState: Any + Send + Sync + 'static {
-    fn as_any(&self) -> &dyn Any;
-    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// Returns the key used to sign and encrypt cookies.
+    ///
+    /// When deriving `AppState`, this is the field marked with `#[cookie_key]`.
+    fn cookie_key(&self) -> Option<&Key> {
+        None
+    }
 }
 
 impl<T: AppState> AppState for Box<T> {
-    fn as_any(&self) -> &dyn Any {
-        self.as_ref()
+    fn cookie_key(&self) -> Option<&Key> {
+        self.as_ref().cookie_key()
     }
+}
 
-    fn as_any_mut(&mut self) -> &mut dyn Any {
-        self.as_mut()
+/// Extracts a state of type `T` as a shared handle.
+///
+/// Unlike `&T`, the handle isn't tied to the context, so it can be moved into spawned tasks.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// #[derive(AppState)]
+/// struct Counter {
+///     hits: AtomicUsize,
+/// }
+///
+/// #[handler]
+/// fn hit(counter: State<Counter>) -> String {
+///     let hits = counter.hits.fetch_add(1, Ordering::Relaxed);
+///     format!("{} hits", hits + 1)
+/// }
+/// ```
+pub struct State<T>(pub Arc<T>);
+
+impl<T> Clone for State<T> {
+    fn clone(&self) -> Self {
+        State(self.0.clone())
     }
 }
 
-/// # Panics
-/// Panics if the state has been dropped. This should never happen unless the state is dropped manually.
-impl From<&Arc<Box<dyn AppState>>> for Box<dyn AppState> {
-    fn from(value: &Arc<Box<dyn AppState>>) -> Self {
-        // creating a clone is essential since this ref will be dropped after this function returns
-        let arc_clone = value.clone();
-        let state_ref: &dyn AppState = &**arc_clone;
+impl<T> Deref for State<T> {
+    type Target = T;
 
-        let state_ptr: *const dyn AppState = state_ref as *const dyn AppState;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: AppState> Transformer<'_> for State<T> {
+    /// Transforms the given `NgynContext` into a `State` instance.
+    ///
+    /// # Panics
+    /// Panics if no state of type `T` has been added to the application.
+    fn transform(cx: &mut NgynContext) -> Self {
+        Resolver::new(cx).state::<T>()
+    }
 
-        // SAFETY: state_ptr is never null, it is safe to convert it to a NonNull pointer, this way we can safely convert it back to a Box
-        // If it is ever found as null, this is a bug. It probably means the memory has been poisoned
-        let nn_ptr = std::ptr::NonNull::new(state_ptr as *mut dyn AppState)
-            .expect("State has been dropped, but this should never happen, ensure it is being cloned correctly."); // This should never happen, if it does, it's a bug
-        let raw_ptr = nn_ptr.as_ptr();
+    fn is_available(cx: &NgynContext) -> bool {
+        cx.state::<T>().is_some()
+    }
 
-        unsafe { Box::from_raw(raw_ptr) }
+    fn required_state() -> Option<Dependency> {
+        Some(Dependency::state::<T>())
     }
 }
 
@@ -60,7 +107,12 @@ pub struct NgynContext<'a> {
     pub(crate) response: NgynResponse,
     pub(crate) params: Option<Params<'a, 'a>>,
     store: HashMap<&'a str, String>,
-    pub(crate) state: Option<ManuallyDrop<Box<dyn AppState>>>,
+    pub(crate) states: Arc<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+    pub(crate) container: Arc<Container>,
+    pub(crate) scoped: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+    pub(crate) cookies: CookieJar,
+    extensions: Extensions,
+    halted: bool,
 }
 
 impl<'a> NgynContext<'a> {
@@ -130,18 +182,33 @@ impl<'a> NgynContext<'a> {
     pub fn params(&self) -> Option<&Params<'a, 'a>> {
         self.params.as_ref()
     }
-}
 
-impl NgynContext<'_> {
-    /// Retrieves the state of the context as a reference to the specified type.
+    /// Retrieves the cookies associated with the context.
     ///
-    /// # Type Parameters
+    /// ### Returns
     ///
-    /// * `T` - The type of the state to retrieve.
+    /// A reference to the cookie jar of the request.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn_shared::core::context::NgynContext;
+    ///
+    /// let context = NgynContext::from_request(request);
+    ///
+    /// let theme = context.cookies().get("theme");
+    /// ```
+    pub fn cookies(&self) -> &CookieJar {
+        &self.cookies
+    }
+
+    /// Retrieves the cookies associated with the context.
+    ///
+    /// Changes made to the jar are sent back with the response.
     ///
     /// ### Returns
     ///
-    /// An optional reference to the state of the specified type. Returns `None` if the state is not found or if it cannot be downcasted to the specified type.
+    /// A mutable reference to the cookie jar of the request.
     ///
     /// ### Examples
     ///
@@ -150,16 +217,83 @@ impl NgynContext<'_> {
     ///
     /// let mut context = NgynContext::from_request(request);
     ///
-    /// let state_ref = context.state::<TestAppState>();
+    /// context.cookies_mut().add(("theme", "dark"));
     /// ```
-    pub fn state<T: 'static>(&self) -> Option<&T> {
-        match &self.state {
-            Some(value) => value.as_any().downcast_ref::<T>(),
-            None => None,
-        }
+    pub fn cookies_mut(&mut self) -> &mut CookieJar {
+        &mut self.cookies
+    }
+
+    /// Retrieves the extensions associated with the context.
+    ///
+    /// ### Returns
+    ///
+    /// A reference to the values stored in the context, keyed by their type.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn_shared::core::context::NgynContext;
+    ///
+    /// let mut context = NgynContext::from_request(request);
+    /// context.extensions_mut().insert(User { id: 1 });
+    ///
+    /// let user = context.extensions().get::<User>().unwrap();
+    /// ```
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
     }
 
-    /// Retrieves the state of the context as a mutable reference to the specified type.
+    /// Retrieves the extensions associated with the context.
+    ///
+    /// Unlike [`NgynContext::set`], values don't have to be serializable.
+    ///
+    /// ### Returns
+    ///
+    /// A mutable reference to the values stored in the context, keyed by their type.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn_shared::core::context::NgynContext;
+    ///
+    /// let mut context = NgynContext::from_request(request);
+    /// context.extensions_mut().insert(User { id: 1 });
+    /// ```
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Stops the handling of the request, the response is sent as it is.
+    ///
+    /// Middlewares added after the one halting the request and the route handler aren't run.
+    /// Middlewares that already ran still process the response once the request is halted.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust ignore
+    /// use ngyn::prelude::*;
+    ///
+    /// struct Maintenance;
+    ///
+    /// impl NgynMiddleware for Maintenance {
+    ///     async fn handle(cx: &mut NgynContext<'_>) {
+    ///         *cx.response_mut().status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    ///         cx.halt();
+    ///     }
+    /// }
+    /// ```
+    pub fn halt(&mut self) {
+        self.halted = true;
+    }
+
+    /// Checks if the handling of the request has been halted with [`NgynContext::halt`].
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+}
+
+impl NgynContext<'_> {
+    /// Retrieves the state of the context as a reference to the specified type.
     ///
     /// # Type Parameters
     ///
@@ -167,7 +301,7 @@ impl NgynContext<'_> {
     ///
     /// ### Returns
     ///
-    /// An optional reference to the state of the specified type. Returns `None` if the state is not found or if it cannot be downcasted to the specified type.
+    /// An optional reference to the state of the specified type. Returns `None` if no state of the specified type has been added to the application.
     ///
     /// ### Examples
     ///
@@ -178,11 +312,10 @@ impl NgynContext<'_> {
     ///
     /// let state_ref = context.state::<TestAppState>();
     /// ```
-    pub fn state_mut<T: 'static>(&mut self) -> Option<&mut T> {
-        match &mut self.state {
-            Some(value) => value.as_any_mut().downcast_mut::<T>(),
-            None => None,
-        }
+    pub fn state<T: 'static>(&self) -> Option<&T> {
+        self.states
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
     }
 }
 
@@ -374,11 +507,16 @@ impl NgynContext<'_> {
     /// ```
     pub(crate) fn from_request(request: Request<Vec<u8>>) -> Self {
         NgynContext {
+            cookies: CookieJar::from_headers(request.headers()),
             request,
             response: NgynResponse::default(),
             store: HashMap::new(),
             params: None,
-            state: None,
+            states: Arc::default(),
+            container: Arc::default(),
+            scoped: HashMap::new(),
+            extensions: Extensions::default(),
+            halted: false,
         }
     }
 }
@@ -414,15 +552,7 @@ mod tests {
     struct TestAppState {
         value: u128,
     }
-    impl AppState for TestAppState {
-        fn as_any(&self) -> &dyn Any {
-            self
-        }
-
-        fn as_any_mut(&mut self) -> &mut dyn Any {
-            self
-        }
-    }
+    impl AppState for TestAppState {}
 
     #[test]
     fn test_request() {
@@ -433,6 +563,18 @@ mod tests {
         assert_eq!(request_ref.method(), Method::GET);
     }
 
+    #[test]
+    fn test_state_is_available() {
+        let mut context = NgynContext::from_request(Request::new(Vec::new()));
+        assert!(!State::<TestAppState>::is_available(&context));
+
+        Arc::make_mut(&mut context.states).insert(
+            TypeId::of::<TestAppState>(),
+            Arc::new(TestAppState { value: 1 }),
+        );
+        assert!(State::<TestAppState>::is_available(&context));
+    }
+
     #[test]
     fn test_state() {
         let request = Request::new(Vec::new());
@@ -441,44 +583,62 @@ mod tests {
         let state_ref = context.state::<TestAppState>();
         assert!(state_ref.is_none());
 
-        context.state = Some(ManuallyDrop::new(Box::new(TestAppState { value: 1 })));
+        Arc::make_mut(&mut context.states).insert(
+            TypeId::of::<TestAppState>(),
+            Arc::new(TestAppState { value: 1 }),
+        );
 
         let state_ref = context.state::<TestAppState>();
-        assert!(state_ref.is_some());
+        assert_eq!(state_ref.unwrap().value, 1);
+        assert!(context.state::<u128>().is_none());
     }
 
     #[test]
-    fn test_state_mut() {
+    fn test_state_transform() {
         let request = Request::new(Vec::new());
         let mut context = NgynContext::from_request(request);
-        context.state = Some(ManuallyDrop::new(Box::new(TestAppState { value: 1 })));
-
-        let state_ref = context.state_mut::<TestAppState>();
-        assert!(state_ref.is_some());
+        let state = Arc::new(TestAppState { value: 1 });
+        Arc::make_mut(&mut context.states).insert(TypeId::of::<TestAppState>(), state.clone());
 
-        state_ref.unwrap().value = 2;
+        let extracted = State::<TestAppState>::transform(&mut context);
+        assert_eq!(extracted.value, 1);
+        // the state is shared, not copied
+        assert!(Arc::ptr_eq(&extracted.0, &state));
+    }
 
-        let state_ref = context.state::<TestAppState>();
-        assert_eq!(state_ref.unwrap().value, 2);
+    #[test]
+    #[should_panic(expected = "has not been added")]
+    fn test_state_transform_missing() {
+        let request = Request::new(Vec::new());
+        let mut context = NgynContext::from_request(request);
+        let _ = State::<TestAppState>::transform(&mut context);
     }
 
     #[test]
     fn test_box_state_impl() {
-        let mut state = Box::new(TestAppState { value: 42 });
-
-        // Test as_any
-        let any_ref = state.as_any();
-        let downcast_result = any_ref.downcast_ref::<TestAppState>();
-        assert!(downcast_result.is_some());
-        let result = downcast_result.unwrap();
-        assert_eq!(result.value, 42);
-
-        // Test as_any_mut
-        let any_mut_ref = state.as_any_mut();
-        let downcast_mut_result = any_mut_ref.downcast_mut::<TestAppState>();
-        assert!(downcast_mut_result.is_some());
-        downcast_mut_result.unwrap().value = 99;
-        assert_eq!(state.value, 99);
+        struct KeyedState(Key);
+        impl AppState for KeyedState {
+            fn cookie_key(&self) -> Option<&Key> {
+                Some(&self.0)
+            }
+        }
+
+        let key = Key::generate();
+        let state = Box::new(KeyedState(key.clone()));
+        assert!(state.cookie_key() == Some(&key));
+        assert!(Box::new(TestAppState { value: 42 }).cookie_key().is_none());
+    }
+
+    #[test]
+    fn test_extensions() {
+        let request = Request::new(Vec::new());
+        let mut context = NgynContext::from_request(request);
+        context.extensions_mut().insert(TestAppState { value: 1 });
+
+        let value = context.extensions().get::<TestAppState>().unwrap().value;
+        assert_eq!(value, 1);
+        // extensions are kept apart from the serialized store
+        assert!(context.is_empty());
     }
 
     #[test]
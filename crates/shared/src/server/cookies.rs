@@ -0,0 +1,285 @@
+use http::{
+    header::{COOKIE, SET_COOKIE},
+    HeaderMap, HeaderValue,
+};
+
+pub use cookie::{time, Cookie, CookieBuilder, Expiration, Key, PrivateJar, SameSite, SignedJar};
+
+use crate::server::{NgynContext, Transformer};
+
+/// Represents the cookies of a request, along with the changes to be sent back in its response.
+///
+/// Cookies added or removed through the jar are written to the response as `Set-Cookie` headers
+/// once the request has been handled.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// #[handler]
+/// fn visit(jar: &mut CookieJar) -> String {
+///     let visits = jar
+///         .get("visits")
+///         .and_then(|cookie| cookie.value().parse::<u32>().ok())
+///         .unwrap_or_default();
+///
+///     jar.add(
+///         Cookie::build(("visits", (visits + 1).to_string()))
+///             .http_only(true)
+///             .same_site(SameSite::Lax),
+///     );
+///
+///     format!("You have visited {} times", visits)
+/// }
+/// ```
+#[derive(Default)]
+pub struct CookieJar {
+    jar: cookie::CookieJar,
+    key: Option<Key>,
+}
+
+impl CookieJar {
+    /// Creates a new `CookieJar` from the `Cookie` headers of a request.
+    ///
+    /// Cookies that cannot be parsed are ignored.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let mut jar = cookie::CookieJar::new();
+
+        for header in headers.get_all(COOKIE) {
+            if let Ok(header) = header.to_str() {
+                for cookie in Cookie::split_parse_encoded(header.to_owned()).flatten() {
+                    jar.add_original(cookie);
+                }
+            }
+        }
+
+        CookieJar { jar, key: None }
+    }
+
+    /// Sets the key used to sign and encrypt cookies.
+    pub(crate) fn set_key(&mut self, key: Option<Key>) {
+        self.key = key;
+    }
+
+    /// Checks if the app state provides a key to sign and encrypt cookies.
+    pub fn has_key(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Retrieves the cookie with the given name.
+    ///
+    /// ### Arguments
+    ///
+    /// * `name` - The name of the cookie.
+    ///
+    /// ### Returns
+    ///
+    /// An optional reference to the cookie. Returns `None` if the cookie doesn't exist or has been removed.
+    pub fn get(&self, name: &str) -> Option<&Cookie<'static>> {
+        self.jar.get(name)
+    }
+
+    /// Adds a cookie to the jar, it will be sent to the client with the response.
+    ///
+    /// ### Arguments
+    ///
+    /// * `cookie` - The cookie to add, can be a [`Cookie`], a [`CookieBuilder`] or a `(name, value)` tuple.
+    pub fn add<C: Into<Cookie<'static>>>(&mut self, cookie: C) {
+        self.jar.add(cookie);
+    }
+
+    /// Removes a cookie from the jar.
+    ///
+    /// If the cookie was sent with the request, the client is instructed to remove it.
+    /// Cookies set with a `path` or `domain` must be removed with the same `path` or `domain`.
+    ///
+    /// ### Arguments
+    ///
+    /// * `cookie` - The cookie to remove, can be a [`Cookie`] or the name of the cookie.
+    pub fn remove<C: Into<Cookie<'static>>>(&mut self, cookie: C) {
+        self.jar.remove(cookie);
+    }
+
+    /// Returns an iterator over all the cookies in the jar.
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie<'static>> {
+        self.jar.iter()
+    }
+
+    /// Returns a read-only jar of signed cookies.
+    ///
+    /// Signed cookies can be read by the client, but can't be tampered with.
+    ///
+    /// # Panics
+    /// Panics if the app state doesn't provide a cookie key.
+    pub fn signed(&self) -> SignedJar<&cookie::CookieJar> {
+        self.jar.signed(self.key())
+    }
+
+    /// Returns a jar of signed cookies, cookies added to it are signed before they are sent.
+    ///
+    /// # Panics
+    /// Panics if the app state doesn't provide a cookie key.
+    pub fn signed_mut(&mut self) -> SignedJar<&mut cookie::CookieJar> {
+        let key = self.key().clone();
+        self.jar.signed_mut(&key)
+    }
+
+    /// Returns a read-only jar of private cookies.
+    ///
+    /// Private cookies are encrypted, they can neither be read nor tampered with by the client.
+    ///
+    /// # Panics
+    /// Panics if the app state doesn't provide a cookie key.
+    pub fn private(&self) -> PrivateJar<&cookie::CookieJar> {
+        self.jar.private(self.key())
+    }
+
+    /// Returns a jar of private cookies, cookies added to it are encrypted before they are sent.
+    ///
+    /// # Panics
+    /// Panics if the app state doesn't provide a cookie key.
+    pub fn private_mut(&mut self) -> PrivateJar<&mut cookie::CookieJar> {
+        let key = self.key().clone();
+        self.jar.private_mut(&key)
+    }
+
+    fn key(&self) -> &Key {
+        self.key.as_ref().expect(
+            "A cookie key is required, mark a `Key` field of your app state with `#[cookie_key]`",
+        )
+    }
+
+    /// Writes the changes made to the jar as `Set-Cookie` headers.
+    pub(crate) fn write_to(&self, headers: &mut HeaderMap) {
+        for cookie in self.jar.delta() {
+            if let Ok(value) = HeaderValue::from_str(&cookie.encoded().to_string()) {
+                headers.append(SET_COOKIE, value);
+            }
+        }
+    }
+}
+
+impl<'a> Transformer<'a> for &'a CookieJar {
+    fn transform(cx: &'a mut NgynContext) -> Self {
+        cx.cookies()
+    }
+}
+
+impl<'a> Transformer<'a> for &'a mut CookieJar {
+    fn transform(cx: &'a mut NgynContext) -> Self {
+        cx.cookies_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cookie::time::Duration;
+
+    fn jar_from(cookies: &str) -> CookieJar {
+        let mut headers = HeaderMap::new();
+        headers.insert(COOKIE, HeaderValue::from_str(cookies).unwrap());
+        CookieJar::from_headers(&headers)
+    }
+
+    #[test]
+    fn test_from_headers() {
+        let jar = jar_from("name=John; theme=dark%20blue");
+
+        assert_eq!(jar.get("name").unwrap().value(), "John");
+        assert_eq!(jar.get("theme").unwrap().value(), "dark blue");
+        assert!(jar.get("age").is_none());
+    }
+
+    #[test]
+    fn test_original_cookies_are_not_written() {
+        let jar = jar_from("name=John");
+        let mut headers = HeaderMap::new();
+        jar.write_to(&mut headers);
+
+        assert!(headers.get(SET_COOKIE).is_none());
+    }
+
+    #[test]
+    fn test_add() {
+        let mut jar = CookieJar::default();
+        jar.add(
+            Cookie::build(("session", "abc"))
+                .http_only(true)
+                .secure(true)
+                .same_site(SameSite::Strict)
+                .max_age(Duration::hours(1)),
+        );
+
+        let mut headers = HeaderMap::new();
+        jar.write_to(&mut headers);
+
+        let header = headers.get(SET_COOKIE).unwrap().to_str().unwrap();
+        assert!(header.starts_with("session=abc"));
+        assert!(header.contains("HttpOnly"));
+        assert!(header.contains("Secure"));
+        assert!(header.contains("SameSite=Strict"));
+        assert!(header.contains("Max-Age=3600"));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut jar = jar_from("name=John");
+        jar.remove("name");
+
+        let mut headers = HeaderMap::new();
+        jar.write_to(&mut headers);
+
+        assert!(jar.get("name").is_none());
+        let header = headers.get(SET_COOKIE).unwrap().to_str().unwrap();
+        assert!(header.starts_with("name=;"));
+        assert!(header.contains("Max-Age=0"));
+    }
+
+    #[test]
+    fn test_signed() {
+        let key = Key::generate();
+        let mut jar = CookieJar::default();
+        jar.set_key(Some(key.clone()));
+        jar.signed_mut().add(("user", "42"));
+
+        let mut headers = HeaderMap::new();
+        jar.write_to(&mut headers);
+        let header = headers.get(SET_COOKIE).unwrap().to_str().unwrap();
+        assert_ne!(header, "user=42");
+
+        let mut jar = jar_from(header);
+        jar.set_key(Some(key.clone()));
+        assert_eq!(jar.signed().get("user").unwrap().value(), "42");
+
+        let mut tampered = jar_from(&header.replace("42", "43"));
+        tampered.set_key(Some(key));
+        assert!(tampered.signed().get("user").is_none());
+    }
+
+    #[test]
+    fn test_private() {
+        let key = Key::generate();
+        let mut jar = CookieJar::default();
+        jar.set_key(Some(key.clone()));
+        jar.private_mut().add(("secret", "value"));
+
+        let mut headers = HeaderMap::new();
+        jar.write_to(&mut headers);
+        let header = headers.get(SET_COOKIE).unwrap().to_str().unwrap();
+        assert!(!header.contains("value"));
+
+        let mut jar = jar_from(header);
+        jar.set_key(Some(key));
+        assert_eq!(jar.private().get("secret").unwrap().value(), "value");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_signed_without_key() {
+        let jar = CookieJar::default();
+        assert!(!jar.has_key());
+        let _ = jar.signed();
+    }
+}
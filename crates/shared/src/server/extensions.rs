@@ -0,0 +1,217 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+};
+
+use crate::server::{NgynContext, Transformer};
+
+/// A map of values keyed by their type.
+///
+/// Extensions are used to pass values from middlewares and gates down to route handlers,
+/// values are stored as-is and don't need to be serializable.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn_shared::server::extensions::Extensions;
+///
+/// struct User {
+///     id: u32,
+/// }
+///
+/// let mut extensions = Extensions::default();
+/// extensions.insert(User { id: 1 });
+///
+/// assert_eq!(extensions.get::<User>().unwrap().id, 1);
+/// ```
+#[derive(Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    /// Inserts a value into the extensions.
+    ///
+    /// ### Returns
+    ///
+    /// The previous value of the same type, if any.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.map
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Retrieves a reference to the value of the specified type.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Retrieves a mutable reference to the value of the specified type.
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.map
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// Removes the value of the specified type from the extensions, and returns it.
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+            .map(|value| *value)
+    }
+
+    /// Checks if the extensions contain a value of the specified type.
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Removes all values from the extensions.
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// Returns the number of values in the extensions.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Checks if the extensions are empty.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// Extracts a value of type `T` from the extensions of the context.
+///
+/// The value is cloned out of the extensions, so it can be extracted by several arguments, gates or handlers.
+/// Handlers respond with `500 Internal Server Error` when no value of type `T` has been inserted.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::prelude::*;
+///
+/// #[derive(Clone)]
+/// struct CurrentUser {
+///     name: String,
+/// }
+///
+/// struct AuthMiddleware;
+///
+/// impl NgynMiddleware for AuthMiddleware {
+///     async fn handle(cx: &mut NgynContext<'_>) {
+///         cx.extensions_mut().insert(CurrentUser { name: "John".to_string() });
+///     }
+/// }
+///
+/// #[handler]
+/// fn greet(Extension(user): Extension<CurrentUser>) -> String {
+///     format!("Hello {}", user.name)
+/// }
+/// ```
+pub struct Extension<T>(pub T);
+
+impl<T> Deref for Extension<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Extension<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Transformer<'_> for Extension<T> {
+    /// Transforms the given `NgynContext` into an `Extension` instance.
+    ///
+    /// # Panics
+    /// Panics if no value of type `T` has been inserted into the extensions.
+    fn transform(cx: &mut NgynContext) -> Self {
+        match cx.extensions().get::<T>() {
+            Some(value) => Extension(value.clone()),
+            None => panic!("Extension of type `{}` was not found", type_name::<T>()),
+        }
+    }
+
+    fn is_available(cx: &NgynContext) -> bool {
+        cx.extensions().contains::<T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct User {
+        id: u32,
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut extensions = Extensions::default();
+        assert_eq!(extensions.insert(User { id: 1 }), None);
+        assert_eq!(extensions.insert(User { id: 2 }), Some(User { id: 1 }));
+        assert_eq!(extensions.len(), 1);
+    }
+
+    #[test]
+    fn test_get() {
+        let mut extensions = Extensions::default();
+        extensions.insert(User { id: 1 });
+        extensions.insert(42u8);
+
+        assert_eq!(extensions.get::<User>(), Some(&User { id: 1 }));
+        assert_eq!(extensions.get::<u8>(), Some(&42));
+        assert_eq!(extensions.get::<u16>(), None);
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut extensions = Extensions::default();
+        extensions.insert(User { id: 1 });
+        extensions.get_mut::<User>().unwrap().id = 2;
+
+        assert_eq!(extensions.get::<User>(), Some(&User { id: 2 }));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut extensions = Extensions::default();
+        extensions.insert(User { id: 1 });
+
+        assert_eq!(extensions.remove::<User>(), Some(User { id: 1 }));
+        assert!(!extensions.contains::<User>());
+        assert!(extensions.is_empty());
+    }
+
+    #[test]
+    fn test_extension_transform() {
+        let mut cx = NgynContext::from_request(http::Request::new(Vec::new()));
+        cx.extensions_mut().insert(User { id: 1 });
+
+        let Extension(user) = Extension::<User>::transform(&mut cx);
+        assert_eq!(user, User { id: 1 });
+        // the value is kept for later extractions
+        let Extension(user) = Extension::<User>::transform(&mut cx);
+        assert_eq!(user, User { id: 1 });
+    }
+
+    #[test]
+    fn test_extension_available() {
+        let mut cx = NgynContext::from_request(http::Request::new(Vec::new()));
+        assert!(!Extension::<User>::is_available(&cx));
+
+        cx.extensions_mut().insert(User { id: 1 });
+        assert!(Extension::<User>::is_available(&cx));
+    }
+}
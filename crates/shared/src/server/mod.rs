@@ -1,12 +1,18 @@
 pub mod body;
+pub mod connect_info;
 pub mod context;
+pub mod cookies;
+pub mod extensions;
 pub mod response;
 pub mod transformer;
 
 pub use self::response::{JsonResponse, JsonResult};
 pub use body::ToBytes;
 pub use bytes::Bytes;
-pub use context::NgynContext;
+pub use connect_info::{ConnectInfo, TrustedProxies};
+pub use context::{NgynContext, State};
+pub use cookies::{Cookie, CookieJar, SameSite};
+pub use extensions::{Extension, Extensions};
 pub use http::Method;
 use http_body_util::Full;
 pub use transformer::{Body, Param, Query, Transducer, Transformer};
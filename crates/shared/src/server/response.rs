@@ -1,6 +1,14 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
 use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
 use http::HeaderMap;
-use http_body_util::BodyExt;
+use http_body::Frame;
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt, StreamBody};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -120,6 +128,61 @@ impl<'a> Transformer<'a> for &'a mut HeaderMap {
     }
 }
 
+type Frames = Pin<Box<dyn Stream<Item = io::Result<Frame<Bytes>>> + Send>>;
+
+/// A response body streamed to the client chunk by chunk, e.g. a large file, rather than buffered in the response.
+///
+/// Platforms send the stream in place of the body of the response.
+/// Middlewares reading the body of responses, e.g. to compress or cache them, leave streamed responses as they are.
+///
+/// ### Examples
+///
+/// ```rust ignore
+/// use ngyn::shared::server::response::ResponseStream;
+/// use tokio_util::io::ReaderStream;
+///
+/// let file = tokio::fs::File::open("export.csv").await?;
+/// ResponseStream::set(cx.response_mut(), ReaderStream::new(file));
+/// ```
+#[derive(Clone)]
+pub struct ResponseStream(Arc<Mutex<Option<Frames>>>);
+
+impl ResponseStream {
+    /// Streams the body of a response, replacing its buffered body.
+    ///
+    /// Set the `Content-Length` header of the response when the length of the stream is known,
+    /// otherwise the body is sent with chunked encoding.
+    pub fn set<S>(res: &mut NgynResponse, stream: S)
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+    {
+        let frames: Frames = Box::pin(stream.map(|chunk| chunk.map(Frame::data)));
+        *res.body_mut() = Default::default();
+        res.extensions_mut()
+            .insert(ResponseStream(Arc::new(Mutex::new(Some(frames)))));
+    }
+
+    /// Checks if the body of a response is streamed.
+    pub fn is_set(res: &NgynResponse) -> bool {
+        res.extensions().get::<ResponseStream>().is_some()
+    }
+
+    /// Takes the streamed body of a response, for platforms to send it.
+    pub fn take(res: &mut NgynResponse) -> Option<UnsyncBoxBody<Bytes, io::Error>> {
+        let stream = res.extensions_mut().remove::<ResponseStream>()?;
+        let frames = stream.0.lock().unwrap_or_else(|e| e.into_inner()).take()?;
+        Some(BodyExt::boxed_unsync(StreamBody::new(frames)))
+    }
+
+    /// Converts a response into one with a boxed body, sending its stream when the body is streamed.
+    pub fn into_boxed(mut res: NgynResponse) -> http::Response<UnsyncBoxBody<Bytes, io::Error>> {
+        match ResponseStream::take(&mut res) {
+            Some(body) => res.map(|_| body),
+            None => res.map(|body| body.map_err(|never| match never {}).boxed_unsync()),
+        }
+    }
+}
+
 pub trait ReadBytes {
     #[allow(async_fn_in_trait)]
     /// Reads the bytes of a valid ngyn response body.
@@ -198,6 +261,23 @@ mod tests {
         assert_eq!(bytes, body);
     }
 
+    #[tokio::test]
+    async fn test_response_stream() {
+        let mut response = NgynResponse::default();
+        *response.body_mut() = Bytes::from("buffered").into();
+        let chunks = ["Hello, ", "world!"].map(|chunk| Ok(Bytes::from(chunk)));
+        ResponseStream::set(&mut response, futures_util::stream::iter(chunks));
+        assert!(ResponseStream::is_set(&response));
+        assert!(response.read_bytes().await.is_err());
+
+        let body = ResponseStream::take(&mut response).unwrap();
+        assert_eq!(body.collect().await.unwrap().to_bytes(), "Hello, world!");
+        assert!(ResponseStream::take(&mut response).is_none());
+
+        let body = ResponseStream::into_boxed(response).into_body();
+        assert_eq!(body.collect().await.unwrap().to_bytes(), "");
+    }
+
     #[tokio::test]
     async fn test_read_bytes() {
         let mut response = NgynResponse::default();
@@ -7,7 +7,7 @@ use http_body_util::{BodyStream, Full};
 use multer::Multipart;
 use serde::Deserialize;
 
-use crate::server::NgynContext;
+use crate::{core::container::Dependency, server::NgynContext};
 
 /// Represents a transformer trait.
 pub trait Transformer<'a> {
@@ -33,6 +33,27 @@ pub trait Transformer<'a> {
     fn transform(cx: &'a mut NgynContext) -> Self
     where
         Self: Sized;
+
+    /// Checks if the given `NgynContext` has what the transformer requires, e.g. a state.
+    ///
+    /// Handlers respond with `500 Internal Server Error` instead of transforming their arguments
+    /// when one of them isn't available.
+    fn is_available(_cx: &NgynContext) -> bool
+    where
+        Self: Sized,
+    {
+        true
+    }
+
+    /// The state the transformer requires, if any.
+    ///
+    /// The states required by the arguments of `#[handler]` functions are checked when the application starts.
+    fn required_state() -> Option<Dependency>
+    where
+        Self: Sized,
+    {
+        None
+    }
 }
 
 /// Represents a transducer struct.
@@ -1,9 +1,18 @@
+use http::uri::Scheme;
+use http_body_util::BodyExt;
 use ngyn_shared::{
     core::engine::{NgynHttpPlatform, PlatformData},
-    server::response::ReadBytes,
+    server::{
+        response::{ReadBytes, ResponseStream},
+        ConnectInfo,
+    },
 };
+use tokio::sync::OnceCell;
 use vercel_runtime::{Body, Error, Request, Response as VercelResponse};
 
+/// The outcome of the startup hooks, which run once per process, on a cold start.
+static STARTUP: OnceCell<Result<(), String>> = OnceCell::const_new();
+
 #[derive(Default)]
 pub struct VercelApplication {
     data: PlatformData,
@@ -16,14 +25,37 @@ impl NgynHttpPlatform for VercelApplication {
 }
 
 impl VercelApplication {
-    pub async fn handle(self, request: Request) -> Result<VercelResponse<Body>, Error> {
-        let request = request.map(|b| b.to_vec());
-        let mut response = self.data.respond(request).await;
+    /// Handles a request from the vercel runtime.
+    ///
+    /// The startup hooks of the first application handling a request run before it is handled,
+    /// the startup hooks of later applications are ignored.
+    /// Shutdown hooks are never run, the runtime doesn't notify functions before they are stopped.
+    ///
+    /// Requests have no peer address, as they are forwarded by the vercel proxy.
+    /// Use `app.trust_proxies(TrustedProxies::default())` to resolve the client address from `X-Forwarded-For`.
+    pub async fn handle(mut self, request: Request) -> Result<VercelResponse<Body>, Error> {
+        self.data.validate()?;
 
-        let body = response
-            .read_bytes()
+        let startup_hooks = self.data.take_startup_hooks();
+        STARTUP
+            .get_or_init(|| async { startup_hooks.run().await.map_err(|e| e.to_string()) })
             .await
-            .expect("Response hasn't been set");
+            .clone()?;
+
+        let mut request = request.map(|b| b.to_vec());
+        let info = ConnectInfo::new()
+            .with_scheme(Scheme::HTTPS)
+            .with_version(request.version());
+        request.extensions_mut().insert(info);
+        let mut response = self.data.respond(request).await;
+
+        let body = match ResponseStream::take(&mut response) {
+            Some(stream) => stream.collect().await?.to_bytes(),
+            None => response
+                .read_bytes()
+                .await
+                .expect("Response hasn't been set"),
+        };
 
         let (parts, ..) = response.into_parts();
 